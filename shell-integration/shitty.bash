@@ -0,0 +1,24 @@
+# OSC 133 prompt marks and OSC 7 directory reports for shitty. Source this
+# from ~/.bashrc, after PROMPT_COMMAND and PS1 are set up (needs bash 4.4 or
+# later for PS0):
+#
+#     source /path/to/shitty/shell-integration/shitty.bash
+
+if [[ $- == *i* && -z ${_shitty_integration-} ]]; then
+    _shitty_integration=1
+
+    _shitty_prompt_command() {
+        local ret=$?
+        # bash can't tell an empty command line from a command, so this is
+        # sent every time; shitty ignores it when no output was marked
+        printf '\e]133;D;%d\a' "$ret"
+        local dir=${PWD//\%/%25}
+        printf '\e]7;file://%s%s\a' "$HOSTNAME" "${dir// /%20}"
+        printf '\e]133;A\a'
+        [[ $PS1 == *'\e]133;B'* ]] || PS1=$PS1'\[\e]133;B\a\]'
+        [[ $PS0 == *'\e]133;C'* ]] || PS0=$PS0'\e]133;C\a'
+        return "$ret"
+    }
+
+    PROMPT_COMMAND="_shitty_prompt_command${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+fi
@@ -0,0 +1,29 @@
+# OSC 133 prompt marks and OSC 7 directory reports for shitty. Source this
+# from ~/.config/fish/config.fish:
+#
+#     source /path/to/shitty/shell-integration/shitty.fish
+
+if status is-interactive; and not set -q _shitty_integration
+    set -g _shitty_integration 1
+
+    function _shitty_prompt_start --on-event fish_prompt
+        set -l dir (string replace -a '%' '%25' -- $PWD | string replace -a ' ' '%20')
+        printf '\e]7;file://%s%s\a' $hostname $dir
+        printf '\e]133;A\a'
+    end
+
+    function _shitty_preexec --on-event fish_preexec
+        printf '\e]133;C\a'
+    end
+
+    function _shitty_postexec --on-event fish_postexec
+        printf '\e]133;D;%d\a' $status
+    end
+
+    # Typing starts where the prompt ends
+    functions -c fish_prompt _shitty_original_prompt
+    function fish_prompt
+        _shitty_original_prompt
+        printf '\e]133;B\a'
+    end
+end
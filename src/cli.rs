@@ -0,0 +1,191 @@
+// Command-line options. They follow xterm and alacritty, so the terminal can
+// stand in for either in scripts and keybindings.
+
+use std::ffi::OsString;
+use std::fmt;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: shitty [OPTIONS] [-e COMMAND [ARGS...]]
+
+Options:
+  -e, --command COMMAND [ARGS...]  Run COMMAND instead of the shell. Every
+                                   argument after it is passed to COMMAND.
+      --working-directory DIR      Start the shell or command in DIR
+  -T, --title TITLE                Window title while the program sets none
+      --class CLASS                Window class (X11) or app id (Wayland)
+  -g, --geometry COLSxROWS         Initial window size in cells, e.g. 120x40
+      --hold                       Keep the window open after the shell or
+                                   command exits
+      --config PATH                Read the configuration from PATH
+  -h, --help                       Print this help and exit
+  -V, --version                    Print the version and exit
+";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Options {
+    /// Program and arguments to run instead of the shell
+    pub command: Vec<String>,
+    pub working_directory: Option<PathBuf>,
+    pub title: Option<String>,
+    pub class: Option<String>,
+    /// Columns and rows
+    pub geometry: Option<(u16, u16)>,
+    pub hold: bool,
+    pub config: Option<PathBuf>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Action {
+    Run(Options),
+    Help,
+    Version,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct CliError(String);
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+/// Parses the arguments after the program name. Options that take a value
+/// accept it as the next argument or after `=`.
+pub fn parse_args(args: impl IntoIterator<Item = OsString>) -> Result<Action, CliError> {
+    let mut args = args
+        .into_iter()
+        .map(|arg| {
+            arg.into_string()
+                .map_err(|arg| CliError(format!("argument '{}' is not valid UTF-8", arg.display())))
+        })
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter();
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        if inline_value.is_some() && matches!(name, "--hold" | "--help" | "--version") {
+            return Err(CliError(format!("option '{name}' doesn't take a value")));
+        }
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| CliError(format!("option '{name}' needs a value")))
+        };
+        match name {
+            "-e" | "--command" => {
+                // Like xterm, everything that follows belongs to the command
+                options.command = inline_value.into_iter().chain(args.by_ref()).collect();
+                if options.command.is_empty() {
+                    return Err(CliError(format!("option '{name}' needs a command")));
+                }
+            }
+            "--working-directory" => options.working_directory = Some(value()?.into()),
+            "-T" | "--title" => options.title = Some(value()?),
+            "--class" => options.class = Some(value()?),
+            "-g" | "--geometry" => options.geometry = Some(parse_geometry(&value()?)?),
+            "--config" => options.config = Some(value()?.into()),
+            "--hold" => options.hold = true,
+            "-h" | "--help" => return Ok(Action::Help),
+            "-V" | "--version" => return Ok(Action::Version),
+            _ if name.starts_with('-') => {
+                return Err(CliError(format!("unknown option '{name}'")));
+            }
+            _ => return Err(CliError(format!("unexpected argument '{arg}'"))),
+        }
+    }
+    Ok(Action::Run(options))
+}
+
+fn parse_geometry(text: &str) -> Result<(u16, u16), CliError> {
+    let size = text.split_once(['x', 'X']).and_then(|(cols, rows)| {
+        let cols = cols.parse::<u16>().ok().filter(|&cols| cols > 0)?;
+        let rows = rows.parse::<u16>().ok().filter(|&rows| rows > 0)?;
+        Some((cols, rows))
+    });
+    size.ok_or_else(|| {
+        CliError(format!(
+            "invalid geometry '{text}', expected COLSxROWS such as 120x40"
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Action, CliError> {
+        parse_args(args.iter().map(OsString::from))
+    }
+
+    fn run(args: &[&str]) -> Options {
+        match parse(args) {
+            Ok(Action::Run(options)) => options,
+            other => panic!("expected options, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn options_take_separate_and_inline_values() {
+        let options = run(&[
+            "--title",
+            "logs",
+            "--working-directory=/tmp",
+            "-g",
+            "120x40",
+            "--class=scratch",
+            "--hold",
+            "--config",
+            "/etc/shitty.toml",
+        ]);
+        assert_eq!(
+            options,
+            Options {
+                command: Vec::new(),
+                working_directory: Some("/tmp".into()),
+                title: Some("logs".to_string()),
+                class: Some("scratch".to_string()),
+                geometry: Some((120, 40)),
+                hold: true,
+                config: Some("/etc/shitty.toml".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn command_takes_every_argument_after_it() {
+        let options = run(&["-T", "top", "-e", "htop", "--title", "x", "-d", "5"]);
+        assert_eq!(options.title.as_deref(), Some("top"));
+        assert_eq!(options.command, ["htop", "--title", "x", "-d", "5"]);
+    }
+
+    #[test]
+    fn help_and_version_win() {
+        assert_eq!(parse(&["--hold", "-h"]), Ok(Action::Help));
+        assert_eq!(parse(&["--version"]), Ok(Action::Version));
+    }
+
+    #[test]
+    fn mistakes_are_explained() {
+        let error = |args: &[&str]| parse(args).unwrap_err().to_string();
+        assert_eq!(error(&["--bogus"]), "unknown option '--bogus'");
+        assert_eq!(error(&["stray"]), "unexpected argument 'stray'");
+        assert_eq!(error(&["--title"]), "option '--title' needs a value");
+        assert_eq!(error(&["-e"]), "option '-e' needs a command");
+        assert_eq!(
+            error(&["--hold=yes"]),
+            "option '--hold' doesn't take a value"
+        );
+        assert_eq!(
+            error(&["--geometry", "120x0"]),
+            "invalid geometry '120x0', expected COLSxROWS such as 120x40"
+        );
+    }
+}
@@ -0,0 +1,924 @@
+// The configuration file, `$XDG_CONFIG_HOME/shitty/config.toml` unless
+// `--config` names another. Every key is optional. Keys that are unknown or
+// hold a bad value are reported with their place in the file and otherwise
+// ignored, so one typo doesn't throw away the rest of the file.
+
+use crate::cli::Options;
+use crate::terminal::bindings::{KeyAction, KeyBindings, KeyChord};
+use crate::terminal::clipboard::{ClipboardAccess, ClipboardPolicy};
+use crate::terminal::color::{Color32, Theme, parse_color_spec};
+use crate::terminal::cwd::expand_home;
+use crate::terminal::detect::{ClickAction, DetectorConfig};
+use crate::terminal::grid::DEFAULT_SCROLLBACK_LINES;
+use crate::terminal::hyperlink::LinkOpener;
+use crate::terminal::keymap::{AltMode, KeyEncoder};
+use crate::terminal::selection::DEFAULT_WORD_DELIMITERS;
+use crate::terminal::shell::ShellConfig;
+use crate::terminal::theme::load_theme;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::{Range, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+use toml_edit::{Document, Item, TableLike};
+
+pub(crate) const DEFAULT_FONT_SIZE: f32 = 14.0;
+
+/// Frames per second, enough for smooth scrolling on common displays
+pub(crate) const DEFAULT_REFRESH_RATE: f64 = 120.0;
+
+/// Most lines of scrollback a window may keep
+const MAX_SCROLLBACK_LINES: i64 = 1_000_000;
+
+/// How often the watcher looks at the file
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum CursorStyle {
+    #[default]
+    Block,
+    Underline,
+    Bar,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FontConfig {
+    /// A TrueType or OpenType file used instead of the bundled font
+    pub(crate) path: Option<PathBuf>,
+    /// Size in points
+    pub(crate) size: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Config {
+    pub(crate) font: FontConfig,
+    /// Space between the window's edges and the text, in points
+    pub(crate) padding: f32,
+    pub(crate) theme: Theme,
+    pub(crate) cursor_style: CursorStyle,
+    /// Lines kept above the screen. Changes apply to new windows.
+    pub(crate) scrollback_lines: usize,
+    /// Most frames drawn per second
+    pub(crate) refresh_rate: f64,
+    /// Changes apply to new windows, the running shell is left alone
+    pub(crate) shell: ShellConfig,
+    pub(crate) keybindings: KeyBindings,
+    /// What each Alt key does to the keys typed with it
+    pub(crate) key_encoder: KeyEncoder,
+    pub(crate) word_delimiters: String,
+    pub(crate) clipboard: ClipboardPolicy,
+    pub(crate) link_opener: LinkOpener,
+    pub(crate) detector: DetectorConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            font: FontConfig {
+                path: None,
+                size: DEFAULT_FONT_SIZE,
+            },
+            padding: 0.0,
+            theme: Theme::default(),
+            cursor_style: CursorStyle::default(),
+            scrollback_lines: DEFAULT_SCROLLBACK_LINES,
+            refresh_rate: DEFAULT_REFRESH_RATE,
+            shell: ShellConfig::default(),
+            keybindings: KeyBindings::default(),
+            key_encoder: KeyEncoder::default(),
+            word_delimiters: DEFAULT_WORD_DELIMITERS.to_string(),
+            clipboard: ClipboardPolicy::default(),
+            link_opener: LinkOpener::default(),
+            detector: DetectorConfig::default(),
+        }
+    }
+}
+
+/// A mistake in the configuration file, or a failure to read it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ConfigError {
+    pub(crate) path: PathBuf,
+    /// Line and column, counted from 1, when the mistake has a place in the
+    /// file
+    pub(crate) position: Option<(usize, usize)>,
+    pub(crate) message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some((line, col)) => {
+                write!(f, "{}:{line}:{col}: {}", self.path.display(), self.message)
+            }
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// The file to read: the one given with `--config`, or else the one in the
+/// user's config directory
+pub(crate) fn config_path(options: &Options) -> Option<PathBuf> {
+    options.config.clone().or_else(|| {
+        default_path(
+            std::env::var_os("XDG_CONFIG_HOME"),
+            std::env::var_os("HOME"),
+        )
+    })
+}
+
+fn default_path(xdg_config_home: Option<OsString>, home: Option<OsString>) -> Option<PathBuf> {
+    // The XDG spec says relative paths are to be ignored
+    let dir = xdg_config_home
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| {
+            home.filter(|home| !home.is_empty())
+                .map(|home| Path::new(&home).join(".config"))
+        })?;
+    Some(dir.join("shitty").join("config.toml"))
+}
+
+impl Config {
+    /// Reads the configuration at `path`. A missing file leaves everything
+    /// at its default, and is only an error when `required`, as it is for a
+    /// file named on the command line.
+    pub(crate) fn load(path: &Path, required: bool) -> (Config, Vec<ConfigError>) {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text, path),
+            Err(err) if err.kind() == io::ErrorKind::NotFound && !required => {
+                (Config::default(), Vec::new())
+            }
+            Err(err) => {
+                let error = ConfigError {
+                    path: path.to_path_buf(),
+                    position: None,
+                    message: err.to_string(),
+                };
+                (Config::default(), vec![error])
+            }
+        }
+    }
+
+    /// Parses `text`, read from `path`. A syntax error leaves everything at
+    /// its default.
+    pub(crate) fn parse(text: &str, path: &Path) -> (Config, Vec<ConfigError>) {
+        let mut parser = Parser {
+            text,
+            path,
+            section: String::new(),
+            errors: Vec::new(),
+        };
+        let mut config = Config::default();
+        match Document::parse(text) {
+            Ok(document) => parser.config(document.as_table(), &mut config),
+            Err(err) => parser.error(err.span(), err.message().trim_end()),
+        }
+        (config, parser.errors)
+    }
+}
+
+/// Watches the file at `path` and calls `changed` with what it holds each
+/// time it is written, created or removed, until `changed` returns false.
+/// The file is polled, since editors that save by renaming a new file over
+/// the old one get past most change notifications.
+pub(crate) fn watch(
+    path: PathBuf,
+    required: bool,
+    mut changed: impl FnMut(Config, Vec<ConfigError>) -> bool + Send + 'static,
+) {
+    thread::spawn(move || {
+        let stamp = |path: &Path| {
+            fs::metadata(path)
+                .ok()
+                .map(|metadata| (metadata.modified().ok(), metadata.len()))
+        };
+        let mut last = stamp(&path);
+        loop {
+            thread::sleep(WATCH_INTERVAL);
+            let current = stamp(&path);
+            if current == last {
+                continue;
+            }
+            last = current;
+            let (config, errors) = Config::load(&path, required);
+            if !changed(config, errors) {
+                break;
+            }
+        }
+    });
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    path: &'a Path,
+    // Dotted name of the table being read, empty at the top level
+    section: String,
+    errors: Vec<ConfigError>,
+}
+
+impl Parser<'_> {
+    fn config(&mut self, root: &dyn TableLike, config: &mut Config) {
+        self.fields(root, |p, key, item| {
+            match key {
+                "scrollback_lines" => {
+                    if let Some(lines) = p.integer(item, key, 0..=MAX_SCROLLBACK_LINES) {
+                        config.scrollback_lines = lines as usize;
+                    }
+                }
+                "refresh_rate" => {
+                    if let Some(rate) = p.number(item, key, 1.0..=1000.0) {
+                        config.refresh_rate = rate;
+                    }
+                }
+                "font" => p.table(item, key, |p, key, item| {
+                    match key {
+                        "path" => {
+                            if let Some(path) = p.string(item, key) {
+                                config.font.path = Some(expand_home(&path));
+                            }
+                        }
+                        "size" => {
+                            if let Some(size) = p.number(item, key, 4.0..=200.0) {
+                                config.font.size = size as f32;
+                            }
+                        }
+                        _ => return false,
+                    }
+                    true
+                }),
+                "window" => p.table(item, key, |p, key, item| {
+                    match key {
+                        "padding" => {
+                            if let Some(padding) = p.number(item, key, 0.0..=200.0) {
+                                config.padding = padding as f32;
+                            }
+                        }
+                        _ => return false,
+                    }
+                    true
+                }),
+                "colors" => {
+                    // The theme is read first, so the colors beside it override
+                    // its own wherever they are written
+                    if let Some(theme) = item.as_table_like().and_then(|table| table.get("theme")) {
+                        p.theme_file(theme, &mut config.theme);
+                    }
+                    p.table(item, key, |p, key, item| {
+                        key == "theme" || p.color_field(key, item, config)
+                    })
+                }
+                "cursor" => p.table(item, key, |p, key, item| {
+                    match key {
+                        "style" => {
+                            let styles = [
+                                ("block", CursorStyle::Block),
+                                ("underline", CursorStyle::Underline),
+                                ("bar", CursorStyle::Bar),
+                            ];
+                            if let Some(style) = p.choice(item, key, &styles) {
+                                config.cursor_style = style;
+                            }
+                        }
+                        _ => return false,
+                    }
+                    true
+                }),
+                "keyboard" => p.table(item, key, |p, key, item| {
+                    let modes = [
+                        ("escape", AltMode::Escape),
+                        ("8bit", AltMode::EightBit),
+                        ("compose", AltMode::Compose),
+                    ];
+                    let side = match key {
+                        "left_alt" => &mut config.key_encoder.left_alt,
+                        "right_alt" => &mut config.key_encoder.right_alt,
+                        _ => return false,
+                    };
+                    if let Some(mode) = p.choice(item, key, &modes) {
+                        *side = mode;
+                    }
+                    true
+                }),
+                "shell" => p.table(item, key, |p, key, item| {
+                    p.shell_field(key, item, &mut config.shell)
+                }),
+                "selection" => p.table(item, key, |p, key, item| {
+                    match key {
+                        "word_delimiters" => {
+                            if let Some(delimiters) = p.string(item, key) {
+                                config.word_delimiters = delimiters;
+                            }
+                        }
+                        _ => return false,
+                    }
+                    true
+                }),
+                "clipboard" => p.table(item, key, |p, key, item| {
+                    let access = [
+                        ("allow", ClipboardAccess::Allow),
+                        ("ask", ClipboardAccess::Ask),
+                        ("deny", ClipboardAccess::Deny),
+                    ];
+                    let direction = match key {
+                        "read" => &mut config.clipboard.read,
+                        "write" => &mut config.clipboard.write,
+                        _ => return false,
+                    };
+                    if let Some(access) = p.choice(item, key, &access) {
+                        *direction = access;
+                    }
+                    true
+                }),
+                "links" => p.table(item, key, |p, key, item| p.link_field(key, item, config)),
+                "keybindings" => p.table(item, key, |p, key, item| {
+                    let Some(action) = KeyAction::from_name(key) else {
+                        return false;
+                    };
+                    if let Some(chords) = p.chords(item, key) {
+                        config.keybindings.bind(action, chords);
+                    }
+                    true
+                }),
+                _ => return false,
+            }
+            true
+        });
+    }
+
+    /// Loads the theme `colors.theme` names. A relative path is taken from
+    /// the directory the configuration is in.
+    fn theme_file(&mut self, item: &Item, theme: &mut Theme) {
+        let Some(path) = self.string(item, "colors.theme") else {
+            return;
+        };
+        let dir = self.path.parent().unwrap_or(Path::new(""));
+        let path = dir.join(expand_home(&path));
+        match load_theme(&path) {
+            Ok(loaded) => *theme = loaded,
+            Err(err) => {
+                let message = format!("can't load theme {}: {err}", path.display());
+                self.error(item.span(), message);
+            }
+        }
+    }
+
+    fn color_field(&mut self, key: &str, item: &Item, config: &mut Config) -> bool {
+        let theme = &mut config.theme;
+        let optional = |color: Option<Color32>, slot: &mut Option<Color32>| {
+            if color.is_some() {
+                *slot = color;
+            }
+        };
+        match key {
+            "foreground" => {
+                if let Some(color) = self.color(item, key) {
+                    theme.foreground = color;
+                }
+            }
+            "background" => {
+                if let Some(color) = self.color(item, key) {
+                    theme.background = color;
+                }
+            }
+            "cursor" => optional(self.color(item, key), &mut theme.cursor),
+            "cursor_text" => optional(self.color(item, key), &mut theme.cursor_text),
+            "selection_foreground" => {
+                optional(self.color(item, key), &mut theme.selection_foreground)
+            }
+            "selection_background" => {
+                optional(self.color(item, key), &mut theme.selection_background)
+            }
+            // Colors from 0 up; the ones left out keep their default
+            "palette" => {
+                let Some(colors) = self.array(item, key) else {
+                    return true;
+                };
+                if colors.len() > theme.palette.len() {
+                    let message = format!("`{}` holds at most 256 colors", self.name(key));
+                    self.error(item.span(), message);
+                    return true;
+                }
+                for (index, color) in colors.iter().enumerate() {
+                    if let Some(color) = self.color(color, key) {
+                        theme.palette[index] = color;
+                    }
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn shell_field(&mut self, key: &str, item: &Item, shell: &mut ShellConfig) -> bool {
+        match key {
+            "program" => {
+                if let Some(program) = self.string(item, key) {
+                    shell.program = Some(expand_home(&program));
+                }
+            }
+            "args" => {
+                if let Some(args) = self.strings(item, key) {
+                    shell.args = args;
+                }
+            }
+            "login" => {
+                if let Some(login) = self.boolean(item, key) {
+                    shell.login = login;
+                }
+            }
+            "working_directory" => {
+                if let Some(dir) = self.string(item, key) {
+                    shell.working_dir = Some(expand_home(&dir));
+                }
+            }
+            "env" => self.table(item, key, |p, key, item| {
+                if let Some(value) = p.string(item, key) {
+                    shell.env.push((key.to_string(), value));
+                }
+                true
+            }),
+            _ => return false,
+        }
+        true
+    }
+
+    fn link_field(&mut self, key: &str, item: &Item, config: &mut Config) -> bool {
+        let detector = &mut config.detector;
+        let action = match key {
+            "opener" => {
+                if let Some((program, args)) = self.command(item, key) {
+                    config.link_opener = LinkOpener { program, args };
+                }
+                return true;
+            }
+            "editor" => {
+                if let Some((program, args)) = self.command(item, key) {
+                    detector.editor = [vec![program], args].concat();
+                }
+                return true;
+            }
+            "url" => &mut detector.url,
+            "path" => &mut detector.path,
+            "ip" => &mut detector.ip,
+            "git_sha" => &mut detector.git_sha,
+            _ => return false,
+        };
+        let actions = [
+            ("open", ClickAction::Open),
+            ("copy", ClickAction::Copy),
+            ("edit", ClickAction::Edit),
+        ];
+        if let Some(chosen) = self.choice(item, key, &actions) {
+            *action = chosen;
+        }
+        true
+    }
+
+    /// Runs `field` for each key of the table in `item`. Keys it doesn't
+    /// know, which it signals by returning false, are reported.
+    fn table(&mut self, item: &Item, key: &str, field: impl FnMut(&mut Self, &str, &Item) -> bool) {
+        let Some(table) = item.as_table_like() else {
+            self.expected(item, key, "a table");
+            return;
+        };
+        let name = self.name(key);
+        let outer = std::mem::replace(&mut self.section, name);
+        self.fields(table, field);
+        self.section = outer;
+    }
+
+    fn fields(
+        &mut self,
+        table: &dyn TableLike,
+        mut field: impl FnMut(&mut Self, &str, &Item) -> bool,
+    ) {
+        for (key, item) in table.iter() {
+            if !field(self, key, item) {
+                let span = table.get_key_value(key).and_then(|(key, _)| key.span());
+                let message = format!("unknown key `{}`", self.name(key));
+                self.error(span, message);
+            }
+        }
+    }
+
+    /// Dotted name of `key` in the current table
+    fn name(&self, key: &str) -> String {
+        if self.section.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{key}", self.section)
+        }
+    }
+
+    fn error(&mut self, span: Option<Range<usize>>, message: impl Into<String>) {
+        let position = span.map(|span| {
+            let before = &self.text[..span.start.min(self.text.len())];
+            let line = before.matches('\n').count() + 1;
+            let col = before
+                .rsplit('\n')
+                .next()
+                .unwrap_or_default()
+                .chars()
+                .count()
+                + 1;
+            (line, col)
+        });
+        self.errors.push(ConfigError {
+            path: self.path.to_path_buf(),
+            position,
+            message: message.into(),
+        });
+    }
+
+    fn expected(&mut self, item: &Item, key: &str, what: &str) {
+        let message = format!(
+            "expected {what} for `{}`, found {}",
+            self.name(key),
+            item.type_name()
+        );
+        self.error(item.span(), message);
+    }
+
+    fn string(&mut self, item: &Item, key: &str) -> Option<String> {
+        let value = item.as_str().map(str::to_string);
+        if value.is_none() {
+            self.expected(item, key, "a string");
+        }
+        value
+    }
+
+    fn boolean(&mut self, item: &Item, key: &str) -> Option<bool> {
+        let value = item.as_bool();
+        if value.is_none() {
+            self.expected(item, key, "true or false");
+        }
+        value
+    }
+
+    fn integer(&mut self, item: &Item, key: &str, range: RangeInclusive<i64>) -> Option<i64> {
+        let Some(value) = item.as_integer() else {
+            self.expected(item, key, "a whole number");
+            return None;
+        };
+        self.in_range(item, key, value, range)
+    }
+
+    /// A number with or without a fraction
+    fn number(&mut self, item: &Item, key: &str, range: RangeInclusive<f64>) -> Option<f64> {
+        let value = item
+            .as_float()
+            .or_else(|| item.as_integer().map(|value| value as f64));
+        let Some(value) = value else {
+            self.expected(item, key, "a number");
+            return None;
+        };
+        self.in_range(item, key, value, range)
+    }
+
+    fn in_range<T: PartialOrd + fmt::Display>(
+        &mut self,
+        item: &Item,
+        key: &str,
+        value: T,
+        range: RangeInclusive<T>,
+    ) -> Option<T> {
+        if range.contains(&value) {
+            return Some(value);
+        }
+        let message = format!(
+            "`{}` must be between {} and {}",
+            self.name(key),
+            range.start(),
+            range.end()
+        );
+        self.error(item.span(), message);
+        None
+    }
+
+    fn choice<T: Copy>(&mut self, item: &Item, key: &str, choices: &[(&str, T)]) -> Option<T> {
+        let text = self.string(item, key)?;
+        let found = choices
+            .iter()
+            .find(|(name, _)| *name == text)
+            .map(|(_, value)| *value);
+        if found.is_none() {
+            let names: Vec<_> = choices
+                .iter()
+                .map(|(name, _)| format!("\"{name}\""))
+                .collect();
+            let message = format!(
+                "`{}` must be one of {}, not \"{text}\"",
+                self.name(key),
+                names.join(", ")
+            );
+            self.error(item.span(), message);
+        }
+        found
+    }
+
+    fn color(&mut self, item: &Item, key: &str) -> Option<Color32> {
+        let text = self.string(item, key)?;
+        let color = parse_color_spec(&text);
+        if color.is_none() {
+            let message = format!(
+                "`{}` must be a color such as \"#d79921\", not \"{text}\"",
+                self.name(key)
+            );
+            self.error(item.span(), message);
+        }
+        color
+    }
+
+    /// The values of an array, as items the other readers take
+    fn array(&mut self, item: &Item, key: &str) -> Option<Vec<Item>> {
+        let Some(array) = item.as_array() else {
+            self.expected(item, key, "an array");
+            return None;
+        };
+        Some(array.iter().cloned().map(Item::Value).collect())
+    }
+
+    fn strings(&mut self, item: &Item, key: &str) -> Option<Vec<String>> {
+        let items = self.array(item, key)?;
+        let strings: Vec<_> = items.iter().map(|item| self.string(item, key)).collect();
+        strings.into_iter().collect()
+    }
+
+    /// A program and its arguments
+    fn command(&mut self, item: &Item, key: &str) -> Option<(String, Vec<String>)> {
+        let mut command = self.strings(item, key)?;
+        if command.is_empty() {
+            let message = format!("`{}` needs at least a program", self.name(key));
+            self.error(item.span(), message);
+            return None;
+        }
+        let program = command.remove(0);
+        Some((program, command))
+    }
+
+    /// One chord or a list of them. "none" or an empty list leaves the
+    /// action without keys.
+    fn chords(&mut self, item: &Item, key: &str) -> Option<Vec<KeyChord>> {
+        let items = match item.as_str() {
+            Some("none") => return Some(Vec::new()),
+            Some(_) => vec![item.clone()],
+            None if item.is_array() => self.array(item, key)?,
+            None => {
+                self.expected(
+                    item,
+                    key,
+                    "a key such as \"ctrl+shift+f\" or a list of them",
+                );
+                return None;
+            }
+        };
+        let mut chords = Vec::new();
+        for item in &items {
+            let text = self.string(item, key)?;
+            match KeyChord::parse(&text) {
+                Ok(chord) => chords.push(chord),
+                Err(err) => {
+                    let message = format!("`{}`: {err}", self.name(key));
+                    self.error(item.span(), message);
+                    return None;
+                }
+            }
+        }
+        Some(chords)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> (Config, Vec<String>) {
+        let (config, errors) = Config::parse(text, Path::new("config.toml"));
+        (config, errors.iter().map(ToString::to_string).collect())
+    }
+
+    #[test]
+    fn every_section_is_read() {
+        let (config, errors) = parse(
+            r##"
+scrollback_lines = 500
+refresh_rate = 60
+
+[font]
+path = "/usr/share/fonts/iosevka.ttf"
+size = 16.5
+
+[window]
+padding = 8
+
+[colors]
+foreground = "#ebdbb2"
+background = "rgb:28/28/28"
+cursor = "#fe8019"
+palette = ["#282828", "#cc241d"]
+
+[cursor]
+style = "bar"
+
+[keyboard]
+left_alt = "8bit"
+right_alt = "escape"
+
+[shell]
+program = "/bin/zsh"
+args = ["-i"]
+login = true
+env = { EDITOR = "nvim" }
+
+[selection]
+word_delimiters = " "
+
+[clipboard]
+read = "ask"
+
+[links]
+opener = ["firefox", "--new-tab"]
+editor = ["nvim", "+{line}", "{file}"]
+path = "edit"
+
+[keybindings]
+search = "ctrl+alt+f"
+hints = ["ctrl+shift+e", "ctrl+shift+h"]
+new_window = "none"
+"##,
+        );
+        assert_eq!(errors, Vec::<String>::new());
+        assert_eq!(config.scrollback_lines, 500);
+        assert_eq!(config.refresh_rate, 60.0);
+        assert_eq!(
+            config.font,
+            FontConfig {
+                path: Some(PathBuf::from("/usr/share/fonts/iosevka.ttf")),
+                size: 16.5,
+            }
+        );
+        assert_eq!(config.padding, 8.0);
+        assert_eq!(config.theme.background, Color32::from_rgb(0x28, 0x28, 0x28));
+        assert_eq!(
+            config.theme.cursor,
+            Some(Color32::from_rgb(0xfe, 0x80, 0x19))
+        );
+        assert_eq!(config.theme.palette[1], Color32::from_rgb(0xcc, 0x24, 0x1d));
+        assert_eq!(config.theme.palette[2], Theme::default().palette[2]);
+        assert_eq!(config.cursor_style, CursorStyle::Bar);
+        assert_eq!(
+            config.key_encoder,
+            KeyEncoder {
+                left_alt: AltMode::EightBit,
+                right_alt: AltMode::Escape,
+            }
+        );
+        assert_eq!(config.shell.program, Some(PathBuf::from("/bin/zsh")));
+        assert!(config.shell.login);
+        assert_eq!(
+            config.shell.env,
+            [("EDITOR".to_string(), "nvim".to_string())]
+        );
+        assert_eq!(config.word_delimiters, " ");
+        assert_eq!(config.clipboard.read, ClipboardAccess::Ask);
+        assert_eq!(config.clipboard.write, ClipboardAccess::Allow);
+        assert_eq!(config.link_opener.args, ["--new-tab"]);
+        assert_eq!(config.detector.editor, ["nvim", "+{line}", "{file}"]);
+        assert_eq!(config.detector.path, ClickAction::Edit);
+
+        let chord = |text| KeyChord::parse(text).unwrap();
+        let bindings = &config.keybindings;
+        assert_eq!(
+            bindings.action(&chord("ctrl+alt+f")),
+            Some(KeyAction::Search)
+        );
+        assert_eq!(bindings.action(&chord("ctrl+shift+f")), None);
+        assert_eq!(
+            bindings.action(&chord("ctrl+shift+h")),
+            Some(KeyAction::Hints)
+        );
+        assert_eq!(bindings.action(&chord("ctrl+shift+n")), None);
+    }
+
+    #[test]
+    fn mistakes_are_reported_where_they_are() {
+        let (config, errors) = parse(
+            r##"refresh_rate = 0
+colour = "red"
+
+[font]
+size = "big"
+
+[colors]
+foreground = "#zzzzzz"
+background = "#101010"
+
+[cursor]
+style = "beam"
+
+[keyboard]
+right_alt = "meta"
+
+[keybindings]
+search = "ctrl+banana"
+paste = "ctrl+v"
+"##,
+        );
+        assert_eq!(
+            errors,
+            [
+                "config.toml:1:16: `refresh_rate` must be between 1 and 1000",
+                "config.toml:2:1: unknown key `colour`",
+                "config.toml:5:8: expected a number for `font.size`, found string",
+                "config.toml:8:14: `colors.foreground` must be a color such as \"#d79921\", \
+                 not \"#zzzzzz\"",
+                "config.toml:12:9: `cursor.style` must be one of \"block\", \"underline\", \
+                 \"bar\", not \"beam\"",
+                "config.toml:15:13: `keyboard.right_alt` must be one of \"escape\", \"8bit\", \
+                 \"compose\", not \"meta\"",
+                "config.toml:18:10: `keybindings.search`: unknown key 'banana' in 'ctrl+banana'",
+                "config.toml:19:1: unknown key `keybindings.paste`",
+            ]
+        );
+        // The rest still applies
+        assert_eq!(config.theme.background, Color32::from_rgb(0x10, 0x10, 0x10));
+        assert_eq!(config.font.size, DEFAULT_FONT_SIZE);
+        assert_eq!(config.keybindings, KeyBindings::default());
+    }
+
+    #[test]
+    fn syntax_errors_leave_the_defaults() {
+        let (config, errors) = parse("[font]\nsize = 12\npadding 4\n");
+        assert_eq!(config, Config::default());
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("config.toml:3:"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn colors_beside_a_theme_override_it() {
+        let dir = std::env::temp_dir().join(format!("shitty-theme-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("kanagawa.conf"),
+            "foreground #dcd7ba\nbackground #1f1f28\nselection_background #2d4f67\n",
+        )
+        .unwrap();
+        let text = "[colors]\nforeground = \"#010101\"\ntheme = \"kanagawa.conf\"\n";
+        let (config, errors) = Config::parse(text, &dir.join("config.toml"));
+        let missing = Config::parse(
+            "[colors]\ntheme = \"gone.conf\"\n",
+            &dir.join("config.toml"),
+        );
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(errors, Vec::new());
+        assert_eq!(config.theme.foreground, Color32::from_rgb(1, 1, 1));
+        assert_eq!(config.theme.background, Color32::from_rgb(0x1f, 0x1f, 0x28));
+        assert_eq!(
+            config.theme.selection_background,
+            Some(Color32::from_rgb(0x2d, 0x4f, 0x67))
+        );
+        let (config, errors) = missing;
+        assert_eq!(config.theme, Theme::default());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].position, Some((2, 9)));
+        assert!(
+            errors[0].message.starts_with("can't load theme "),
+            "{}",
+            errors[0]
+        );
+    }
+
+    #[test]
+    fn config_lives_in_the_xdg_config_dir() {
+        let path = |xdg: Option<&str>, home: Option<&str>| {
+            default_path(xdg.map(OsString::from), home.map(OsString::from))
+        };
+        assert_eq!(
+            path(Some("/xdg"), Some("/home/me")),
+            Some(PathBuf::from("/xdg/shitty/config.toml"))
+        );
+        assert_eq!(
+            path(Some("relative"), Some("/home/me")),
+            Some(PathBuf::from("/home/me/.config/shitty/config.toml"))
+        );
+        assert_eq!(path(None, None), None);
+    }
+
+    #[test]
+    fn only_a_required_file_has_to_exist() {
+        let missing = Path::new("/nonexistent/shitty/config.toml");
+        assert_eq!(
+            Config::load(missing, false),
+            (Config::default(), Vec::new())
+        );
+        let (_, errors) = Config::load(missing, true);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].position, None);
+    }
+}
@@ -5,22 +5,73 @@ use nix::libc::{ioctl, setsid, TIOCSCTTY};
 use nix::pty::openpty;
 use nix::unistd::{read, write};
 use std::fs;
+use std::io;
 use std::os::fd::{AsFd, AsRawFd, OwnedFd};
 use std::os::unix::process::CommandExt;
-use std::process::Command;
-use std::sync::mpsc::{channel, Receiver, Sender};
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::terminal::keymap::append_input_from_event;
+use crate::cli::Options;
+use crate::config::{self, config_path, Config, ConfigError, CursorStyle, FontConfig};
+use crate::terminal::bindings::{KeyAction, KeyBindings, KeyChord};
+use crate::terminal::clipboard::{clipboard_reply, queue_unconfirmed, ClipboardRequest};
+use crate::terminal::cwd::{display_dir, foreground_pgid, process_group_dir};
+use crate::terminal::detect::{ClickAction, DetectedMatch, DetectorConfig};
+use crate::terminal::keymap::{append_input_from_event, KeyEncoder};
+use crate::terminal::mouse::{encode_mouse_event, MouseAction, MouseButton, MouseModifiers};
 use crate::terminal::pty::{apply_resize, PtyEvent};
+use crate::terminal::search::{SearchHighlight, SearchQuery};
 use crate::terminal::grid::TerminalGrid;
+use crate::terminal::hints::{HintAction, HintInput, HintsMode};
+use crate::terminal::hyperlink::LinkOpener;
+use crate::terminal::selection::SelectionMode;
+use crate::terminal::shell::ShellConfig;
+use crate::terminal::vi_mode::{ViAction, ViKey, ViMode};
 
-pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// Window title used until the running program sets its own
+const APP_TITLE: &str = "shitty";
+
+/// Longest gap between clicks that still counts as a double or triple click
+const MULTI_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+/// How long the working directory read for a foreground process is trusted,
+/// since a `cd` doesn't change the process group
+const FOREGROUND_DIR_TTL: Duration = Duration::from_secs(1);
+/// Hint labels in black on yellow, so they stand out from any theme
+const HINT_FG: egui::Color32 = egui::Color32::BLACK;
+const HINT_BG: egui::Color32 = egui::Color32::from_rgb(0xf5, 0xc2, 0x11);
+/// Search matches in black on amber, the current one on orange
+const SEARCH_FG: crate::terminal::color::Color32 = crate::terminal::color::Color32::BLACK;
+const SEARCH_MATCH_BG: crate::terminal::color::Color32 =
+    crate::terminal::color::Color32::from_rgb(0xb8, 0x8a, 0x1e);
+const SEARCH_CURRENT_BG: crate::terminal::color::Color32 =
+    crate::terminal::color::Color32::from_rgb(0xf5, 0x7c, 0x00);
+/// Bar at the left edge of prompts whose command failed
+const FAILED_COMMAND_MARK: egui::Color32 = egui::Color32::from_rgb(0xe0, 0x3c, 0x31);
+const FAILED_COMMAND_MARK_WIDTH: f32 = 3.0;
+
+pub fn run(options: Options) -> Result<(), Box<dyn std::error::Error>> {
     let pty_result = openpty(None, None)?;
     let master_fd = pty_result.master;
     let slave_fd = pty_result.slave;
-    let shell_pgid = spawn_shell(&slave_fd);
+    let config_file = config_path(&options);
+    let config_required = options.config.is_some();
+    let (config, config_errors) = config_file
+        .as_deref()
+        .map(|path| Config::load(path, config_required))
+        .unwrap_or_default();
+    for error in &config_errors {
+        eprintln!("shitty: {error}");
+    }
+    let shell = config.shell.clone().with_options(&options);
+    let shell_pgid = spawn_shell(&slave_fd, &shell)
+        .map_err(|err| format!("failed to start {}: {err}", shell.program().display()))?;
+    // With the shell holding the only copies of the slave side, reads from
+    // the master fail once it exits
+    drop(slave_fd);
 
     let icon_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/icon.png");
     let icon_data = if let Ok(img) = image::open(&icon_path) {
@@ -35,26 +86,31 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
         None
     };
 
-    let mut viewport = egui::ViewportBuilder::default();
+    let mut viewport =
+        egui::ViewportBuilder::default().with_title(options.title.as_deref().unwrap_or(APP_TITLE));
     if let Some(icon) = icon_data {
         viewport = viewport.with_icon(icon);
     }
+    if let Some(class) = &options.class {
+        viewport = viewport.with_app_id(class);
+    }
 
     eframe::run_native(
-        "shitty",
+        APP_TITLE,
         eframe::NativeOptions {
             viewport,
             ..Default::default()
         },
-        Box::new(|cc| {
+        Box::new(move |cc| {
             configure_visuals(cc);
-            configure_fonts(cc);
 
             let (tx_pty_output, rx_pty_output) = channel::<Vec<u8>>();
             let (tx_pty_input, rx_pty_input) = channel::<PtyEvent>();
             let ctx = cc.egui_ctx.clone();
+            let frame_pacer = Arc::new(FramePacer::new(config.refresh_rate));
 
             let master_read = master_fd.try_clone().expect("master fd clone failed");
+            let master_query = master_fd.try_clone().expect("master fd clone failed");
             let master_write = master_fd;
 
             spawn_pty_threads(
@@ -63,10 +119,24 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
                 tx_pty_output,
                 rx_pty_input,
                 ctx,
+                Arc::clone(&frame_pacer),
                 shell_pgid,
             );
 
-            Ok(Box::new(TerminalUI::new(rx_pty_output, tx_pty_input)))
+            let mut terminal = TerminalUI::new(
+                rx_pty_output,
+                tx_pty_input,
+                master_query,
+                shell_pgid,
+                &options,
+                &config,
+                frame_pacer,
+            );
+            terminal.apply_config(&cc.egui_ctx, config, config_errors);
+            if let Some(path) = config_file {
+                terminal.watch_config(&cc.egui_ctx, path, config_required);
+            }
+            Ok(Box::new(terminal))
         }),
     )
     .map_err(Into::into)
@@ -79,26 +149,89 @@ fn configure_visuals(cc: &eframe::CreationContext<'_>) {
     });
 }
 
-fn configure_fonts(cc: &eframe::CreationContext<'_>) {
-    let font_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+/// Loads the configured font, or the bundled one without a configured font
+/// or when it can't be read, which is then reported
+fn configure_fonts(ctx: &egui::Context, font: &FontConfig) -> Result<(), String> {
+    let bundled = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
         .join("assets/MonacoNerdFontMono-Regular.ttf");
-    if let Ok(font_data) = fs::read(&font_path) {
-        let mut fonts = egui::FontDefinitions::default();
+    let (font_data, result) = match &font.path {
+        Some(path) => match fs::read(path) {
+            Ok(data) => (Ok(data), Ok(())),
+            Err(err) => (
+                fs::read(&bundled),
+                Err(format!("can't read font {}: {err}", path.display())),
+            ),
+        },
+        None => (fs::read(&bundled), Ok(())),
+    };
+    let mut fonts = egui::FontDefinitions::default();
+    if let Ok(font_data) = font_data {
         fonts.font_data.insert(
-            "monaco".to_string(),
+            "terminal".to_string(),
             egui::FontData::from_owned(font_data).into(),
         );
         fonts
             .families
-            .insert(egui::FontFamily::Monospace, vec!["monaco".to_string()]);
-        cc.egui_ctx.set_fonts(fonts);
+            .insert(egui::FontFamily::Monospace, vec!["terminal".to_string()]);
+    }
+    ctx.set_fonts(fonts);
+    result
+}
+
+/// Spaces out the repaints PTY output asks for, so a flood of output is
+/// drawn at the configured refresh rate rather than once per read
+struct FramePacer {
+    // Shortest time between frames, and when the last one was drawn
+    state: Mutex<(Duration, Instant)>,
+}
+
+impl FramePacer {
+    fn new(refresh_rate: f64) -> Self {
+        Self {
+            state: Mutex::new((frame_interval(refresh_rate), Instant::now())),
+        }
+    }
+
+    fn set_refresh_rate(&self, refresh_rate: f64) {
+        self.state.lock().unwrap().0 = frame_interval(refresh_rate);
+    }
+
+    fn frame_drawn(&self) {
+        self.state.lock().unwrap().1 = Instant::now();
+    }
+
+    fn request_repaint(&self, ctx: &egui::Context) {
+        let (interval, last_frame) = *self.state.lock().unwrap();
+        ctx.request_repaint_after(interval.saturating_sub(last_frame.elapsed()));
     }
 }
 
-fn spawn_shell(slave_fd: &OwnedFd) -> i32 {
+fn frame_interval(refresh_rate: f64) -> Duration {
+    Duration::from_secs_f64(1.0 / refresh_rate)
+}
+
+/// Starts another terminal window as a separate process, with its shell in
+/// `dir` and its configuration read from `config`.
+fn open_new_window(dir: Option<&Path>, config: Option<&Path>) -> io::Result<()> {
+    let mut command = Command::new(std::env::current_exe()?);
+    if let Some(dir) = dir.filter(|dir| dir.is_dir()) {
+        command.arg("--working-directory").arg(dir);
+    }
+    if let Some(config) = config.filter(|config| config.is_file()) {
+        command.arg("--config").arg(config);
+    }
+    let mut child = command.stdin(Stdio::null()).spawn()?;
+    thread::spawn(move || {
+        let _ = child.wait();
+    });
+    Ok(())
+}
+
+fn spawn_shell(slave_fd: &OwnedFd, shell: &ShellConfig) -> io::Result<i32> {
     unsafe {
         let ctty_fd = slave_fd.try_clone().expect("slave fd clone failed");
-        let mut child = Command::new("/bin/zsh")
+        let mut child = shell
+            .command()
             .stdin(slave_fd.try_clone().expect("slave fd clone failed"))
             .stdout(slave_fd.try_clone().expect("slave fd clone failed"))
             .stderr(slave_fd.try_clone().expect("slave fd clone failed"))
@@ -107,38 +240,44 @@ fn spawn_shell(slave_fd: &OwnedFd) -> i32 {
                 let _ = ioctl(ctty_fd.as_raw_fd(), TIOCSCTTY as _, 0);
                 Ok(())
             })
-            .spawn()
-            .expect("Failed to spawn shell");
+            .spawn()?;
         let pid = child.id() as i32;
         thread::spawn(move || {
             let _ = child.wait();
         });
-        pid
+        Ok(pid)
     }
 }
 
-// PtyEvent carries both input bytes and resize signals; ctx.request_repaint()
-// wakes the egui loop whenever new PTY output arrives.
+// PtyEvent carries input bytes (keyboard input and replies to terminal
+// queries) and resize signals; ctx.request_repaint() wakes the egui loop
+// whenever new PTY output arrives.
 fn spawn_pty_threads(
     master_read: OwnedFd,
     master_write: OwnedFd,
     tx_pty_output: Sender<Vec<u8>>,
     rx_pty_input: Receiver<PtyEvent>,
     ctx: egui::Context,
+    frame_pacer: Arc<FramePacer>,
     shell_pgid: i32,
 ) {
-    thread::spawn(move || loop {
-        let mut buffer = [0u8; 8192];
-        match read(master_read.as_fd(), &mut buffer) {
-            Ok(0) => break,
-            Ok(n) => {
-                if tx_pty_output.send(buffer[..n].to_vec()).is_err() {
-                    break;
+    thread::spawn(move || {
+        loop {
+            let mut buffer = [0u8; 8192];
+            match read(master_read.as_fd(), &mut buffer) {
+                Ok(0) => break,
+                Ok(n) => {
+                    if tx_pty_output.send(buffer[..n].to_vec()).is_err() {
+                        break;
+                    }
+                    frame_pacer.request_repaint(&ctx);
                 }
-                ctx.request_repaint();
+                Err(_) => break,
             }
-            Err(_) => break,
         }
+        // The closed channel tells the UI the shell has exited
+        drop(tx_pty_output);
+        ctx.request_repaint();
     });
 
     thread::spawn(move || {
@@ -162,25 +301,830 @@ fn to_egui_color(c: crate::terminal::color::Color32) -> egui::Color32 {
     egui::Color32::from_rgba_premultiplied(c.r, c.g, c.b, c.a)
 }
 
+/// What the Ctrl+Shift+F search bar holds
+#[derive(Default)]
+struct SearchBar {
+    query: SearchQuery,
+    error: Option<String>,
+}
+
 struct TerminalUI {
     rx_pty_output: Receiver<Vec<u8>>,
     tx_pty_input: Sender<PtyEvent>,
+    // PTY master, kept to look up the foreground process group
+    pty_fd: OwnedFd,
+    shell_pgid: i32,
+    // Configuration file, absolute so new windows find it from anywhere
+    config_file: Option<PathBuf>,
+    // The shell's working directory, refreshed as output arrives
+    cwd: Option<PathBuf>,
+    // Foreground process group `cwd` was last read for, and when
+    cwd_read: Option<(i32, Instant)>,
+    // Keep the window open once the shell exits, and whether it has
+    hold: bool,
+    shell_exited: bool,
+    // Title from the command line, shown while the program sets none
+    title: Option<String>,
+    // Size in cells asked for on the command line, applied once the cell
+    // size is known
+    requested_size: Option<(u16, u16)>,
     grid: TerminalGrid,
+    // Font in use, to tell whether a new configuration changes it
+    font: FontConfig,
     font_id: egui::FontId,
     cached_cell_size: Option<(f32, f32)>,
+    padding: f32,
+    cursor_style: CursorStyle,
+    keybindings: KeyBindings,
+    frame_pacer: Arc<FramePacer>,
+    // New configurations from the file watcher
+    rx_config: Option<Receiver<(Config, Vec<ConfigError>)>>,
+    // Mistakes in the configuration file, shown until dismissed
+    config_errors: Vec<String>,
+    // Fractional wheel movement that hasn't added up to a whole line yet
+    scroll_remainder: f32,
+    key_encoder: KeyEncoder,
+    // A Paste arrived since the last Ctrl+V release
+    pasted_since_ctrl_v: bool,
+    // Last title sent to the viewport, so it is only updated on change
+    window_title: String,
+    // Button held down while the program tracks the mouse, for drag reports
+    mouse_button_held: Option<MouseButton>,
+    // Cell of the last reported pointer position, so motion is reported once per cell
+    last_mouse_cell: Option<(usize, usize)>,
+    // Primary button held down over the grid while selecting
+    selecting: bool,
+    // Time and cell of the last selection click, and how many clicks in a row
+    // landed there
+    last_click: Option<(Instant, (usize, usize))>,
+    click_count: u8,
+    // OSC 52 requests waiting for the user to allow or deny them
+    pending_clipboard: Vec<ClipboardRequest>,
+    link_opener: LinkOpener,
+    // OSC 8 link under the pointer, underlined while hovered
+    hovered_link: Option<u32>,
+    detector: DetectorConfig,
+    // Cell under the pointer and the URL, path or hash detected there
+    hovered_cell: Option<(usize, usize)>,
+    hovered_match: Option<DetectedMatch>,
+    // Labelled matches while picking one from the keyboard
+    hints: Option<HintsMode>,
+    search_bar: Option<SearchBar>,
+    vi_mode: Option<ViMode>,
 }
 
 impl TerminalUI {
-    fn new(rx_pty_output: Receiver<Vec<u8>>, tx_pty_input: Sender<PtyEvent>) -> Self {
+    fn new(
+        rx_pty_output: Receiver<Vec<u8>>,
+        tx_pty_input: Sender<PtyEvent>,
+        pty_fd: OwnedFd,
+        shell_pgid: i32,
+        options: &Options,
+        config: &Config,
+        frame_pacer: Arc<FramePacer>,
+    ) -> Self {
+        let (cols, rows) = options.geometry.unwrap_or((80, 24));
         Self {
             rx_pty_output,
             tx_pty_input,
-            grid: TerminalGrid::new(80, 24),
-            font_id: egui::FontId::monospace(14.0),
+            pty_fd,
+            shell_pgid,
+            config_file: config_path(options).and_then(|path| std::path::absolute(path).ok()),
+            cwd: None,
+            cwd_read: None,
+            hold: options.hold,
+            shell_exited: false,
+            title: options.title.clone(),
+            requested_size: options.geometry,
+            grid: TerminalGrid::with_scrollback(
+                cols as usize,
+                rows as usize,
+                config.scrollback_lines,
+            ),
+            font: config.font.clone(),
+            font_id: egui::FontId::monospace(config.font.size),
             cached_cell_size: None,
+            padding: config.padding,
+            cursor_style: config.cursor_style,
+            keybindings: config.keybindings.clone(),
+            frame_pacer,
+            rx_config: None,
+            config_errors: Vec::new(),
+            scroll_remainder: 0.0,
+            key_encoder: config.key_encoder,
+            pasted_since_ctrl_v: false,
+            window_title: APP_TITLE.to_string(),
+            mouse_button_held: None,
+            last_mouse_cell: None,
+            selecting: false,
+            last_click: None,
+            click_count: 0,
+            pending_clipboard: Vec::new(),
+            link_opener: config.link_opener.clone(),
+            hovered_link: None,
+            detector: config.detector.clone(),
+            hovered_cell: None,
+            hovered_match: None,
+            hints: None,
+            search_bar: None,
+            vi_mode: None,
+        }
+    }
+
+    /// Takes over `config`, as read at startup or after the file changed.
+    /// The shell and the scrollback size stay as they were.
+    fn apply_config(&mut self, ctx: &egui::Context, config: Config, errors: Vec<ConfigError>) {
+        self.config_errors = errors.iter().map(ToString::to_string).collect();
+        if config.font != self.font || self.cached_cell_size.is_none() {
+            if let Err(err) = configure_fonts(ctx, &config.font) {
+                self.config_errors.push(err);
+            }
+            self.font_id = egui::FontId::monospace(config.font.size);
+            // The new font is only in place from the next frame on
+            self.cached_cell_size = None;
+            self.font = config.font;
+        }
+        self.padding = config.padding;
+        self.cursor_style = config.cursor_style;
+        self.keybindings = config.keybindings;
+        self.key_encoder = config.key_encoder;
+        self.link_opener = config.link_opener;
+        self.detector = config.detector;
+        self.frame_pacer.set_refresh_rate(config.refresh_rate);
+        self.grid.set_theme(config.theme);
+        self.grid.set_word_delimiters(&config.word_delimiters);
+        self.grid.set_clipboard_policy(config.clipboard);
+        ctx.request_repaint();
+    }
+
+    /// Applies the configuration at `path` again whenever it changes
+    fn watch_config(&mut self, ctx: &egui::Context, path: PathBuf, required: bool) {
+        let (tx_config, rx_config) = channel();
+        let ctx = ctx.clone();
+        config::watch(path, required, move |config, errors| {
+            let sent = tx_config.send((config, errors)).is_ok();
+            ctx.request_repaint();
+            sent
+        });
+        self.rx_config = Some(rx_config);
+    }
+
+    /// The action bound to a key press
+    fn bound_action(&self, event: &egui::Event) -> Option<KeyAction> {
+        let egui::Event::Key {
+            key,
+            pressed: true,
+            modifiers,
+            ..
+        } = event
+        else {
+            return None;
+        };
+        let chord = KeyChord {
+            key: key.name().to_ascii_lowercase(),
+            ctrl: modifiers.ctrl,
+            shift: modifiers.shift,
+            alt: modifiers.alt,
+        };
+        self.keybindings.action(&chord)
+    }
+
+    /// Converts a wheel delta to whole lines, carrying the fractional part
+    /// over to the next event. Positive values scroll towards older output.
+    fn wheel_lines(
+        &mut self,
+        unit: egui::MouseWheelUnit,
+        delta: egui::Vec2,
+        cell_h: f32,
+    ) -> i32 {
+        let lines = match unit {
+            egui::MouseWheelUnit::Point => delta.y / cell_h,
+            egui::MouseWheelUnit::Line => delta.y,
+            egui::MouseWheelUnit::Page => delta.y * self.grid.rows() as f32,
+        } + self.scroll_remainder;
+        let whole = lines.trunc();
+        self.scroll_remainder = lines - whole;
+        whole as i32
+    }
+
+    /// Reports pointer events to the program when it enabled mouse tracking.
+    /// Shift bypasses reporting so local selection keeps working. Returns true
+    /// when the event was consumed.
+    fn report_mouse_event(
+        &mut self,
+        event: &egui::Event,
+        mods: egui::Modifiers,
+        pointer_pos: Option<egui::Pos2>,
+        rect: egui::Rect,
+        (cell_w, cell_h): (f32, f32),
+        out: &mut Vec<u8>,
+    ) -> bool {
+        let (mode, encoding) = self.grid.mouse_protocol();
+        if mode == vt100::MouseProtocolMode::None {
+            self.mouse_button_held = None;
+            return false;
+        }
+
+        let (actions, pos, mods) = match event {
+            egui::Event::PointerButton {
+                pos,
+                button,
+                pressed,
+                modifiers,
+            } => {
+                let button = match button {
+                    egui::PointerButton::Primary => MouseButton::Left,
+                    egui::PointerButton::Middle => MouseButton::Middle,
+                    egui::PointerButton::Secondary => MouseButton::Right,
+                    _ => return false,
+                };
+                if *pressed && (modifiers.shift || !rect.contains(*pos)) {
+                    return false;
+                }
+                if !*pressed && self.mouse_button_held != Some(button) {
+                    return false;
+                }
+                let action = if *pressed {
+                    self.mouse_button_held = Some(button);
+                    MouseAction::Press(button)
+                } else {
+                    self.mouse_button_held = None;
+                    MouseAction::Release(button)
+                };
+                (vec![action], *pos, *modifiers)
+            }
+            egui::Event::PointerMoved(pos) => {
+                if mods.shift || (self.mouse_button_held.is_none() && !rect.contains(*pos)) {
+                    return false;
+                }
+                (vec![MouseAction::Motion(self.mouse_button_held)], *pos, mods)
+            }
+            egui::Event::MouseWheel {
+                unit,
+                delta,
+                modifiers,
+            } => {
+                if modifiers.shift {
+                    return false;
+                }
+                // Wheel events carry no position of their own
+                let Some(pos) = pointer_pos.filter(|pos| rect.contains(*pos)) else {
+                    return false;
+                };
+                let lines = self.wheel_lines(*unit, *delta, cell_h);
+                let button = if lines > 0 {
+                    MouseButton::WheelUp
+                } else {
+                    MouseButton::WheelDown
+                };
+                let actions = vec![MouseAction::Press(button); lines.unsigned_abs() as usize];
+                (actions, pos, *modifiers)
+            }
+            _ => return false,
+        };
+
+        let cell = screen_to_grid(
+            rect.min,
+            cell_w,
+            cell_h,
+            pos,
+            self.grid.rows(),
+            self.grid.cols(),
+        );
+        let moved = self.last_mouse_cell != Some(cell);
+        self.last_mouse_cell = Some(cell);
+        let mods = MouseModifiers {
+            shift: mods.shift,
+            alt: mods.alt,
+            ctrl: mods.ctrl,
+        };
+        for action in actions {
+            if matches!(action, MouseAction::Motion(_)) && !moved {
+                continue;
+            }
+            let (row, col) = cell;
+            if let Some(report) = encode_mouse_event(mode, encoding, action, mods, row, col) {
+                out.extend_from_slice(&report);
+            }
+        }
+        true
+    }
+
+    /// Selects text with the primary button: drag for a linear selection,
+    /// double click for words, triple click for lines, Alt+drag for a block.
+    /// Returns true when the event was consumed.
+    fn handle_selection_event(
+        &mut self,
+        event: &egui::Event,
+        rect: egui::Rect,
+        (cell_w, cell_h): (f32, f32),
+    ) -> bool {
+        let to_cell = |grid: &TerminalGrid, pos| {
+            screen_to_grid(rect.min, cell_w, cell_h, pos, grid.rows(), grid.cols())
+        };
+        match event {
+            egui::Event::PointerButton {
+                pos,
+                button: egui::PointerButton::Primary,
+                pressed: true,
+                modifiers,
+            } if rect.contains(*pos) => {
+                let cell = to_cell(&self.grid, *pos);
+                let now = Instant::now();
+                let repeated = self.last_click.is_some_and(|(time, last_cell)| {
+                    last_cell == cell && now.duration_since(time) <= MULTI_CLICK_INTERVAL
+                });
+                self.click_count = if repeated { self.click_count % 3 + 1 } else { 1 };
+                self.last_click = Some((now, cell));
+                let mode = match self.click_count {
+                    2 => SelectionMode::Word,
+                    3 => SelectionMode::Line,
+                    _ if modifiers.alt => SelectionMode::Block,
+                    _ => SelectionMode::Linear,
+                };
+                self.grid.start_selection(mode, cell.0, cell.1);
+                self.selecting = true;
+                true
+            }
+            egui::Event::PointerButton {
+                button: egui::PointerButton::Primary,
+                pressed: false,
+                ..
+            } if self.selecting => {
+                self.selecting = false;
+                true
+            }
+            egui::Event::PointerMoved(pos) if self.selecting => {
+                let (row, col) = to_cell(&self.grid, *pos);
+                self.grid.extend_selection(row, col);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn link_at(
+        &self,
+        pos: egui::Pos2,
+        rect: egui::Rect,
+        (cell_w, cell_h): (f32, f32),
+    ) -> Option<u32> {
+        if !rect.contains(pos) {
+            return None;
+        }
+        let (row, col) =
+            screen_to_grid(rect.min, cell_w, cell_h, pos, self.grid.rows(), self.grid.cols());
+        let cell = self.grid.get_cell(row, col)?;
+        if cell.wide_continuation && col > 0 {
+            return self.grid.get_cell(row, col - 1)?.link;
+        }
+        cell.link
+    }
+
+    /// Opens the OSC 8 link under a Ctrl+click, or runs the configured action
+    /// on a detected URL, path or hash. Returns true when the event was
+    /// consumed.
+    fn handle_link_click(
+        &mut self,
+        ctx: &egui::Context,
+        event: &egui::Event,
+        rect: egui::Rect,
+        cell_size: (f32, f32),
+    ) -> bool {
+        let egui::Event::PointerButton {
+            pos,
+            button: egui::PointerButton::Primary,
+            pressed: true,
+            modifiers,
+        } = event
+        else {
+            return false;
+        };
+        if !modifiers.ctrl || !rect.contains(*pos) {
+            return false;
+        }
+        if let Some(link) = self.link_at(*pos, rect, cell_size) {
+            if let Some(link) = self.grid.hyperlink(link)
+                && let Err(err) = self.link_opener.open(&link.uri)
+            {
+                eprintln!("failed to open {}: {err}", link.uri);
+            }
+            return true;
+        }
+        let (cell_w, cell_h) = cell_size;
+        let (row, col) =
+            screen_to_grid(rect.min, cell_w, cell_h, *pos, self.grid.rows(), self.grid.cols());
+        let Some(found) = self.grid.detect_at(row, col) else {
+            return false;
+        };
+        self.run_match_action(ctx, &found, HintAction::Default);
+        true
+    }
+
+    fn run_match_action(&self, ctx: &egui::Context, found: &DetectedMatch, action: HintAction) {
+        let action = match action {
+            HintAction::Default => self.detector.action(&found.kind),
+            HintAction::Copy => ClickAction::Copy,
+        };
+        let result = match action {
+            ClickAction::Open => {
+                let found = found.resolved(self.current_dir());
+                self.link_opener.open(&found.text)
+            }
+            ClickAction::Copy => {
+                ctx.copy_text(found.text.clone());
+                Ok(())
+            }
+            ClickAction::Edit => self.detector.edit(&found.resolved(self.current_dir())),
+        };
+        if let Err(err) = result {
+            eprintln!("failed to open {}: {err}", found.text);
+        }
+    }
+
+    /// The search key, Ctrl+Shift+F by default, opens the search bar and
+    /// closes it again, as does Escape. While it's open, Enter moves to older
+    /// matches and Shift+Enter to newer ones, and no keys reach the program.
+    /// Returns true when the event was consumed.
+    fn handle_search_event(&mut self, event: &egui::Event) -> bool {
+        let toggles = self.bound_action(event) == Some(KeyAction::Search);
+        if self.search_bar.is_none() {
+            if toggles {
+                self.search_bar = Some(SearchBar::default());
+            }
+            return toggles;
+        }
+        match event {
+            _ if toggles => {
+                self.search_bar = None;
+                self.grid.end_search();
+            }
+            egui::Event::Key {
+                key: egui::Key::Escape,
+                pressed: true,
+                ..
+            } => {
+                self.search_bar = None;
+                self.grid.end_search();
+            }
+            egui::Event::Key {
+                key: egui::Key::Enter,
+                pressed: true,
+                modifiers,
+                ..
+            } => {
+                if modifiers.shift {
+                    self.grid.search_next();
+                } else {
+                    self.grid.search_previous();
+                }
+            }
+            // The text field reads these on its own
+            egui::Event::Key { .. }
+            | egui::Event::Text(_)
+            | egui::Event::Copy
+            | egui::Event::Cut
+            | egui::Event::Paste(_) => {}
+            _ => return false,
+        }
+        true
+    }
+
+    /// egui-winit reads the clipboard for every Ctrl+V, Shift or not, and
+    /// drops the key when the clipboard is empty. A Ctrl+V release with no
+    /// Paste since the last one gets an empty Paste in front of it, so the
+    /// program still receives its ^V.
+    fn recover_ctrl_v(&mut self, events: Vec<egui::Event>) -> Vec<egui::Event> {
+        let mut recovered = Vec::with_capacity(events.len());
+        for event in events {
+            match &event {
+                egui::Event::Paste(_) => self.pasted_since_ctrl_v = true,
+                egui::Event::Key {
+                    key: egui::Key::V,
+                    pressed: false,
+                    modifiers,
+                    ..
+                } if modifiers.ctrl => {
+                    let lost = !std::mem::take(&mut self.pasted_since_ctrl_v);
+                    if lost && !modifiers.shift {
+                        recovered.push(egui::Event::Paste(String::new()));
+                    }
+                }
+                _ => {}
+            }
+            recovered.push(event);
+        }
+        recovered
+    }
+
+    /// With OSC 133 shell integration, Ctrl+Shift+Up/Down by default jump
+    /// between prompts and Ctrl+Shift+O selects the last command's output.
+    /// Returns true when the event was consumed; with no prompt to go to or
+    /// no output to select, the keys reach the program instead.
+    fn handle_prompt_event(&mut self, event: &egui::Event) -> bool {
+        match self.bound_action(event) {
+            Some(KeyAction::PreviousPrompt) => self.grid.scroll_to_previous_prompt(),
+            Some(KeyAction::NextPrompt) => self.grid.scroll_to_next_prompt(),
+            Some(KeyAction::SelectOutput) => self.grid.select_last_output(),
+            _ => false,
+        }
+    }
+
+    /// The vi mode key, Ctrl+Shift+Space by default, toggles vi copy mode.
+    /// While it's on, keys move its cursor and select instead of reaching the
+    /// program, and yanks go to the clipboard. Returns true when the event
+    /// was consumed.
+    fn handle_vi_event(&mut self, ctx: &egui::Context, event: &egui::Event) -> bool {
+        let toggles = self.bound_action(event) == Some(KeyAction::ViMode);
+        let Some(vi) = &mut self.vi_mode else {
+            if toggles {
+                self.vi_mode = Some(ViMode::new(&self.grid));
+            }
+            return toggles;
+        };
+        let keys = match event {
+            _ if toggles => vec![ViKey::Escape, ViKey::Escape],
+            egui::Event::Text(text) => text.chars().map(ViKey::Char).collect(),
+            egui::Event::Key {
+                key: egui::Key::Escape,
+                pressed: true,
+                ..
+            } => vec![ViKey::Escape],
+            // egui hands Ctrl+V over as a paste, and only once the key is
+            // released when the clipboard is empty; Ctrl+Q doesn't wait
+            egui::Event::Paste(_) => vec![ViKey::Ctrl('v')],
+            egui::Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+                ..
+            } if modifiers.ctrl => match key {
+                egui::Key::D => vec![ViKey::Ctrl('d')],
+                egui::Key::U => vec![ViKey::Ctrl('u')],
+                egui::Key::F => vec![ViKey::Ctrl('f')],
+                egui::Key::B => vec![ViKey::Ctrl('b')],
+                egui::Key::Q => vec![ViKey::Ctrl('q')],
+                _ => Vec::new(),
+            },
+            egui::Event::Key { .. } | egui::Event::Copy | egui::Event::Cut => Vec::new(),
+            _ => return false,
+        };
+        for key in keys {
+            match vi.handle_key(&mut self.grid, key) {
+                ViAction::None => {}
+                ViAction::Yank(text) => ctx.copy_text(text),
+                ViAction::Exit => {
+                    self.vi_mode = None;
+                    break;
+                }
+            }
+        }
+        true
+    }
+
+    /// The hints key, Ctrl+Shift+E by default, labels every match on the
+    /// screen; typing a label runs its action, or copies it when the last
+    /// letter is upper case. Escape leaves. Returns true when the event was
+    /// consumed.
+    fn handle_hints_event(&mut self, ctx: &egui::Context, event: &egui::Event) -> bool {
+        let starts = self.bound_action(event) == Some(KeyAction::Hints);
+        let Some(hints) = &mut self.hints else {
+            if starts {
+                let hints = HintsMode::new(self.grid.visible_matches());
+                self.hints = (!hints.is_empty()).then_some(hints);
+                return true;
+            }
+            return false;
+        };
+        match event {
+            egui::Event::Key {
+                key: egui::Key::Escape,
+                pressed: true,
+                ..
+            } => self.hints = None,
+            egui::Event::Key {
+                key: egui::Key::Backspace,
+                pressed: true,
+                ..
+            } => hints.backspace(),
+            egui::Event::Text(text) => {
+                for c in text.chars() {
+                    if let HintInput::Picked(found, action) = hints.type_char(c) {
+                        self.hints = None;
+                        self.run_match_action(ctx, &found, action);
+                        break;
+                    }
+                }
+            }
+            // Keys never reach the program while picking
+            egui::Event::Key { .. }
+            | egui::Event::Copy
+            | egui::Event::Cut
+            | egui::Event::Paste(_) => {}
+            _ => return false,
+        }
+        true
+    }
+
+    fn handle_clipboard_request(&mut self, ctx: &egui::Context, request: ClipboardRequest) {
+        match request {
+            ClipboardRequest::Store(text) => ctx.copy_text(text),
+            ClipboardRequest::Load {
+                targets,
+                bell_terminated,
+            } => {
+                // egui only hands out the clipboard with paste events
+                let text = arboard::Clipboard::new()
+                    .and_then(|mut clipboard| clipboard.get_text())
+                    .unwrap_or_default();
+                let reply = clipboard_reply(&targets, &text, bell_terminated);
+                let _ = self.tx_pty_input.send(PtyEvent::Input(reply));
+            }
+        }
+    }
+
+    /// Asks whether the oldest pending OSC 52 request may go ahead.
+    fn show_clipboard_prompt(&mut self, ctx: &egui::Context) {
+        let Some(request) = self.pending_clipboard.first() else {
+            return;
+        };
+        let message = match request {
+            ClipboardRequest::Store(text) => format!(
+                "A program wants to copy {} characters to the clipboard.",
+                text.chars().count()
+            ),
+            ClipboardRequest::Load { .. } => "A program wants to read the clipboard.".to_string(),
+        };
+        let mut decision = None;
+        egui::Window::new("Clipboard access")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(message);
+                ui.horizontal(|ui| {
+                    if ui.button("Allow").clicked() {
+                        decision = Some(true);
+                    }
+                    if ui.button("Deny").clicked() {
+                        decision = Some(false);
+                    }
+                });
+            });
+        if let Some(allowed) = decision {
+            let request = self.pending_clipboard.remove(0);
+            if allowed {
+                self.handle_clipboard_request(ctx, request);
+            }
         }
     }
 
+    /// Lists the mistakes in the configuration file until they are fixed or
+    /// dismissed
+    fn show_config_errors(&mut self, ctx: &egui::Context) {
+        if self.config_errors.is_empty() {
+            return;
+        }
+        let mut dismissed = false;
+        egui::Area::new(egui::Id::new("config_errors"))
+            .anchor(egui::Align2::LEFT_TOP, egui::vec2(8.0, 8.0))
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(egui::RichText::new("Configuration errors").strong());
+                    for error in &self.config_errors {
+                        ui.label(egui::RichText::new(error).monospace());
+                    }
+                    dismissed = ui.button("Dismiss").clicked();
+                });
+            });
+        if dismissed {
+            self.config_errors.clear();
+        }
+    }
+
+    fn show_search_bar(&mut self, ctx: &egui::Context) {
+        let Some(bar) = &mut self.search_bar else {
+            return;
+        };
+        let status = match (&bar.error, self.grid.search_status()) {
+            (Some(error), _) => error.clone(),
+            (None, Some((Some(current), count))) => format!("{current}/{count}"),
+            (None, Some((None, _))) => "No matches".to_string(),
+            (None, None) => String::new(),
+        };
+        let mut changed = false;
+        egui::Area::new(egui::Id::new("search_bar"))
+            .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-8.0, 8.0))
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        let field = ui.add(
+                            egui::TextEdit::singleline(&mut bar.query.text)
+                                .hint_text("Search")
+                                .desired_width(200.0),
+                        );
+                        field.request_focus();
+                        changed |= field.changed();
+                        changed |= ui.checkbox(&mut bar.query.regex, "Regex").changed();
+                        changed |= ui
+                            .checkbox(&mut bar.query.case_sensitive, "Match case")
+                            .changed();
+                        ui.label(status);
+                    });
+                });
+            });
+        if !changed {
+            return;
+        }
+        bar.error = None;
+        if bar.query.text.is_empty() {
+            self.grid.end_search();
+        } else if let Err(err) = self.grid.start_search(&bar.query) {
+            bar.error = Some(err.to_string());
+            self.grid.end_search();
+        }
+        ctx.request_repaint();
+    }
+
+    fn sync_window_title(&mut self, ctx: &egui::Context) {
+        // Without a title from the program, the one from the command line or
+        // else the working directory stands in
+        let title = match (self.grid.title(), &self.title, &self.cwd) {
+            ("", Some(title), _) => title.clone(),
+            ("", None, Some(dir)) => display_dir(dir, std::env::var_os("HOME").as_deref()),
+            ("", None, None) => APP_TITLE.to_string(),
+            (title, _, _) => title.to_string(),
+        };
+        if title != self.window_title {
+            self.window_title = title;
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(self.window_title.clone()));
+        }
+    }
+
+    /// The shell's working directory: the last one it reported with OSC 7,
+    /// or else that of the foreground process
+    fn current_dir(&self) -> Option<&Path> {
+        self.cwd.as_deref()
+    }
+
+    fn refresh_current_dir(&mut self) {
+        if let Some(dir) = self.grid.reported_dir().filter(|dir| dir.is_local()) {
+            if self.cwd.as_ref() != Some(&dir.path) {
+                self.cwd = Some(dir.path.clone());
+            }
+            self.cwd_read = None;
+            return;
+        }
+        // /proc is only read again for a new foreground process, or once
+        // the last reading is old enough to have missed a `cd`
+        let pgid = foreground_pgid(self.pty_fd.as_raw_fd(), self.shell_pgid);
+        let fresh = self.cwd_read.is_some_and(|(read_pgid, read_at)| {
+            read_pgid == pgid && read_at.elapsed() < FOREGROUND_DIR_TTL
+        });
+        if !fresh {
+            self.cwd = process_group_dir(pgid);
+            self.cwd_read = Some((pgid, Instant::now()));
+        }
+    }
+
+    /// The new window key, Ctrl+Shift+N by default, opens a new window in
+    /// the current directory. Returns true when the event was consumed.
+    fn handle_window_event(&mut self, event: &egui::Event) -> bool {
+        if self.bound_action(event) != Some(KeyAction::NewWindow) {
+            return false;
+        }
+        if let Err(err) = open_new_window(self.current_dir(), self.config_file.as_deref()) {
+            eprintln!("failed to open a new window: {err}");
+        }
+        true
+    }
+
+    /// Moves the scrollback viewport for wheel events and the scroll keys,
+    /// by default Shift+PageUp/PageDown and Shift+Home/End. Returns true when
+    /// the event was consumed.
+    fn handle_scroll_event(&mut self, event: &egui::Event, cell_h: f32) -> bool {
+        match self.bound_action(event) {
+            Some(KeyAction::ScrollPageUp) => self.grid.scroll_page_up(),
+            Some(KeyAction::ScrollPageDown) => self.grid.scroll_page_down(),
+            Some(KeyAction::ScrollToTop) => self.grid.scroll_to_top(),
+            Some(KeyAction::ScrollToBottom) => self.grid.scroll_to_bottom(),
+            _ => {
+                let egui::Event::MouseWheel { unit, delta, .. } = event else {
+                    return false;
+                };
+                let lines = self.wheel_lines(*unit, *delta, cell_h);
+                if lines > 0 {
+                    self.grid.scroll_up(lines as usize);
+                } else if lines < 0 {
+                    self.grid.scroll_down(lines.unsigned_abs() as usize);
+                }
+            }
+        }
+        true
+    }
+
     fn cell_size(&mut self, ctx: &egui::Context) -> (f32, f32) {
         if let Some(size) = self.cached_cell_size {
             return size;
@@ -209,17 +1153,42 @@ fn grid_to_screen(
     )
 }
 
+// Inverse of grid_to_screen, clamped to the grid so drags past the edge
+// still land on a cell.
+fn screen_to_grid(
+    origin: egui::Pos2,
+    cell_w: f32,
+    cell_h: f32,
+    pos: egui::Pos2,
+    rows: usize,
+    cols: usize,
+) -> (usize, usize) {
+    let col = ((pos.x - origin.x) / cell_w).floor().max(0.0) as usize;
+    let row = ((pos.y - origin.y) / cell_h).floor().max(0.0) as usize;
+    (row.min(rows.saturating_sub(1)), col.min(cols.saturating_sub(1)))
+}
+
 impl eframe::App for TerminalUI {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         let mut needs_repaint = false;
+        self.frame_pacer.frame_drawn();
 
         egui::CentralPanel::default()
             .frame(egui::Frame::NONE.fill(to_egui_color(self.grid.default_bg())))
             .show(ctx, |ui| {
                 let (cell_w, cell_h) = self.cell_size(ctx);
                 let available = ui.available_size();
-                let cols = ((available.x / cell_w).floor() as usize).max(1);
-                let rows = ((available.y / cell_h).floor() as usize).max(1);
+                let grid_size =
+                    (available - egui::Vec2::splat(2.0 * self.padding)).max(egui::Vec2::ZERO);
+                let cols = ((grid_size.x / cell_w).floor() as usize).max(1);
+                let rows = ((grid_size.y / cell_h).floor() as usize).max(1);
+
+                if let Some((cols, rows)) = self.requested_size.take() {
+                    // Whatever surrounds the grid stays the same size
+                    let chrome = ctx.content_rect().size() - grid_size;
+                    let size = egui::vec2(cols as f32 * cell_w, rows as f32 * cell_h) + chrome;
+                    ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(size));
+                }
 
                 if self.grid.resize(cols, rows) {
                     let _ = self.tx_pty_input.send(PtyEvent::Resize {
@@ -230,35 +1199,156 @@ impl eframe::App for TerminalUI {
                 }
 
                 let mut received_data = false;
-                while let Ok(bytes) = self.rx_pty_output.try_recv() {
-                    self.grid.process_pty_bytes(&bytes);
-                    received_data = true;
+                loop {
+                    match self.rx_pty_output.try_recv() {
+                        Ok(bytes) => {
+                            self.grid.process_pty_bytes(&bytes);
+                            received_data = true;
+                        }
+                        Err(TryRecvError::Empty) => break,
+                        Err(TryRecvError::Disconnected) => {
+                            if !self.shell_exited && !self.hold {
+                                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                            }
+                            self.shell_exited = true;
+                            break;
+                        }
+                    }
+                }
+                if received_data {
+                    self.refresh_current_dir();
+                }
+
+                self.sync_window_title(ctx);
+                self.grid.refresh_search();
+
+                // Answers to DA/DSR/OSC queries go back through the writer thread
+                let responses = self.grid.take_responses();
+                if !responses.is_empty() {
+                    let _ = self.tx_pty_input.send(PtyEvent::Input(responses));
+                }
+
+                for event in self.grid.take_clipboard_events() {
+                    if event.needs_confirmation {
+                        queue_unconfirmed(&mut self.pending_clipboard, event.request);
+                    } else {
+                        self.handle_clipboard_request(ctx, event.request);
+                    }
                 }
 
                 if received_data && self.grid.has_changes() {
                     needs_repaint = true;
                 }
 
-                let (rect, _response) = ui.allocate_at_least(available, egui::Sense::click());
+                let (panel_rect, _response) =
+                    ui.allocate_at_least(available, egui::Sense::click());
+                let rect = panel_rect.shrink(self.padding);
 
                 let mut input_bytes = Vec::new();
-                ctx.input(|input| {
-                    let mods = input.modifiers;
-                    for event in &input.events {
-                        append_input_from_event(event, mods, &mut input_bytes);
-                    }
+                let (events, mods, pointer_pos) = ctx.input(|input| {
+                    (input.events.clone(), input.modifiers, input.pointer.latest_pos())
                 });
+                let events = self.recover_ctrl_v(events);
+                let keyboard_modes = self.grid.keyboard_modes();
+                let cell_size = (cell_w, cell_h);
+                for event in &events {
+                    if self.handle_search_event(event) {
+                        needs_repaint = true;
+                        continue;
+                    }
+                    if self.handle_vi_event(ctx, event) {
+                        needs_repaint = true;
+                        continue;
+                    }
+                    if self.handle_hints_event(ctx, event) {
+                        needs_repaint = true;
+                        continue;
+                    }
+                    if self.handle_window_event(event) {
+                        continue;
+                    }
+                    if self.handle_prompt_event(event) {
+                        needs_repaint = true;
+                        continue;
+                    }
+                    if self.handle_link_click(ctx, event, rect, cell_size) {
+                        continue;
+                    }
+                    if self.report_mouse_event(
+                        event,
+                        mods,
+                        pointer_pos,
+                        rect,
+                        cell_size,
+                        &mut input_bytes,
+                    ) {
+                        continue;
+                    }
+                    if self.handle_selection_event(event, rect, cell_size) {
+                        needs_repaint = true;
+                        continue;
+                    }
+                    if self.handle_scroll_event(event, cell_h) {
+                        needs_repaint = true;
+                        continue;
+                    }
+                    // Ctrl+Shift+C copies the selection; plain Ctrl+C always
+                    // goes to the program
+                    if matches!(event, egui::Event::Copy) && mods.shift {
+                        if let Some(text) = self.grid.selected_text() {
+                            ctx.copy_text(text);
+                            self.grid.clear_selection();
+                            needs_repaint = true;
+                        }
+                        continue;
+                    }
+                    append_input_from_event(
+                        &self.key_encoder,
+                        event,
+                        mods,
+                        keyboard_modes,
+                        &mut input_bytes,
+                    );
+                }
                 if !input_bytes.is_empty() {
+                    // Typing always brings the live screen back into view
+                    self.grid.scroll_to_bottom();
                     let _ = self.tx_pty_input.send(PtyEvent::Input(input_bytes));
                 }
 
-                let painter = ui.painter_at(rect);
+                let hovered_link = pointer_pos.and_then(|pos| self.link_at(pos, rect, cell_size));
+                if hovered_link != self.hovered_link {
+                    self.hovered_link = hovered_link;
+                    needs_repaint = true;
+                }
+                // Detection scans the whole line, so it only reruns when the
+                // pointer or the screen changed
+                let hovered_cell = pointer_pos.filter(|pos| rect.contains(*pos)).map(|pos| {
+                    screen_to_grid(rect.min, cell_w, cell_h, pos, rows, cols)
+                });
+                if hovered_cell != self.hovered_cell || needs_repaint {
+                    self.hovered_cell = hovered_cell;
+                    let hovered_match = match hovered_cell {
+                        Some((row, col)) if hovered_link.is_none() => self.grid.detect_at(row, col),
+                        _ => None,
+                    };
+                    if hovered_match != self.hovered_match {
+                        self.hovered_match = hovered_match;
+                        needs_repaint = true;
+                    }
+                }
+                if hovered_link.is_some() || self.hovered_match.is_some() {
+                    ctx.set_cursor_icon(egui::CursorIcon::PointingHand);
+                }
+
+                let painter = ui.painter_at(panel_rect);
                 let origin = rect.min;
 
-                painter.rect_filled(rect, 0.0, to_egui_color(self.grid.default_bg()));
+                painter.rect_filled(panel_rect, 0.0, to_egui_color(self.grid.default_bg()));
 
                 let default_bg = self.grid.default_bg();
                 let font_id = &self.font_id;
+                let theme = self.grid.theme();
 
                 for row in 0..rows {
                     let mut col = 0;
@@ -270,13 +1360,37 @@ impl eframe::App for TerminalUI {
                             continue;
                         }
 
-                        let (text, fg, bg, underline, col_span) = if let Some(cell) = &cell {
+                        let (text, mut fg, mut bg, underline, col_span) = if let Some(cell) = &cell {
                             let (fg, bg) = self.grid.resolve_cell_colors(cell);
                             let span = if cell.wide { 2 } else { 1 };
-                            (cell.text.as_str(), fg, bg, cell.underline, span)
+                            // Links are underlined while hovered
+                            let hovered = (cell.link.is_some() && cell.link == self.hovered_link)
+                                || self
+                                    .hovered_match
+                                    .as_ref()
+                                    .is_some_and(|found| self.grid.match_contains(found, row, col));
+                            (cell.text.as_str(), fg, bg, cell.underline || hovered, span)
                         } else {
                             ("", crate::terminal::color::Color32::WHITE, default_bg, false, 1)
                         };
+                        // Selected cells take the theme's selection colors, or
+                        // are drawn in inverse video
+                        let selected = self.grid.is_selected(row, col);
+                        if selected {
+                            match theme.selection_background {
+                                Some(selection_bg) => bg = selection_bg,
+                                None => std::mem::swap(&mut fg, &mut bg),
+                            }
+                            fg = theme.selection_foreground.unwrap_or(fg);
+                        }
+                        let highlight = self.grid.search_highlight(row, col);
+                        if let Some(highlight) = highlight {
+                            fg = SEARCH_FG;
+                            bg = match highlight {
+                                SearchHighlight::Current => SEARCH_CURRENT_BG,
+                                SearchHighlight::Match => SEARCH_MATCH_BG,
+                            };
+                        }
 
                         let pos = grid_to_screen(origin, cell_w, cell_h, row, col);
                         let rect = egui::Rect::from_min_size(
@@ -284,7 +1398,7 @@ impl eframe::App for TerminalUI {
                             egui::vec2(cell_w * col_span as f32, cell_h),
                         );
 
-                        if bg != default_bg {
+                        if bg != default_bg || selected || highlight.is_some() {
                             painter.rect_filled(rect, 0.0, to_egui_color(bg));
                         }
 
@@ -303,9 +1417,22 @@ impl eframe::App for TerminalUI {
 
                         col += col_span;
                     }
+
+                    if self.grid.failed_prompt_at(row) {
+                        let pos = grid_to_screen(origin, cell_w, cell_h, row, 0);
+                        let mark_rect = egui::Rect::from_min_size(
+                            pos,
+                            egui::vec2(FAILED_COMMAND_MARK_WIDTH, cell_h),
+                        );
+                        painter.rect_filled(mark_rect, 0.0, FAILED_COMMAND_MARK);
+                    }
                 }
 
-                if self.grid.cursor_visible() {
+                // Copy mode draws its own cursor instead
+                if self.grid.cursor_visible()
+                    && !self.grid.is_scrolled()
+                    && self.vi_mode.is_none()
+                {
                     let (cursor_row, cursor_col) = self.grid.cursor_pos();
                     let cursor_cell = self.grid.get_cell(cursor_row, cursor_col);
                     let (cell_fg, cell_bg) = cursor_cell
@@ -322,27 +1449,96 @@ impl eframe::App for TerminalUI {
                             cell_fg
                         }
                     });
-                    let cursor_fg = if cursor_bg == cell_bg {
-                        cell_fg
-                    } else {
-                        cell_bg
+                    let cursor_fg = match theme.cursor_text {
+                        Some(cursor_text) => cursor_text,
+                        None if cursor_bg == cell_bg => cell_fg,
+                        None => cell_bg,
                     };
-                    painter.rect_filled(cursor_rect, 0.0, to_egui_color(cursor_bg));
-                    painter.text(
-                        cursor_pos,
-                        egui::Align2::LEFT_TOP,
-                        cursor_cell
-                            .as_ref()
-                            .map(|cell| cell.text.as_str())
-                            .unwrap_or(" "),
-                        font_id.clone(),
-                        to_egui_color(cursor_fg),
+                    // Thin cursors leave the character to be drawn as usual
+                    let thickness = (cell_h / 8.0).round().max(2.0);
+                    match self.cursor_style {
+                        CursorStyle::Block => {
+                            painter.rect_filled(cursor_rect, 0.0, to_egui_color(cursor_bg));
+                            painter.text(
+                                cursor_pos,
+                                egui::Align2::LEFT_TOP,
+                                cursor_cell
+                                    .as_ref()
+                                    .map(|cell| cell.text.as_str())
+                                    .unwrap_or(" "),
+                                font_id.clone(),
+                                to_egui_color(cursor_fg),
+                            );
+                        }
+                        CursorStyle::Underline => {
+                            let mut underline_rect = cursor_rect;
+                            underline_rect.min.y = cursor_rect.max.y - thickness;
+                            painter.rect_filled(underline_rect, 0.0, to_egui_color(cursor_bg));
+                        }
+                        CursorStyle::Bar => {
+                            let mut bar_rect = cursor_rect;
+                            bar_rect.max.x = cursor_rect.min.x + thickness;
+                            painter.rect_filled(bar_rect, 0.0, to_egui_color(cursor_bg));
+                        }
+                    }
+                }
+
+                if let Some(vi) = &self.vi_mode
+                    && let Some((row, col)) = self.grid.viewport_cell(vi.cursor())
+                {
+                    // A hollow box keeps the text and selection under it visible
+                    let color = self
+                        .grid
+                        .cursor_color()
+                        .unwrap_or(crate::terminal::color::Color32::WHITE);
+                    let pos = grid_to_screen(origin, cell_w, cell_h, row, col);
+                    let cursor_rect = egui::Rect::from_min_size(pos, egui::vec2(cell_w, cell_h));
+                    painter.rect_stroke(
+                        cursor_rect,
+                        0.0,
+                        egui::Stroke::new(2.0, to_egui_color(color)),
+                        egui::StrokeKind::Inside,
                     );
                 }
 
+                if let Some(hints) = &self.hints {
+                    for hint in hints.visible_hints() {
+                        // Matches wrapped in from above are labelled in the
+                        // top left corner
+                        let cell = self.grid.viewport_cell(hint.found.start).or_else(|| {
+                            self.grid.match_contains(&hint.found, 0, 0).then_some((0, 0))
+                        });
+                        let Some((row, col)) = cell else {
+                            continue;
+                        };
+                        let label = &hint.label[hints.typed().len()..];
+                        let pos = grid_to_screen(origin, cell_w, cell_h, row, col);
+                        let label_rect = egui::Rect::from_min_size(
+                            pos,
+                            egui::vec2(cell_w * label.len() as f32, cell_h),
+                        );
+                        painter.rect_filled(label_rect, 0.0, HINT_BG);
+                        painter.text(pos, egui::Align2::LEFT_TOP, label, font_id.clone(), HINT_FG);
+                    }
+                }
+
                 self.grid.mark_rendered();
             });
 
+        // Applied after drawing, since a new font only takes effect from the
+        // next frame on
+        let new_config = self.rx_config.as_ref().and_then(|rx| rx.try_iter().last());
+        if let Some((config, errors)) = new_config {
+            for error in &errors {
+                eprintln!("shitty: {error}");
+            }
+            self.apply_config(ctx, config, errors);
+        }
+
+        self.show_clipboard_prompt(ctx);
+        self.show_search_bar(ctx);
+        self.show_config_errors(ctx);
+
         if needs_repaint {
             ctx.request_repaint();
         }
@@ -1,3 +1,5 @@
+pub mod cli;
+mod config;
 mod terminal;
 
 #[cfg(target_os = "macos")]
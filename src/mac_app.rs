@@ -1,6 +1,10 @@
+use crate::cli::Options;
+use crate::config::{Config, DEFAULT_REFRESH_RATE, config_path};
+use crate::terminal::clipboard::{ClipboardRequest, clipboard_reply, queue_unconfirmed};
 use crate::terminal::color::Color32;
 use crate::terminal::grid::TerminalGrid;
 use crate::terminal::keymap;
+use crate::terminal::shell::ShellConfig;
 use nix::libc::{
     SIGWINCH, TIOCSCTTY, TIOCSWINSZ, ioctl, killpg, pid_t, setsid, tcgetpgrp, winsize,
 };
@@ -12,8 +16,9 @@ use objc2::{
     AnyThread, DefinedClass, MainThreadMarker, MainThreadOnly, define_class, msg_send, sel,
 };
 use objc2_app_kit::{
-    NSApplication, NSApplicationActivationPolicy, NSApplicationDelegate, NSAutoresizingMaskOptions,
-    NSBackingStoreType, NSBezierPath, NSColor, NSEvent, NSFont, NSImage, NSMenu, NSMenuItem,
+    NSAlert, NSAlertFirstButtonReturn, NSApplication, NSApplicationActivationPolicy,
+    NSApplicationDelegate, NSAutoresizingMaskOptions, NSBackingStoreType, NSBezierPath, NSColor,
+    NSEvent, NSFont, NSImage, NSMenu, NSMenuItem, NSPasteboard, NSPasteboardTypeString,
     NSResponder, NSStringDrawing, NSView, NSWindow, NSWindowDelegate, NSWindowStyleMask,
 };
 use objc2_core_foundation::{CFArray, CFError, CFRetained, CFString, CFType, CFURL};
@@ -28,14 +33,10 @@ use objc2_foundation::{
 use std::collections::HashSet;
 use std::os::fd::{AsFd, AsRawFd, OwnedFd};
 use std::os::unix::process::CommandExt;
-use std::process::Command;
 use std::sync::OnceLock;
-use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError, channel};
 use std::thread;
 
-/// Refresh rate for the terminal rendering timer (in Hz)
-const RENDER_REFRESH_RATE_HZ: f64 = 120.0;
-
 fn load_app_icon(_mtm: MainThreadMarker) -> Option<Retained<NSImage>> {
     let icon_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/icon.png");
     if !icon_path.exists() {
@@ -50,11 +51,24 @@ fn load_app_icon(_mtm: MainThreadMarker) -> Option<Retained<NSImage>> {
     Some(image)
 }
 
-pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+pub fn run(options: Options) -> Result<(), Box<dyn std::error::Error>> {
     let pty_result = openpty(None, None)?;
     let master_fd = pty_result.master;
     let slave_fd = pty_result.slave;
-    let shell_pgid = spawn_shell(&slave_fd);
+    // Changes to the file take effect in the next window
+    let (config, errors) = config_path(&options)
+        .map(|path| Config::load(&path, options.config.is_some()))
+        .unwrap_or_default();
+    for error in &errors {
+        eprintln!("shitty: {error}");
+    }
+    if options.class.is_some() {
+        // Apps are told apart by their bundle identifier on macOS
+        eprintln!("shitty: --class has no effect on macOS");
+    }
+    let shell = config.shell.clone().with_options(&options);
+    let shell_pgid = spawn_shell(&slave_fd, &shell)
+        .map_err(|err| format!("failed to start {}: {err}", shell.program().display()))?;
 
     let (tx_pty_output, rx_pty_output) = channel::<Vec<u8>>();
     let (tx_pty_input, rx_pty_input) = channel::<Vec<u8>>();
@@ -77,13 +91,27 @@ pub fn run() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
-    let delegate = ShittyAppDelegate::new(
-        mtm,
+    let view_state = ShittyAppDelegate::terminal_state_from_channels(
         rx_pty_output,
         tx_pty_input,
         master_ui,
         slave_ui,
         shell_pgid,
+        &config,
+        options.hold,
+    );
+    let content_size = match options.geometry {
+        Some((cols, rows)) => NSSize::new(
+            f64::from(cols) * view_state.cell_width,
+            f64::from(rows) * view_state.cell_height,
+        ),
+        None => NSSize::new(800.0, 600.0),
+    };
+    let delegate = ShittyAppDelegate::new(
+        mtm,
+        view_state,
+        options.title.unwrap_or_else(|| "shitty (AppKit)".to_string()),
+        content_size,
     );
     app.setDelegate(Some(ProtocolObject::from_ref(&*delegate)));
 
@@ -105,10 +133,11 @@ fn macos_quit_menu_item_spec() -> QuitMenuItemSpec {
     }
 }
 
-fn spawn_shell(slave_fd: &OwnedFd) -> i32 {
+fn spawn_shell(slave_fd: &OwnedFd, shell: &ShellConfig) -> std::io::Result<i32> {
     unsafe {
         let ctty_fd = slave_fd.try_clone().expect("slave fd clone failed");
-        let mut child = Command::new("/bin/zsh")
+        let mut child = shell
+            .command()
             .stdin(slave_fd.try_clone().expect("slave fd clone failed"))
             .stdout(slave_fd.try_clone().expect("slave fd clone failed"))
             .stderr(slave_fd.try_clone().expect("slave fd clone failed"))
@@ -117,13 +146,12 @@ fn spawn_shell(slave_fd: &OwnedFd) -> i32 {
                 let _ = ioctl(ctty_fd.as_raw_fd(), TIOCSCTTY as _, 0);
                 Ok(())
             })
-            .spawn()
-            .expect("Failed to spawn shell");
+            .spawn()?;
         let pid = child.id() as i32;
         thread::spawn(move || {
             let _ = child.wait();
         });
-        pid
+        Ok(pid)
     }
 }
 
@@ -166,6 +194,7 @@ struct TerminalViewState {
     grid: TerminalGrid,
     rx_output: Receiver<Vec<u8>>,
     tx_input: Sender<Vec<u8>>,
+    key_encoder: keymap::KeyEncoder,
     font: Retained<NSFont>,
     cell_width: f64,
     cell_height: f64,
@@ -173,6 +202,10 @@ struct TerminalViewState {
     master_fd: Option<OwnedFd>,
     slave_fd: Option<OwnedFd>,
     shell_pgid: pid_t,
+    // Frames per second of the rendering timer
+    refresh_rate: f64,
+    // Keep the window open once the shell exits
+    hold: bool,
 }
 
 impl Drop for TerminalViewState {
@@ -200,7 +233,7 @@ define_class!(
             let window = unsafe {
                 NSWindow::initWithContentRect_styleMask_backing_defer(
                     NSWindow::alloc(mtm),
-                    NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(800.0, 600.0)),
+                    NSRect::new(NSPoint::new(0.0, 0.0), self.ivars().content_size),
                     NSWindowStyleMask::Titled
                         | NSWindowStyleMask::Closable
                         | NSWindowStyleMask::Miniaturizable
@@ -211,7 +244,7 @@ define_class!(
             };
 
             window.center();
-            window.setTitle(ns_string!("shitty (AppKit)"));
+            window.setTitle(&NSString::from_str(&self.ivars().title));
             unsafe { window.setReleasedWhenClosed(false) };
 
             let view = self
@@ -247,16 +280,12 @@ define_class!(
 impl ShittyAppDelegate {
     fn new(
         mtm: MainThreadMarker,
-        rx_output: Receiver<Vec<u8>>,
-        tx_input: Sender<Vec<u8>>,
-        master_fd: OwnedFd,
-        slave_fd: OwnedFd,
-        shell_pgid: pid_t,
+        view_state: TerminalViewState,
+        title: String,
+        content_size: NSSize,
     ) -> Retained<Self> {
-        let view_state = Self::terminal_state_from_channels(
-            rx_output, tx_input, master_fd, slave_fd, shell_pgid,
-        );
-        let this = Self::alloc(mtm).set_ivars(AppDelegateIvars::new(mtm, view_state));
+        let ivars = AppDelegateIvars::new(mtm, view_state, title, content_size);
+        let this = Self::alloc(mtm).set_ivars(ivars);
         unsafe { msg_send![super(this), init] }
     }
 
@@ -266,14 +295,21 @@ impl ShittyAppDelegate {
         master_fd: OwnedFd,
         slave_fd: OwnedFd,
         shell_pgid: pid_t,
+        config: &Config,
+        hold: bool,
     ) -> TerminalViewState {
-        let font = load_terminal_font(14.0);
+        let font = load_terminal_font(config.font.size as f64);
         let (cell_width, cell_height) = measure_cell_size(&font);
+        let mut grid = TerminalGrid::with_scrollback(80, 24, config.scrollback_lines);
+        grid.set_theme(config.theme.clone());
+        grid.set_word_delimiters(&config.word_delimiters);
+        grid.set_clipboard_policy(config.clipboard);
 
         TerminalViewState {
-            grid: TerminalGrid::new(80, 24),
+            grid,
             rx_output,
             tx_input,
+            key_encoder: config.key_encoder,
             font,
             cell_width,
             cell_height,
@@ -281,6 +317,8 @@ impl ShittyAppDelegate {
             master_fd: Some(master_fd),
             slave_fd: Some(slave_fd),
             shell_pgid,
+            refresh_rate: config.refresh_rate,
+            hold,
         }
     }
 
@@ -295,6 +333,7 @@ impl ShittyAppDelegate {
             grid: TerminalGrid::new(80, 24),
             rx_output,
             tx_input,
+            key_encoder: keymap::KeyEncoder::default(),
             font,
             cell_width,
             cell_height,
@@ -302,6 +341,9 @@ impl ShittyAppDelegate {
             master_fd: None,
             slave_fd: None,
             shell_pgid: 0,
+            refresh_rate: DEFAULT_REFRESH_RATE,
+            // There's no shell whose exit could close the window
+            hold: true,
         }
     }
 }
@@ -448,13 +490,23 @@ fn postscript_name_from_descriptor(desc: &CTFontDescriptor) -> Option<String> {
 struct AppDelegateIvars {
     window: std::cell::RefCell<Option<Retained<NSWindow>>>,
     terminal_view: std::cell::RefCell<Option<Retained<ShittyTerminalView>>>,
+    title: String,
+    // Of the window, from --geometry if given
+    content_size: NSSize,
 }
 
 impl AppDelegateIvars {
-    fn new(mtm: MainThreadMarker, view_state: TerminalViewState) -> Self {
+    fn new(
+        mtm: MainThreadMarker,
+        view_state: TerminalViewState,
+        title: String,
+        content_size: NSSize,
+    ) -> Self {
         Self {
             window: std::cell::RefCell::new(None),
             terminal_view: std::cell::RefCell::new(Some(ShittyTerminalView::new(mtm, view_state))),
+            title,
+            content_size,
         }
     }
 }
@@ -484,16 +536,29 @@ define_class!(
 
         #[unsafe(method(keyDown:))]
         fn key_down(&self, event: &NSEvent) {
+            let state_ptr = self.ivars().state;
+            let Some(state) = (unsafe { state_ptr.as_ref() }) else {
+                return;
+            };
             let mut input_bytes = Vec::new();
-            keymap::append_input_from_nsevent(event, &mut input_bytes);
+            keymap::append_input_from_nsevent(
+                &state.key_encoder,
+                event,
+                state.grid.keyboard_modes(),
+                &mut input_bytes,
+            );
             if !input_bytes.is_empty() {
-                let state_ptr = self.ivars().state;
-                if let Some(state) = unsafe { state_ptr.as_ref() } {
-                    let _ = state.tx_input.send(input_bytes);
-                }
+                let _ = state.tx_input.send(input_bytes);
             }
         }
 
+        // The keymap tells key up from key down, and only reports releases
+        // to programs that enable kitty key release events
+        #[unsafe(method(keyUp:))]
+        fn key_up(&self, event: &NSEvent) {
+            self.key_down(event);
+        }
+
         #[unsafe(method(drawRect:))]
         fn draw_rect(&self, _dirty_rect: NSRect) {
             let state_ptr = self.ivars().state;
@@ -622,7 +687,11 @@ define_class!(
                         cell_fg
                     }
                 });
-                let cursor_fg = if cursor_bg == cell_bg { cell_fg } else { cell_bg };
+                let cursor_fg = match state.grid.theme().cursor_text {
+                    Some(cursor_text) => cursor_text,
+                    None if cursor_bg == cell_bg => cell_fg,
+                    None => cell_bg,
+                };
 
                 let cursor_x0 = snap_to_pixel(origin.x + cursor_col as f64 * cell_w, scale);
                 let cursor_x1 =
@@ -678,6 +747,7 @@ define_class!(
 
 impl ShittyTerminalView {
     fn new(mtm: MainThreadMarker, state: TerminalViewState) -> Retained<Self> {
+        let refresh_rate = state.refresh_rate;
         let state_ptr = Box::into_raw(Box::new(state));
         let this = Self::alloc(mtm).set_ivars(TerminalViewIvars { state: state_ptr });
         let view: Retained<Self> = unsafe {
@@ -689,7 +759,7 @@ impl ShittyTerminalView {
         let timer_target = TimerTarget::new(mtm, &view);
         let timer = unsafe {
             objc2_foundation::NSTimer::scheduledTimerWithTimeInterval_target_selector_userInfo_repeats(
-                1.0 / RENDER_REFRESH_RATE_HZ,
+                1.0 / refresh_rate,
                 &timer_target,
                 sel!(onTimerTick:),
                 None,
@@ -766,15 +836,53 @@ define_class!(
             let state = unsafe { &mut *state_ptr };
 
             let mut received_data = false;
-            while let Ok(bytes) = state.rx_output.try_recv() {
-                state.grid.process_pty_bytes(&bytes);
-                received_data = true;
+            let mut shell_exited = false;
+            loop {
+                match state.rx_output.try_recv() {
+                    Ok(bytes) => {
+                        state.grid.process_pty_bytes(&bytes);
+                        received_data = true;
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        shell_exited = !state.hold;
+                        break;
+                    }
+                }
+            }
+
+            let responses = state.grid.take_responses();
+            if !responses.is_empty() {
+                let _ = state.tx_input.send(responses);
+            }
+
+            let mut unconfirmed = Vec::new();
+            for event in state.grid.take_clipboard_events() {
+                if event.needs_confirmation {
+                    queue_unconfirmed(&mut unconfirmed, event.request);
+                } else {
+                    handle_clipboard_request(&state.tx_input, event.request);
+                }
             }
 
             if received_data && state.grid.has_changes() {
                 self.ivars().view.setNeedsDisplay(true);
                 state.grid.mark_rendered();
             }
+
+            // The alert runs its own event loop, which may draw the view, so
+            // `state` isn't touched once it's up
+            let tx_input = state.tx_input.clone();
+            for request in unconfirmed {
+                if confirm_clipboard_request(self.mtm(), &request) {
+                    handle_clipboard_request(&tx_input, request);
+                }
+            }
+
+            // Last, as terminating tears the view and its state down
+            if shell_exited {
+                NSApplication::sharedApplication(self.mtm()).terminate(None);
+            }
         }
     }
 );
@@ -790,6 +898,44 @@ impl TimerTarget {
     }
 }
 
+/// Asks whether an OSC 52 request may go ahead.
+fn confirm_clipboard_request(mtm: MainThreadMarker, request: &ClipboardRequest) -> bool {
+    let message = match request {
+        ClipboardRequest::Store(text) => format!(
+            "A program wants to copy {} characters to the clipboard.",
+            text.chars().count()
+        ),
+        ClipboardRequest::Load { .. } => "A program wants to read the clipboard.".to_string(),
+    };
+    let alert = NSAlert::new(mtm);
+    alert.setMessageText(ns_string!("Clipboard access"));
+    alert.setInformativeText(&NSString::from_str(&message));
+    alert.addButtonWithTitle(ns_string!("Allow"));
+    alert.addButtonWithTitle(ns_string!("Deny"));
+    alert.runModal() == NSAlertFirstButtonReturn
+}
+
+fn handle_clipboard_request(tx_input: &Sender<Vec<u8>>, request: ClipboardRequest) {
+    let pasteboard = NSPasteboard::generalPasteboard();
+    let string_type = unsafe { NSPasteboardTypeString };
+    match request {
+        ClipboardRequest::Store(text) => {
+            pasteboard.clearContents();
+            pasteboard.setString_forType(&NSString::from_str(&text), string_type);
+        }
+        ClipboardRequest::Load {
+            targets,
+            bell_terminated,
+        } => {
+            let text = pasteboard
+                .stringForType(string_type)
+                .map(|text| text.to_string())
+                .unwrap_or_default();
+            let _ = tx_input.send(clipboard_reply(&targets, &text, bell_terminated));
+        }
+    }
+}
+
 fn cast_any_object<T: ?Sized>(obj: &T) -> &AnyObject {
     unsafe { &*(obj as *const T as *const AnyObject) }
 }
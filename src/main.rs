@@ -1,11 +1,39 @@
+use shitty::cli::{self, Action, Options};
 use std::error::Error;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let options = match cli::parse_args(std::env::args_os().skip(1)) {
+        Ok(Action::Run(options)) => options,
+        Ok(Action::Help) => {
+            print!("{}", cli::USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Ok(Action::Version) => {
+            println!("shitty {}", env!("CARGO_PKG_VERSION"));
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("shitty: {err}");
+            eprintln!("Try 'shitty --help' for more information.");
+            return ExitCode::from(2);
+        }
+    };
+    match run(options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("shitty: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
 
 #[cfg(target_os = "macos")]
-fn main() -> Result<(), Box<dyn Error>> {
-    shitty::mac_app::run()
+fn run(options: Options) -> Result<(), Box<dyn Error>> {
+    shitty::mac_app::run(options)
 }
 
 #[cfg(not(target_os = "macos"))]
-fn main() -> Result<(), Box<dyn Error>> {
-    shitty::fallback_app::run()
+fn run(options: Options) -> Result<(), Box<dyn Error>> {
+    shitty::fallback_app::run(options)
 }
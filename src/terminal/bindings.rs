@@ -0,0 +1,274 @@
+// Keyboard shortcuts for the terminal's own actions. Chords are written
+// like "ctrl+shift+f" in the configuration, with the key names egui uses,
+// lower-cased.
+
+use std::fmt;
+
+/// What a shortcut does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum KeyAction {
+    Search,
+    Hints,
+    ViMode,
+    PreviousPrompt,
+    NextPrompt,
+    SelectOutput,
+    NewWindow,
+    ScrollPageUp,
+    ScrollPageDown,
+    ScrollToTop,
+    ScrollToBottom,
+}
+
+impl KeyAction {
+    pub(crate) const ALL: [KeyAction; 11] = [
+        KeyAction::Search,
+        KeyAction::Hints,
+        KeyAction::ViMode,
+        KeyAction::PreviousPrompt,
+        KeyAction::NextPrompt,
+        KeyAction::SelectOutput,
+        KeyAction::NewWindow,
+        KeyAction::ScrollPageUp,
+        KeyAction::ScrollPageDown,
+        KeyAction::ScrollToTop,
+        KeyAction::ScrollToBottom,
+    ];
+
+    /// Name of the action in the `[keybindings]` table
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            KeyAction::Search => "search",
+            KeyAction::Hints => "hints",
+            KeyAction::ViMode => "vi_mode",
+            KeyAction::PreviousPrompt => "previous_prompt",
+            KeyAction::NextPrompt => "next_prompt",
+            KeyAction::SelectOutput => "select_output",
+            KeyAction::NewWindow => "new_window",
+            KeyAction::ScrollPageUp => "scroll_page_up",
+            KeyAction::ScrollPageDown => "scroll_page_down",
+            KeyAction::ScrollToTop => "scroll_to_top",
+            KeyAction::ScrollToBottom => "scroll_to_bottom",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+/// Keys that have a name of more than one character
+const NAMED_KEYS: [&str; 33] = [
+    "up",
+    "down",
+    "left",
+    "right",
+    "escape",
+    "tab",
+    "backspace",
+    "enter",
+    "insert",
+    "delete",
+    "home",
+    "end",
+    "pageup",
+    "pagedown",
+    "space",
+    "colon",
+    "comma",
+    "minus",
+    "period",
+    "plus",
+    "equals",
+    "semicolon",
+    "backslash",
+    "slash",
+    "pipe",
+    "questionmark",
+    "exclamationmark",
+    "openbracket",
+    "closebracket",
+    "opencurlybracket",
+    "closecurlybracket",
+    "backtick",
+    "quote",
+];
+
+/// A key with the modifiers held down with it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct KeyChord {
+    /// Lower-case key name, such as `f`, `f5` or `pageup`
+    pub(crate) key: String,
+    pub(crate) ctrl: bool,
+    pub(crate) shift: bool,
+    pub(crate) alt: bool,
+}
+
+impl KeyChord {
+    fn ctrl_shift(key: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            ctrl: true,
+            shift: true,
+            alt: false,
+        }
+    }
+
+    fn shift(key: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            ctrl: false,
+            shift: true,
+            alt: false,
+        }
+    }
+
+    /// Parses `ctrl+shift+f` and the like. Modifiers come in any order and
+    /// case; the key comes last.
+    pub(crate) fn parse(text: &str) -> Result<Self, String> {
+        let mut chord = Self {
+            key: String::new(),
+            ctrl: false,
+            shift: false,
+            alt: false,
+        };
+        let mut parts = text.split('+').map(str::trim).peekable();
+        while let Some(part) = parts.next() {
+            let part = part.to_ascii_lowercase();
+            if parts.peek().is_none() {
+                chord.key = canonical_key(&part)
+                    .ok_or_else(|| format!("unknown key '{part}' in '{text}'"))?;
+                break;
+            }
+            match part.as_str() {
+                "ctrl" | "control" => chord.ctrl = true,
+                "shift" => chord.shift = true,
+                "alt" | "option" => chord.alt = true,
+                _ => return Err(format!("unknown modifier '{part}' in '{text}'")),
+            }
+        }
+        Ok(chord)
+    }
+}
+
+fn canonical_key(name: &str) -> Option<String> {
+    let name = match name {
+        "esc" => "escape",
+        "return" => "enter",
+        "pgup" => "pageup",
+        "pgdn" => "pagedown",
+        "del" => "delete",
+        "ins" => "insert",
+        name => name,
+    };
+    let single = name.len() == 1 && name.bytes().all(|byte| byte.is_ascii_alphanumeric());
+    let function = name
+        .strip_prefix('f')
+        .and_then(|number| number.parse::<u8>().ok())
+        .is_some_and(|number| (1..=35).contains(&number));
+    (single || function || NAMED_KEYS.contains(&name)).then(|| name.to_string())
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (held, name) in [
+            (self.ctrl, "ctrl+"),
+            (self.shift, "shift+"),
+            (self.alt, "alt+"),
+        ] {
+            if held {
+                f.write_str(name)?;
+            }
+        }
+        f.write_str(&self.key)
+    }
+}
+
+/// Which chords run which action. An action may have several chords, or
+/// none to leave its keys to the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct KeyBindings {
+    bindings: Vec<(KeyChord, KeyAction)>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let bindings = vec![
+            (KeyChord::ctrl_shift("f"), KeyAction::Search),
+            (KeyChord::ctrl_shift("e"), KeyAction::Hints),
+            (KeyChord::ctrl_shift("space"), KeyAction::ViMode),
+            (KeyChord::ctrl_shift("up"), KeyAction::PreviousPrompt),
+            (KeyChord::ctrl_shift("down"), KeyAction::NextPrompt),
+            (KeyChord::ctrl_shift("o"), KeyAction::SelectOutput),
+            (KeyChord::ctrl_shift("n"), KeyAction::NewWindow),
+            (KeyChord::shift("pageup"), KeyAction::ScrollPageUp),
+            (KeyChord::shift("pagedown"), KeyAction::ScrollPageDown),
+            (KeyChord::shift("home"), KeyAction::ScrollToTop),
+            (KeyChord::shift("end"), KeyAction::ScrollToBottom),
+        ];
+        Self { bindings }
+    }
+}
+
+impl KeyBindings {
+    /// Gives `action` the chords in `chords` instead of its current ones.
+    /// Other actions lose those chords.
+    pub(crate) fn bind(&mut self, action: KeyAction, chords: Vec<KeyChord>) {
+        self.bindings
+            .retain(|(chord, bound)| *bound != action && !chords.contains(chord));
+        self.bindings
+            .extend(chords.into_iter().map(|chord| (chord, action)));
+    }
+
+    pub(crate) fn action(&self, chord: &KeyChord) -> Option<KeyAction> {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| bound == chord)
+            .map(|(_, action)| *action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chords_parse_in_any_case_and_order() {
+        let chord = KeyChord::parse("Shift+Ctrl+F").unwrap();
+        assert_eq!(chord, KeyChord::ctrl_shift("f"));
+        assert_eq!(chord.to_string(), "ctrl+shift+f");
+        assert_eq!(KeyChord::parse("alt+esc").unwrap().key, "escape");
+        assert_eq!(KeyChord::parse("f12").unwrap().key, "f12");
+        assert_eq!(
+            KeyChord::parse("ctrl+banana").unwrap_err(),
+            "unknown key 'banana' in 'ctrl+banana'"
+        );
+        assert_eq!(
+            KeyChord::parse("hyper+a").unwrap_err(),
+            "unknown modifier 'hyper' in 'hyper+a'"
+        );
+        assert!(KeyChord::parse("f36").is_err());
+    }
+
+    #[test]
+    fn rebinding_moves_chords_between_actions() {
+        let mut bindings = KeyBindings::default();
+        let search = KeyChord::ctrl_shift("f");
+        assert_eq!(bindings.action(&search), Some(KeyAction::Search));
+
+        bindings.bind(KeyAction::Hints, vec![search.clone()]);
+        assert_eq!(bindings.action(&search), Some(KeyAction::Hints));
+        assert_eq!(bindings.action(&KeyChord::ctrl_shift("e")), None);
+
+        bindings.bind(KeyAction::Hints, Vec::new());
+        assert_eq!(bindings.action(&search), None);
+    }
+
+    #[test]
+    fn actions_round_trip_through_their_names() {
+        for action in KeyAction::ALL {
+            assert_eq!(KeyAction::from_name(action.name()), Some(action));
+        }
+        assert_eq!(KeyAction::from_name("paste"), None);
+    }
+}
@@ -0,0 +1,164 @@
+// OSC 52 clipboard access for programs that can't reach the local clipboard
+// any other way, like tmux or neovim over ssh. The grid queues requests that
+// pass the policy; front ends own the actual clipboard.
+
+/// Whether programs may use one direction of OSC 52
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ClipboardAccess {
+    Allow,
+    /// Let the user decide each time
+    Ask,
+    Deny,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ClipboardPolicy {
+    pub(crate) write: ClipboardAccess,
+    pub(crate) read: ClipboardAccess,
+}
+
+impl Default for ClipboardPolicy {
+    fn default() -> Self {
+        // Reading hands the clipboard to whatever runs in the terminal,
+        // including remote hosts, so it's opt-in
+        Self {
+            write: ClipboardAccess::Allow,
+            read: ClipboardAccess::Deny,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ClipboardRequest {
+    /// Put `text` on the clipboard
+    Store(String),
+    /// Send the clipboard contents back, answering `OSC 52 ; targets ; ?`
+    Load {
+        targets: String,
+        bell_terminated: bool,
+    },
+}
+
+/// A request that passed the policy, possibly pending the user's approval
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ClipboardEvent {
+    pub(crate) request: ClipboardRequest,
+    pub(crate) needs_confirmation: bool,
+}
+
+/// Adds a request to those waiting for the user's answer, keeping at most
+/// one of each kind so a program repeating OSC 52 can't pile up prompts. A
+/// newer store replaces the waiting one; a waiting read answers later ones.
+pub(crate) fn queue_unconfirmed(pending: &mut Vec<ClipboardRequest>, request: ClipboardRequest) {
+    let waiting = pending.iter_mut().find(|waiting| {
+        matches!(
+            (&**waiting, &request),
+            (ClipboardRequest::Store(_), ClipboardRequest::Store(_))
+                | (ClipboardRequest::Load { .. }, ClipboardRequest::Load { .. })
+        )
+    });
+    match waiting {
+        Some(waiting @ ClipboardRequest::Store(_)) => *waiting = request,
+        Some(_) => {}
+        None => pending.push(request),
+    }
+}
+
+/// The reply to a clipboard query, terminated like the query was
+pub(crate) fn clipboard_reply(targets: &str, text: &str, bell_terminated: bool) -> Vec<u8> {
+    let terminator = if bell_terminated { "\x07" } else { "\x1b\\" };
+    format!(
+        "\x1b]52;{targets};{}{terminator}",
+        base64_encode(text.as_bytes())
+    )
+    .into_bytes()
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub(crate) fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3f;
+                out.push(char::from(BASE64_ALPHABET[index as usize]));
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decodes standard base64, padded or not. Returns `None` on anything else.
+pub(crate) fn base64_decode(text: &[u8]) -> Option<Vec<u8>> {
+    let text = text
+        .strip_suffix(b"==")
+        .or(text.strip_suffix(b"="))
+        .unwrap_or(text);
+    if text.len() % 4 == 1 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    for chunk in text.chunks(4) {
+        let mut group = 0u32;
+        for (i, &byte) in chunk.iter().enumerate() {
+            let value = BASE64_ALPHABET.iter().position(|&c| c == byte)?;
+            group |= (value as u32) << (18 - 6 * i);
+        }
+        let bytes = group.to_be_bytes();
+        out.extend_from_slice(&bytes[1..chunk.len()]);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_request_of_each_kind_waits() {
+        let load = || ClipboardRequest::Load {
+            targets: "c".to_string(),
+            bell_terminated: true,
+        };
+        let mut pending = Vec::new();
+        for _ in 0..1000 {
+            queue_unconfirmed(&mut pending, load());
+        }
+        queue_unconfirmed(&mut pending, ClipboardRequest::Store("old".to_string()));
+        queue_unconfirmed(&mut pending, ClipboardRequest::Store("new".to_string()));
+        assert_eq!(
+            pending,
+            [load(), ClipboardRequest::Store("new".to_string())]
+        );
+    }
+
+    #[test]
+    fn base64_round_trips() {
+        for text in ["", "f", "fo", "foo", "foob", "fooba", "foobar", "héllo\n"] {
+            let encoded = base64_encode(text.as_bytes());
+            assert_eq!(base64_decode(encoded.as_bytes()).unwrap(), text.as_bytes());
+        }
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64_encode(b"fooba"), "Zm9vYmE=");
+    }
+
+    #[test]
+    fn base64_accepts_missing_padding_and_rejects_garbage() {
+        assert_eq!(base64_decode(b"Zm9vYg").unwrap(), b"foob");
+        assert_eq!(base64_decode(b"Zm9v!mFy"), None);
+        assert_eq!(base64_decode(b"Zm9vY"), None);
+    }
+
+    #[test]
+    fn replies_use_the_query_terminator() {
+        assert_eq!(clipboard_reply("c", "hi", true), b"\x1b]52;c;aGk=\x07");
+        assert_eq!(clipboard_reply("", "hi", false), b"\x1b]52;;aGk=\x1b\\");
+    }
+}
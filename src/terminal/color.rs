@@ -19,6 +19,37 @@ impl Color32 {
 pub(crate) const DEFAULT_FG: Color32 = Color32::from_rgb(204, 204, 204);
 pub(crate) const DEFAULT_BG: Color32 = Color32::BLACK;
 
+/// The colors a terminal starts with, and returns to when a program resets
+/// the ones it changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Theme {
+    pub(crate) foreground: Color32,
+    pub(crate) background: Color32,
+    /// Without one, the cursor takes the color of the text under it
+    pub(crate) cursor: Option<Color32>,
+    /// Text under a block cursor. Without one, it's picked to stand out
+    /// from the cursor.
+    pub(crate) cursor_text: Option<Color32>,
+    /// Without these, selected cells swap their foreground and background
+    pub(crate) selection_foreground: Option<Color32>,
+    pub(crate) selection_background: Option<Color32>,
+    pub(crate) palette: [Color32; 256],
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            foreground: DEFAULT_FG,
+            background: DEFAULT_BG,
+            cursor: None,
+            cursor_text: None,
+            selection_foreground: None,
+            selection_background: None,
+            palette: std::array::from_fn(|index| xterm_256_color(index as u8)),
+        }
+    }
+}
+
 // Lookup table for RGB levels used in xterm 256 color mode
 const RGB_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
 
@@ -63,3 +94,105 @@ pub(crate) fn xterm_256_color(index: u8) -> Color32 {
     let gray = 8u8.saturating_add((index - 232).saturating_mul(10));
     Color32::from_rgb(gray, gray, gray)
 }
+
+/// Parses an X11 color specification as used by OSC 4/10/11/12:
+/// `rgb:r/g/b` with 1-4 hex digits per channel, or `#rgb` style hex with
+/// 1-4 digits per channel.
+pub(crate) fn parse_color_spec(spec: &str) -> Option<Color32> {
+    if let Some(rest) = spec.strip_prefix("rgb:") {
+        let mut parts = rest.split('/');
+        let r = scale_hex_channel(parts.next()?)?;
+        let g = scale_hex_channel(parts.next()?)?;
+        let b = scale_hex_channel(parts.next()?)?;
+        if parts.next().is_some() {
+            return None;
+        }
+        return Some(Color32::from_rgb(r, g, b));
+    }
+    let hex = spec.strip_prefix('#')?;
+    // Checked before slicing, since OSC strings may carry any UTF-8
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    if hex.is_empty() || hex.len() % 3 != 0 || hex.len() > 12 {
+        return None;
+    }
+    let width = hex.len() / 3;
+    let r = scale_hex_channel(&hex[..width])?;
+    let g = scale_hex_channel(&hex[width..width * 2])?;
+    let b = scale_hex_channel(&hex[width * 2..])?;
+    Some(Color32::from_rgb(r, g, b))
+}
+
+/// Formats a color the way xterm reports it in OSC color query replies
+pub(crate) fn format_color_spec(color: Color32) -> String {
+    format!(
+        "rgb:{0:02x}{0:02x}/{1:02x}{1:02x}/{2:02x}{2:02x}",
+        color.r, color.g, color.b
+    )
+}
+
+// Scales a 1-4 digit hex channel to 8 bits, so "f", "ff" and "ffff" all mean 255
+fn scale_hex_channel(digits: &str) -> Option<u8> {
+    // from_str_radix would also take a leading '+'
+    if digits.is_empty() || digits.len() > 4 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let value = u32::from_str_radix(digits, 16).ok()?;
+    let max = (1u32 << (4 * digits.len())) - 1;
+    Some(((value * 255 + max / 2) / max) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rgb_specs_of_any_width() {
+        assert_eq!(
+            parse_color_spec("rgb:ff/80/00"),
+            Some(Color32::from_rgb(255, 128, 0))
+        );
+        assert_eq!(
+            parse_color_spec("rgb:f/8/0"),
+            Some(Color32::from_rgb(255, 136, 0))
+        );
+        assert_eq!(
+            parse_color_spec("rgb:ffff/0000/8080"),
+            Some(Color32::from_rgb(255, 0, 128))
+        );
+    }
+
+    #[test]
+    fn parses_hash_specs() {
+        assert_eq!(
+            parse_color_spec("#1e1e2e"),
+            Some(Color32::from_rgb(30, 30, 46))
+        );
+        assert_eq!(parse_color_spec("#fff"), Some(Color32::WHITE));
+    }
+
+    #[test]
+    fn formatted_specs_round_trip() {
+        let color = Color32::from_rgb(0x1e, 0x80, 0xff);
+        assert_eq!(format_color_spec(color), "rgb:1e1e/8080/ffff");
+        assert_eq!(parse_color_spec(&format_color_spec(color)), Some(color));
+    }
+
+    #[test]
+    fn rejects_malformed_specs() {
+        assert_eq!(parse_color_spec("rgb:ff/80"), None);
+        assert_eq!(parse_color_spec("rgb:ff/80/00/00"), None);
+        assert_eq!(parse_color_spec("#12345"), None);
+        assert_eq!(parse_color_spec("#gggggg"), None);
+        assert_eq!(parse_color_spec("red"), None);
+    }
+
+    #[test]
+    fn rejects_non_hex_characters_without_panicking() {
+        assert_eq!(parse_color_spec("#a\u{e9}"), None);
+        assert_eq!(parse_color_spec("#\u{e9}\u{e9}\u{e9}"), None);
+        assert_eq!(parse_color_spec("#+f+f+f"), None);
+        assert_eq!(parse_color_spec("rgb:+f/+f/+f"), None);
+    }
+}
@@ -0,0 +1,149 @@
+// The shell's working directory, from its OSC 7 reports or, failing those,
+// from what the system knows about the foreground process.
+
+use nix::libc::{pid_t, tcgetpgrp};
+use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::OsStringExt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// A directory reported by `OSC 7 ; file://host/path`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ReportedDir {
+    pub(crate) host: String,
+    pub(crate) path: PathBuf,
+}
+
+impl ReportedDir {
+    /// Parses a `file://` URI, percent-decoding its path
+    pub(crate) fn parse(uri: &[u8]) -> Option<Self> {
+        let rest = uri.strip_prefix(b"file://")?;
+        let slash = rest.iter().position(|&byte| byte == b'/')?;
+        let (host, path) = rest.split_at(slash);
+        Some(Self {
+            host: String::from_utf8_lossy(host).into_owned(),
+            path: PathBuf::from(OsString::from_vec(percent_decode(path)?)),
+        })
+    }
+
+    /// Whether the directory is on this machine, rather than at the other
+    /// end of an ssh session
+    pub(crate) fn is_local(&self) -> bool {
+        if self.host.is_empty() || self.host.eq_ignore_ascii_case("localhost") {
+            return true;
+        }
+        let Some(hostname) = hostname() else {
+            return false;
+        };
+        // Shells report either the short or the fully qualified name
+        let short = |name: &str| name.split('.').next().unwrap_or_default().to_string();
+        hostname.eq_ignore_ascii_case(&self.host)
+            || short(hostname).eq_ignore_ascii_case(&short(&self.host))
+    }
+}
+
+/// `~` and `~/...` in paths stand for the home directory
+pub(crate) fn expand_home(path: &str) -> PathBuf {
+    let home = std::env::var_os("HOME").filter(|home| !home.is_empty());
+    match (path.strip_prefix('~'), home) {
+        (Some(""), Some(home)) => PathBuf::from(home),
+        (Some(rest), Some(home)) if rest.starts_with('/') => {
+            Path::new(&home).join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+// Looked up once; output can bring an OSC 7 report with every prompt
+fn hostname() -> Option<&'static str> {
+    static HOSTNAME: OnceLock<Option<String>> = OnceLock::new();
+    HOSTNAME
+        .get_or_init(|| {
+            let hostname = nix::unistd::gethostname().ok()?;
+            Some(hostname.to_string_lossy().into_owned())
+        })
+        .as_deref()
+}
+
+fn percent_decode(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut rest = bytes;
+    while let [byte, tail @ ..] = rest {
+        if *byte != b'%' {
+            decoded.push(*byte);
+            rest = tail;
+            continue;
+        }
+        let [high, low, tail @ ..] = tail else {
+            return None;
+        };
+        let digit = |byte: u8| (byte as char).to_digit(16);
+        decoded.push((digit(*high)? * 16 + digit(*low)?) as u8);
+        rest = tail;
+    }
+    Some(decoded)
+}
+
+/// The foreground process group on the PTY `fd`, or the shell's when that
+/// can't be told
+pub(crate) fn foreground_pgid(fd: i32, shell_pgid: pid_t) -> pid_t {
+    let pgid = unsafe { tcgetpgrp(fd) };
+    if pgid > 0 { pgid } else { shell_pgid }
+}
+
+/// Working directory of the leader of process group `pgid`. Needs `/proc`,
+/// so this is Linux only.
+pub(crate) fn process_group_dir(pgid: pid_t) -> Option<PathBuf> {
+    // A group's id is the pid of its leader
+    std::fs::read_link(format!("/proc/{pgid}/cwd")).ok()
+}
+
+/// `path` for display, with the home directory shortened to `~`
+pub(crate) fn display_dir(path: &Path, home: Option<&OsStr>) -> String {
+    match home.and_then(|home| path.strip_prefix(home).ok()) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn osc_7_uris_are_decoded() {
+        let dir = ReportedDir::parse(b"file://box/home/me/my%20dir%2Fx").unwrap();
+        assert_eq!(dir.host, "box");
+        assert_eq!(dir.path, Path::new("/home/me/my dir/x"));
+        assert!(ReportedDir::parse(b"file:///tmp").unwrap().is_local());
+        assert!(
+            ReportedDir::parse(b"file://localhost/tmp")
+                .unwrap()
+                .is_local()
+        );
+        assert_eq!(ReportedDir::parse(b"http://box/tmp"), None);
+        assert_eq!(ReportedDir::parse(b"file://box/%zz"), None);
+        assert_eq!(ReportedDir::parse(b"file://box/%2"), None);
+    }
+
+    #[test]
+    fn home_is_shortened_for_display() {
+        let home = Some(OsStr::new("/home/me"));
+        assert_eq!(display_dir(Path::new("/home/me"), home), "~");
+        assert_eq!(display_dir(Path::new("/home/me/src"), home), "~/src");
+        assert_eq!(display_dir(Path::new("/home/meg"), home), "/home/meg");
+        assert_eq!(display_dir(Path::new("/tmp"), None), "/tmp");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn foreground_group_falls_back_to_the_shell() {
+        let pid = std::process::id() as pid_t;
+        assert_eq!(foreground_pgid(-1, pid), pid);
+        assert_eq!(
+            process_group_dir(pid),
+            Some(std::env::current_dir().unwrap())
+        );
+    }
+}
@@ -0,0 +1,506 @@
+// Detection of URLs, file references, IP addresses and git hashes in plain
+// screen text, for programs that don't mark their links up with OSC 8.
+// Matchers are regexes over a logical line, soft-wrapped rows already
+// joined, with checks the regex crate can't express done afterwards.
+
+use std::io;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::terminal::cwd::expand_home;
+use crate::terminal::hyperlink::spawn_detached;
+use crate::terminal::selection::Point;
+
+const URL_SCHEMES: [&str; 8] = [
+    "https://", "http://", "file://", "ftp://", "sftp://", "ssh://", "git://", "mailto:",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum MatchKind {
+    Url,
+    /// A file path, optionally followed by `:line` or `:line:col`
+    Path {
+        line: Option<usize>,
+        col: Option<usize>,
+    },
+    /// An IPv4 address, optionally with a port
+    Ip,
+    GitSha,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TextMatch {
+    pub(crate) kind: MatchKind,
+    /// Char range in the searched text
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    /// The matched text, without the position for paths
+    pub(crate) text: String,
+}
+
+/// A match on the screen, from its first to its last buffer cell
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DetectedMatch {
+    pub(crate) kind: MatchKind,
+    pub(crate) text: String,
+    pub(crate) start: Point,
+    pub(crate) end: Point,
+}
+
+impl DetectedMatch {
+    /// The match with a path in it made absolute, `~` standing for the
+    /// home directory and relative paths starting from `dir`, the shell's
+    /// directory. Openers and editors run in the terminal's own directory.
+    pub(crate) fn resolved(&self, dir: Option<&Path>) -> DetectedMatch {
+        let MatchKind::Path { .. } = self.kind else {
+            return self.clone();
+        };
+        let path = expand_home(&self.text);
+        let path = match dir {
+            Some(dir) if path.is_relative() => dir.join(path),
+            _ => path,
+        };
+        DetectedMatch {
+            text: path.to_string_lossy().into_owned(),
+            ..self.clone()
+        }
+    }
+}
+
+/// What clicking a match does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ClickAction {
+    /// Hand it to the link opener
+    Open,
+    Copy,
+    /// Run the editor command
+    Edit,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DetectorConfig {
+    pub(crate) url: ClickAction,
+    pub(crate) path: ClickAction,
+    pub(crate) ip: ClickAction,
+    pub(crate) git_sha: ClickAction,
+    /// Program and arguments; `{file}`, `{line}` and `{col}` are replaced
+    /// with the match, lines and columns defaulting to 1
+    pub(crate) editor: Vec<String>,
+}
+
+impl Default for DetectorConfig {
+    fn default() -> Self {
+        Self {
+            url: ClickAction::Open,
+            path: ClickAction::Open,
+            ip: ClickAction::Copy,
+            git_sha: ClickAction::Copy,
+            editor: ["code", "--goto", "{file}:{line}:{col}"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+impl DetectorConfig {
+    pub(crate) fn action(&self, kind: &MatchKind) -> ClickAction {
+        match kind {
+            MatchKind::Url => self.url,
+            MatchKind::Path { .. } => self.path,
+            MatchKind::Ip => self.ip,
+            MatchKind::GitSha => self.git_sha,
+        }
+    }
+
+    /// The editor command for a match, with its placeholders filled in
+    pub(crate) fn editor_command(&self, found: &DetectedMatch) -> Vec<String> {
+        let (line, col) = match found.kind {
+            MatchKind::Path { line, col } => (line.unwrap_or(1), col.unwrap_or(1)),
+            _ => (1, 1),
+        };
+        self.editor
+            .iter()
+            .map(|arg| {
+                arg.replace("{file}", &found.text)
+                    .replace("{line}", &line.to_string())
+                    .replace("{col}", &col.to_string())
+            })
+            .collect()
+    }
+
+    pub(crate) fn edit(&self, found: &DetectedMatch) -> io::Result<()> {
+        // Same rule as the link opener: output doesn't get to pass options
+        if found.text.starts_with('-') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "match starts with '-'",
+            ));
+        }
+        let command = self.editor_command(found);
+        let Some((program, args)) = command.split_first() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no editor command",
+            ));
+        };
+        spawn_detached(program, args)
+    }
+}
+
+// Start of a word: the line start or a char that can't continue one.
+// Consumed, as the regex crate has no look-behind.
+const WORD_START: &str = r"(?:^|[^\p{Alphabetic}\p{N}])";
+
+static URL: LazyLock<Regex> = LazyLock::new(|| {
+    let schemes: Vec<String> = URL_SCHEMES.iter().map(|s| regex::escape(s)).collect();
+    let pattern = format!(
+        r#"(?i){WORD_START}(?P<url>(?P<scheme>{})[^\s\p{{Cc}}<>"`{{}}|\\\^│]+)"#,
+        schemes.join("|")
+    );
+    Regex::new(&pattern).unwrap()
+});
+
+static PATH: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?P<path>[\p{Alphabetic}\p{N}/._~+@-]+)(?::(?P<line>[0-9]+)(?::(?P<col>[0-9]+))?)?",
+    )
+    .unwrap()
+});
+
+// Dots can't come before an address either, or versions like v1.2.3.4.5
+// would match in their middle
+static IP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?:^|[^\p{Alphabetic}\p{N}.])(?P<ip>[0-9]{1,3}(?:\.[0-9]{1,3}){3})(?::(?P<port>[0-9]+))?",
+    )
+    .unwrap()
+});
+
+static GIT_SHA: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b[0-9a-fA-F]{7,40}\b").unwrap());
+
+/// Every URL, file reference, IP address and git hash in `text`, in order.
+/// Earlier kinds in that list win over later ones.
+pub(crate) fn find_matches(text: &str) -> Vec<TextMatch> {
+    let mut matches = find_urls(text);
+    let paths = find_paths(text, &matches);
+    matches.extend(paths);
+    let ips = find_ips(text, &matches);
+    matches.extend(ips);
+    let hashes = find_git_shas(text, &matches);
+    matches.extend(hashes);
+    matches.sort_by_key(|found| found.start);
+    // Byte offset of every char, to turn match offsets back into chars
+    let offsets: Vec<usize> = text.char_indices().map(|(offset, _)| offset).collect();
+    let char_index = |offset| offsets.binary_search(&offset).unwrap_or_else(|end| end);
+    for found in &mut matches {
+        found.start = char_index(found.start);
+        found.end = char_index(found.end);
+    }
+    matches
+}
+
+// Matchers below work in byte offsets, which find_matches turns into chars
+fn text_match(text: &str, kind: MatchKind, start: usize, end: usize) -> TextMatch {
+    TextMatch {
+        kind,
+        start,
+        end,
+        text: text[start..end].to_string(),
+    }
+}
+
+fn overlaps(matches: &[TextMatch], start: usize, end: usize) -> bool {
+    matches
+        .iter()
+        .any(|found| start < found.end && found.start < end)
+}
+
+fn find_urls(text: &str) -> Vec<TextMatch> {
+    let mut matches = Vec::new();
+    for caps in URL.captures_iter(text) {
+        let url = caps.name("url").unwrap();
+        let body = caps.name("scheme").unwrap().end();
+        let end = url.start() + trim_url_end(url.as_str());
+        if end > body {
+            matches.push(text_match(text, MatchKind::Url, url.start(), end));
+        }
+    }
+    matches
+}
+
+// Drops sentence punctuation and closing brackets that the URL didn't open,
+// as in "(see https://example.com/a)."
+fn trim_url_end(url: &str) -> usize {
+    let mut end = url.len();
+    while let Some(last) = url[..end].chars().next_back() {
+        let unbalanced = |open, close| {
+            let count = |c| url[..end].matches(c).count();
+            count(close) > count(open)
+        };
+        let drop = match last {
+            '.' | ',' | ':' | ';' | '!' | '?' | '\'' => true,
+            ')' => unbalanced('(', ')'),
+            ']' => unbalanced('[', ']'),
+            _ => false,
+        };
+        if !drop {
+            break;
+        }
+        end -= last.len_utf8();
+    }
+    end
+}
+
+fn find_paths(text: &str, urls: &[TextMatch]) -> Vec<TextMatch> {
+    let mut matches = Vec::new();
+    for caps in PATH.captures_iter(text) {
+        let found = caps.name("path").unwrap();
+        let number = |name| {
+            let group = caps.name(name)?;
+            Some((group.as_str().parse::<usize>().ok()?, group.end()))
+        };
+        let mut path = found.as_str();
+        let mut position = (None, None);
+        let end = match number("line") {
+            Some((line, end)) => {
+                position.0 = Some(line);
+                match number("col") {
+                    Some((col, end)) => {
+                        position.1 = Some(col);
+                        end
+                    }
+                    None => end,
+                }
+            }
+            None => {
+                // A path ending a sentence
+                path = path.trim_end_matches(['.', '-']);
+                found.start() + path.len()
+            }
+        };
+        let has_letter = path.chars().any(char::is_alphabetic);
+        let has_dir = path.contains('/') && has_letter;
+        // Without a directory, only `name.ext:line` looks enough like a file
+        let has_extension = path
+            .rfind('.')
+            .is_some_and(|dot| dot > 0 && dot + 1 < path.len());
+        let is_file_ref = position.0.is_some() && has_extension && has_letter;
+        if (has_dir || is_file_ref) && !overlaps(urls, found.start(), end) {
+            matches.push(TextMatch {
+                kind: MatchKind::Path {
+                    line: position.0,
+                    col: position.1,
+                },
+                start: found.start(),
+                end,
+                text: path.to_string(),
+            });
+        }
+    }
+    matches
+}
+
+fn find_ips(text: &str, earlier: &[TextMatch]) -> Vec<TextMatch> {
+    let mut matches = Vec::new();
+    let mut from = 0;
+    while let Some(caps) = IP.captures_at(text, from) {
+        let ip = caps.name("ip").unwrap();
+        let mut end = ip.end();
+        if let Some(port) = caps.name("port")
+            && port.as_str().parse::<u32>().is_ok_and(|port| port <= 65535)
+        {
+            end = port.end();
+        }
+        let valid = ip
+            .as_str()
+            .split('.')
+            .all(|octet| octet.parse::<u8>().is_ok());
+        // Longer dotted numbers like versions aren't addresses
+        let mut rest = text[end..].chars();
+        let at_word_end = match rest.next() {
+            Some(c) if c.is_alphanumeric() => false,
+            Some('.') => !rest.next().is_some_and(char::is_alphanumeric),
+            _ => true,
+        };
+        if valid && at_word_end {
+            if !overlaps(earlier, ip.start(), end) {
+                matches.push(text_match(text, MatchKind::Ip, ip.start(), end));
+            }
+            from = end;
+        } else {
+            // A port may hide the start of another address, as in
+            // "1.2.3.400:1.2.3.4"
+            from = ip.start() + 1;
+        }
+    }
+    matches
+}
+
+fn find_git_shas(text: &str, earlier: &[TextMatch]) -> Vec<TextMatch> {
+    GIT_SHA
+        .find_iter(text)
+        // Both a digit and a letter, so plain numbers and words like
+        // "deadbeef" or "facade" stay out
+        .filter(|word| {
+            let word = word.as_str();
+            word.contains(|c: char| c.is_ascii_digit())
+                && word.contains(|c: char| c.is_ascii_alphabetic())
+        })
+        .filter(|word| !overlaps(earlier, word.start(), word.end()))
+        .map(|word| text_match(text, MatchKind::GitSha, word.start(), word.end()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn found(text: &str) -> Vec<(MatchKind, String)> {
+        find_matches(text)
+            .into_iter()
+            .map(|found| (found.kind, found.text))
+            .collect()
+    }
+
+    fn path(text: &str, line: Option<usize>, col: Option<usize>) -> (MatchKind, String) {
+        (MatchKind::Path { line, col }, text.to_string())
+    }
+
+    #[test]
+    fn urls_drop_trailing_punctuation_and_stray_brackets() {
+        assert_eq!(
+            found("see (https://example.com/a_(b)?q=1). or <http://x.org>"),
+            vec![
+                (MatchKind::Url, "https://example.com/a_(b)?q=1".to_string()),
+                (MatchKind::Url, "http://x.org".to_string()),
+            ]
+        );
+        assert_eq!(found("https:// alone"), vec![]);
+    }
+
+    #[test]
+    fn file_references_carry_their_position() {
+        assert_eq!(
+            found("error at src/main.rs:12:5: oops"),
+            vec![path("src/main.rs", Some(12), Some(5))]
+        );
+        assert_eq!(found("lib.rs:7"), vec![path("lib.rs", Some(7), None)]);
+        assert_eq!(
+            found("cat /etc/hosts."),
+            vec![path("/etc/hosts", None, None)]
+        );
+        // Bare names and fractions aren't paths
+        assert_eq!(found("main.rs 1/2 and:3"), vec![]);
+    }
+
+    #[test]
+    fn ipv4_addresses_may_have_a_port() {
+        assert_eq!(
+            found("ping 10.0.0.1, ssh 192.168.1.20:2222."),
+            vec![
+                (MatchKind::Ip, "10.0.0.1".to_string()),
+                (MatchKind::Ip, "192.168.1.20:2222".to_string()),
+            ]
+        );
+        assert_eq!(found("1.2.3.4.5 256.1.1.1 v1.2.3.4"), vec![]);
+    }
+
+    #[test]
+    fn git_shas_need_letters_and_digits() {
+        assert_eq!(
+            found("9cce8ee fix, deadbeef 1234567 e439348a0"),
+            vec![
+                (MatchKind::GitSha, "9cce8ee".to_string()),
+                (MatchKind::GitSha, "e439348a0".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn urls_and_paths_hide_what_they_contain() {
+        assert_eq!(
+            found("https://github.com/o/r/commit/9cce8ee1"),
+            vec![(
+                MatchKind::Url,
+                "https://github.com/o/r/commit/9cce8ee1".to_string()
+            )]
+        );
+        assert_eq!(
+            found("objects/9c/ce8ee12"),
+            vec![path("objects/9c/ce8ee12", None, None)]
+        );
+    }
+
+    #[test]
+    fn ranges_count_chars_not_bytes() {
+        let matches = find_matches("café → http://é.fr/ok 1.2.3.400:1.2.3.4");
+        let ranges: Vec<_> = matches.iter().map(|m| (m.start, m.end)).collect();
+        assert_eq!(ranges, [(7, 21), (32, 39)]);
+        assert_eq!(matches[0].text, "http://é.fr/ok");
+    }
+
+    #[test]
+    fn paths_resolve_against_the_shell_directory() {
+        let found = |text: &str, kind| DetectedMatch {
+            kind,
+            text: text.to_string(),
+            start: Point::new(0, 0),
+            end: Point::new(0, 1),
+        };
+        let path = MatchKind::Path {
+            line: Some(12),
+            col: None,
+        };
+        let dir = Some(Path::new("/home/me/project"));
+        let resolved = found("src/main.rs", path.clone()).resolved(dir);
+        assert_eq!(resolved.text, "/home/me/project/src/main.rs");
+        assert_eq!(resolved.kind, path);
+        assert_eq!(
+            found("/etc/hosts", path.clone()).resolved(dir).text,
+            "/etc/hosts"
+        );
+        assert_eq!(
+            found("src/main.rs", path.clone()).resolved(None).text,
+            "src/main.rs"
+        );
+        if let Some(home) = std::env::var_os("HOME").filter(|home| !home.is_empty()) {
+            assert_eq!(
+                Path::new(&found("~/notes.md", path).resolved(dir).text),
+                Path::new(&home).join("notes.md")
+            );
+        }
+        // Only paths are touched
+        assert_eq!(
+            found("https://x.org/a", MatchKind::Url).resolved(dir).text,
+            "https://x.org/a"
+        );
+    }
+
+    #[test]
+    fn editor_placeholders_are_filled_in() {
+        let config = DetectorConfig::default();
+        let found = DetectedMatch {
+            kind: MatchKind::Path {
+                line: Some(3),
+                col: None,
+            },
+            text: "a.rs".to_string(),
+            start: Point::new(0, 0),
+            end: Point::new(0, 5),
+        };
+        assert_eq!(
+            config.editor_command(&found),
+            ["code", "--goto", "a.rs:3:1"]
+        );
+        assert!(
+            config
+                .edit(&DetectedMatch {
+                    text: "-rf".to_string(),
+                    ..found
+                })
+                .is_err()
+        );
+    }
+}
@@ -1,29 +1,133 @@
-use crate::terminal::color::{Color32, DEFAULT_BG, DEFAULT_FG, xterm_256_color};
+use crate::terminal::clipboard::{
+    ClipboardAccess, ClipboardEvent, ClipboardPolicy, ClipboardRequest,
+};
+use crate::terminal::color::{Color32, Theme, format_color_spec};
+use crate::terminal::cwd::ReportedDir;
+use crate::terminal::detect::{DetectedMatch, find_matches};
+use crate::terminal::hyperlink::Hyperlink;
+use crate::terminal::keymap::KeyboardModes;
+use crate::terminal::prompt::{ShellCommand, ShellCommands};
+use crate::terminal::search::{Matcher, PatternError, SearchHighlight, SearchMatch, SearchQuery};
+use crate::terminal::selection::{DEFAULT_WORD_DELIMITERS, Point, Selection, SelectionMode};
+use crate::terminal::sequences::{
+    DynamicColor, FlagsUpdate, Sequence, SequenceScanner, TitleParts,
+};
+use std::collections::HashMap;
+use unicode_width::UnicodeWidthChar;
+
+/// DA1 reply: a VT220-class terminal with ANSI color
+const PRIMARY_DEVICE_ATTRIBUTES: &[u8] = b"\x1b[?62;22c";
+
+/// Deepest the XTWINOPS title stack may grow, matching xterm
+const TITLE_STACK_LIMIT: usize = 10;
+
+/// Deepest each kitty keyboard flag stack may grow before the oldest entry
+/// is dropped
+const KEYBOARD_FLAG_STACK_LIMIT: usize = 16;
+
+/// Number of lines kept above the screen when no explicit size is given
+pub(crate) const DEFAULT_SCROLLBACK_LINES: usize = 10_000;
 
 pub(crate) struct TerminalGrid {
     parser: vt100::Parser,
+    scanner: SequenceScanner,
+    theme: Theme,
+    // Colors the program changed with OSC 4, 10, 11 and 12 take the place
+    // of the theme's until it resets them
     palette: [Option<Color32>; 256],
     default_fg: Color32,
     default_bg: Color32,
     cursor_color: Option<Color32>,
+    // Replies to terminal queries, waiting to be written back to the PTY
+    responses: Vec<u8>,
+    title: String,
+    icon_name: String,
+    // (title, icon name) pairs saved by CSI 22 t, holding only the parts the
+    // push asked for
+    title_stack: Vec<(Option<String>, Option<String>)>,
+    // kitty keyboard flags pushed by the program, kept separately for the
+    // main and the alternate screen
+    keyboard_flag_stacks: [Vec<u8>; 2],
+    // xterm modifyOtherKeys level, 0 to 2
+    modify_other_keys: u8,
+    // Lines that have scrolled into the scrollback so far. Buffer lines are
+    // numbered from the first line ever shown, which keeps their numbers
+    // stable while output scrolls and old lines drop off.
+    scrolled_lines: usize,
+    // Lines currently in the scrollback, refreshed whenever output arrives
+    history_len: usize,
+    // Most lines the main screen's scrollback holds
+    scrollback_capacity: usize,
+    selection: Option<Selection>,
+    word_delimiters: String,
+    clipboard_policy: ClipboardPolicy,
+    // OSC 52 requests the policy let through, waiting for the front end
+    clipboard_events: Vec<ClipboardEvent>,
+    // OSC 8 links, referred to by their index
+    hyperlinks: Vec<Hyperlink>,
+    active_hyperlink: Option<u32>,
+    // Cells printed inside a link, keyed by (screen index, buffer line,
+    // column), with the character printed there. Cells whose character has
+    // since changed no longer count as linked.
+    link_cells: HashMap<(usize, usize, usize), (u32, char)>,
+    // Last directory the shell reported with OSC 7
+    reported_dir: Option<ReportedDir>,
+    // Prompts and commands marked by OSC 133 on the main screen
+    commands: ShellCommands,
+    search: Option<SearchState>,
     has_changes: bool,
 }
 
+struct SearchState {
+    matcher: Matcher,
+    matches: Vec<SearchMatch>,
+    current: Option<usize>,
+    // First line that may have changed since the matches were collected.
+    // Output only ever changes the live screen; lines above it are final.
+    stale_from: Option<usize>,
+}
+
 impl TerminalGrid {
+    #[allow(dead_code)]
     pub(crate) fn new(cols: usize, rows: usize) -> Self {
+        Self::with_scrollback(cols, rows, DEFAULT_SCROLLBACK_LINES)
+    }
+
+    pub(crate) fn with_scrollback(cols: usize, rows: usize, scrollback_lines: usize) -> Self {
         let cols = cols.max(1) as u16;
         let rows = rows.max(1) as u16;
+        let theme = Theme::default();
         Self {
-            parser: vt100::Parser::new(rows, cols, 0),
+            parser: vt100::Parser::new(rows, cols, scrollback_lines),
+            scanner: SequenceScanner::new(),
             palette: [None; 256],
-            default_fg: DEFAULT_FG,
-            default_bg: DEFAULT_BG,
-            cursor_color: None,
+            default_fg: theme.foreground,
+            default_bg: theme.background,
+            cursor_color: theme.cursor,
+            theme,
+            responses: Vec::new(),
+            title: String::new(),
+            icon_name: String::new(),
+            title_stack: Vec::new(),
+            keyboard_flag_stacks: [Vec::new(), Vec::new()],
+            modify_other_keys: 0,
+            scrolled_lines: 0,
+            history_len: 0,
+            scrollback_capacity: scrollback_lines,
+            selection: None,
+            word_delimiters: DEFAULT_WORD_DELIMITERS.to_string(),
+            clipboard_policy: ClipboardPolicy::default(),
+            clipboard_events: Vec::new(),
+            hyperlinks: Vec::new(),
+            active_hyperlink: None,
+            link_cells: HashMap::new(),
+            reported_dir: None,
+            commands: ShellCommands::default(),
+            search: None,
             has_changes: false,
         }
     }
 
-    #[allow(dead_code)]
     pub(crate) fn rows(&self) -> usize {
         self.parser.screen().size().0 as usize // size() returns (rows, cols)
     }
@@ -33,10 +137,78 @@ impl TerminalGrid {
         self.parser.screen().size().1 as usize // size() returns (rows, cols)
     }
 
+    /// Window title set by OSC 0/2; empty until the program sets one
+    pub(crate) fn title(&self) -> &str {
+        &self.title
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn icon_name(&self) -> &str {
+        &self.icon_name
+    }
+
     pub(crate) fn default_bg(&self) -> Color32 {
         self.default_bg
     }
 
+    pub(crate) fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /// Switches to `theme`. Colors the program set itself stay as they are.
+    pub(crate) fn set_theme(&mut self, theme: Theme) {
+        if self.default_fg == self.theme.foreground {
+            self.default_fg = theme.foreground;
+        }
+        if self.default_bg == self.theme.background {
+            self.default_bg = theme.background;
+        }
+        if self.cursor_color == self.theme.cursor {
+            self.cursor_color = theme.cursor;
+        }
+        self.theme = theme;
+        self.has_changes = true;
+    }
+
+    /// Cursor, keypad, keyboard protocol and paste modes the key encoder has
+    /// to honor
+    pub(crate) fn keyboard_modes(&self) -> KeyboardModes {
+        let screen = self.parser.screen();
+        KeyboardModes {
+            application_cursor: screen.application_cursor(),
+            application_keypad: screen.application_keypad(),
+            kitty_flags: self.keyboard_flags(),
+            modify_other_keys: self.modify_other_keys,
+            bracketed_paste: screen.bracketed_paste(),
+        }
+    }
+
+    fn keyboard_flags(&self) -> u8 {
+        let stack = &self.keyboard_flag_stacks[self.screen_index()];
+        stack.last().copied().unwrap_or(0)
+    }
+
+    fn keyboard_flag_stack(&mut self) -> &mut Vec<u8> {
+        let index = self.screen_index();
+        &mut self.keyboard_flag_stacks[index]
+    }
+
+    // 0 for the main screen, 1 for the alternate screen
+    fn screen_index(&self) -> usize {
+        usize::from(self.parser.screen().alternate_screen())
+    }
+
+    /// Mouse tracking mode and encoding requested by the running program
+    pub(crate) fn mouse_protocol(
+        &self,
+    ) -> (vt100::MouseProtocolMode, vt100::MouseProtocolEncoding) {
+        let screen = self.parser.screen();
+        (
+            screen.mouse_protocol_mode(),
+            screen.mouse_protocol_encoding(),
+        )
+    }
+
     pub(crate) fn cursor_visible(&self) -> bool {
         !self.parser.screen().hide_cursor()
     }
@@ -59,13 +231,995 @@ impl TerminalGrid {
             return false;
         }
         self.parser.set_size(rows, cols);
+        // vt100 doesn't reflow, so the selected text no longer lines up
+        self.selection = None;
+        if let Some(search) = &mut self.search {
+            search.stale_from = Some(0);
+        }
         self.has_changes = true;
         true
     }
 
+    /// Number of lines the viewport is scrolled back from the live screen
+    pub(crate) fn scroll_offset(&self) -> usize {
+        self.parser.screen().scrollback()
+    }
+
+    /// Number of lines currently held in the scrollback buffer
+    pub(crate) fn scrollback_len(&mut self) -> usize {
+        // vt100 clamps the offset to the buffer length, which is the only way
+        // to read that length back out of it.
+        let offset = self.scroll_offset();
+        self.parser.set_scrollback(usize::MAX);
+        let len = self.scroll_offset();
+        self.parser.set_scrollback(offset);
+        len
+    }
+
+    pub(crate) fn is_scrolled(&self) -> bool {
+        self.scroll_offset() > 0
+    }
+
+    pub(crate) fn set_scroll_offset(&mut self, offset: usize) {
+        if offset != self.scroll_offset() {
+            self.parser.set_scrollback(offset);
+            self.has_changes = true;
+        }
+    }
+
+    pub(crate) fn scroll_up(&mut self, lines: usize) {
+        self.set_scroll_offset(self.scroll_offset().saturating_add(lines));
+    }
+
+    pub(crate) fn scroll_down(&mut self, lines: usize) {
+        self.set_scroll_offset(self.scroll_offset().saturating_sub(lines));
+    }
+
+    pub(crate) fn scroll_page_up(&mut self) {
+        self.scroll_up(self.rows().saturating_sub(1).max(1));
+    }
+
+    pub(crate) fn scroll_page_down(&mut self) {
+        self.scroll_down(self.rows().saturating_sub(1).max(1));
+    }
+
+    pub(crate) fn scroll_to_top(&mut self) {
+        self.set_scroll_offset(usize::MAX);
+    }
+
+    pub(crate) fn scroll_to_bottom(&mut self) {
+        self.set_scroll_offset(0);
+    }
+
     pub(crate) fn process_pty_bytes(&mut self, bytes: &[u8]) {
+        let (screen_top, screen) = (self.scrolled_lines, self.screen_index());
+        // Hand vt100 everything up to the end of each intercepted sequence
+        // before applying it, so it sees the screen state of that moment.
+        let mut start = 0;
+        let mut escape_at = None;
+        for (i, &byte) in bytes.iter().enumerate() {
+            if byte == 0x1b {
+                escape_at = Some(i);
+            }
+            let Some(sequences) = self.scanner.advance(byte) else {
+                continue;
+            };
+            for sequence in sequences {
+                match sequence {
+                    Sequence::Print(c) => {
+                        // Feed linked characters on their own to see where
+                        // they land
+                        let char_start = (i + 1).saturating_sub(c.len_utf8()).max(start);
+                        self.feed(&bytes[start..char_start]);
+                        let before = self.cursor_point();
+                        self.feed(&bytes[char_start..=i]);
+                        self.link_printed_cell(c, before);
+                    }
+                    Sequence::SwitchScreen => {
+                        // Lines that scroll before the switch are counted on
+                        // the screen they scrolled on
+                        if let Some(escape) = escape_at.filter(|&escape| escape >= start) {
+                            self.feed(&bytes[start..escape]);
+                            start = escape;
+                        }
+                        self.feed(&bytes[start..=i]);
+                        self.apply_sequence(Sequence::SwitchScreen);
+                    }
+                    sequence => {
+                        self.feed(&bytes[start..=i]);
+                        self.apply_sequence(sequence);
+                    }
+                }
+                start = i + 1;
+            }
+        }
+        self.feed(&bytes[start..]);
+        self.forget_dropped_links();
+        self.commands.forget_before(self.first_line());
+        // The scrollback searched before a screen switch isn't the one in
+        // the buffer now
+        let changed_from = if self.screen_index() == screen {
+            screen_top
+        } else {
+            0
+        };
+        if let Some(search) = &mut self.search {
+            search.stale_from = Some(
+                search
+                    .stale_from
+                    .map_or(changed_from, |from| from.min(changed_from)),
+            );
+        }
+        self.has_changes = true;
+    }
+
+    // Hands bytes to vt100 while counting the lines that scroll into the
+    // scrollback. vt100 has no such counter, but while the view is scrolled
+    // back it bumps the offset for every line that arrives to keep the view
+    // still, so parking the offset at 1 does the counting.
+    //
+    // This leans on two things vt100 0.15's `Grid::scroll_up` does: it only
+    // bumps an offset that is already above 0, which is why the offset can't
+    // be parked at 0 and why an empty scrollback is counted by its length
+    // instead; and it caps the bumped offset at the scrollback's length.
+    // Once the scrollback is full, a batch that scrolls it over entirely
+    // therefore reads as fewer lines than it was. Everything anchored before
+    // such a batch may then point at the wrong text, so the numbering jumps
+    // past all of it and the selection ends.
+    fn feed(&mut self, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        let screen = self.screen_index();
+        let view_offset = self.scroll_offset();
+        self.parser.set_scrollback(1);
         self.parser.process(bytes);
+        if self.screen_index() != screen {
+            // The screen we switched to is shown from the bottom
+            self.parser.set_scrollback(0);
+            self.history_len = self.scrollback_len();
+            return;
+        }
+        let history_len = self.scrollback_len();
+        let (mut scrolled, counted_all) = if self.history_len == 0 {
+            (history_len, history_len < self.scrollback_capacity)
+        } else {
+            let offset = self.scroll_offset();
+            (offset.saturating_sub(1), offset < self.scrollback_capacity)
+        };
+        // The alternate screen has no scrollback to count with, and nothing
+        // anchored on it outlives a switch anyway
+        if !counted_all && screen == 0 && self.scrollback_capacity > 0 {
+            scrolled += history_len + self.rows();
+            self.selection = None;
+        }
+        self.scrolled_lines += scrolled;
+        self.history_len = history_len;
+        let view_offset = if view_offset > 0 {
+            view_offset + scrolled
+        } else {
+            0
+        };
+        self.parser.set_scrollback(view_offset);
+    }
+
+    /// Buffer point of the terminal cursor
+    pub(crate) fn cursor_point(&self) -> Point {
+        let (row, col) = self.cursor_pos();
+        Point::new(self.scrolled_lines + row, col)
+    }
+
+    // Works out which cell `c` went to from the cursor positions before and
+    // after printing it: it lands under the cursor unless a pending wrap
+    // moved it to the start of the next line first.
+    fn link_printed_cell(&mut self, c: char, before: Point) {
+        let Some(link) = self.active_hyperlink else {
+            return;
+        };
+        let width = c.width().unwrap_or(0);
+        if width == 0 {
+            return;
+        }
+        let after = self.cursor_point();
+        let wrapped = after.col >= width && (after.line != before.line || after.col < before.col);
+        let cell = if wrapped {
+            Point::new(after.line, after.col - width)
+        } else {
+            before
+        };
+        self.link_cells
+            .insert((self.screen_index(), cell.line, cell.col), (link, c));
+    }
+
+    fn forget_dropped_links(&mut self) {
+        let first_line = self.first_line();
+        self.link_cells
+            .retain(|&(screen, line, _), _| screen != 0 || line >= first_line);
+        if self.link_cells.is_empty() && self.active_hyperlink.is_none() {
+            self.hyperlinks.clear();
+        }
+    }
+
+    /// The OSC 8 link a cell belongs to, see `CellInfo::link`
+    pub(crate) fn hyperlink(&self, link: u32) -> Option<&Hyperlink> {
+        self.hyperlinks.get(link as usize)
+    }
+
+    /// The working directory the shell last reported, see `cwd`
+    pub(crate) fn reported_dir(&self) -> Option<&ReportedDir> {
+        self.reported_dir.as_ref()
+    }
+
+    /// Scrolls the closest prompt above the top of the view to the top.
+    /// Returns false when there is no such prompt.
+    pub(crate) fn scroll_to_previous_prompt(&mut self) -> bool {
+        let top = self.viewport_point(0, 0).line;
+        match self.commands.prompt_before(top) {
+            Some(line) if self.screen_index() == 0 => {
+                self.scroll_line_to_top(line);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Scrolls the closest prompt below the top of the view to the top, or
+    /// as far as the live screen allows. Returns false when the view is
+    /// already live or there is no such prompt.
+    pub(crate) fn scroll_to_next_prompt(&mut self) -> bool {
+        let top = self.viewport_point(0, 0).line;
+        match self.commands.prompt_after(top) {
+            Some(line) if self.screen_index() == 0 && self.is_scrolled() => {
+                self.scroll_line_to_top(line);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn scroll_line_to_top(&mut self, line: usize) {
+        let offset = self.scrolled_lines.saturating_sub(line);
+        self.set_scroll_offset(offset.min(self.history_len));
+    }
+
+    /// Selects what the latest finished command printed and scrolls to it.
+    /// Returns false when no command printed anything.
+    pub(crate) fn select_last_output(&mut self) -> bool {
+        let Some((start, end)) = self.commands.last_output() else {
+            return false;
+        };
+        if self.screen_index() != 0 || end <= start {
+            return false;
+        }
+        let last = if end.col > 0 {
+            Point::new(end.line, end.col - 1)
+        } else {
+            Point::new(end.line - 1, self.cols() - 1)
+        };
+        self.select(SelectionMode::Linear, start, last);
+        self.scroll_to_line(start.line);
+        true
+    }
+
+    /// Whether the viewport `row` holds the prompt of a command that exited
+    /// with a non-zero status
+    pub(crate) fn failed_prompt_at(&self, row: usize) -> bool {
+        self.screen_index() == 0
+            && self
+                .commands
+                .at_line(self.viewport_point(row, 0).line)
+                .is_some_and(ShellCommand::failed)
+    }
+
+    /// Starts a selection at the viewport cell `row`/`col`, replacing any
+    /// previous one.
+    pub(crate) fn start_selection(&mut self, mode: SelectionMode, row: usize, col: usize) {
+        let point = self.viewport_point(row, col);
+        self.selection = Some(Selection::new(mode, point));
+        self.update_selection_bounds();
+    }
+
+    /// Moves the free end of the selection to the viewport cell `row`/`col`
+    pub(crate) fn extend_selection(&mut self, row: usize, col: usize) {
+        let point = self.viewport_point(row, col);
+        let Some(selection) = self.selection.as_mut() else {
+            return;
+        };
+        if selection.cursor != point {
+            selection.cursor = point;
+            self.update_selection_bounds();
+        }
+    }
+
+    /// Selects from `anchor` to `cursor` in buffer points, both included,
+    /// as keyboard selection does
+    pub(crate) fn select(&mut self, mode: SelectionMode, anchor: Point, cursor: Point) {
+        self.selection = Some(Selection {
+            cursor,
+            from_click: false,
+            ..Selection::new(mode, anchor)
+        });
+        self.update_selection_bounds();
+    }
+
+    pub(crate) fn clear_selection(&mut self) {
+        if self.selection.take().is_some() {
+            self.has_changes = true;
+        }
+    }
+
+    /// Whether the viewport cell `row`/`col` is highlighted as selected
+    pub(crate) fn is_selected(&self, row: usize, col: usize) -> bool {
+        self.selection
+            .is_some_and(|selection| selection.contains(self.viewport_point(row, col)))
+    }
+
+    /// Characters that end a word for double-click selection
+    pub(crate) fn set_word_delimiters(&mut self, delimiters: &str) {
+        self.word_delimiters = delimiters.to_string();
+    }
+
+    /// The selected text. Soft-wrapped rows are joined, other rows end in a
+    /// newline, and trailing blanks are dropped from each line.
+    pub(crate) fn selected_text(&mut self) -> Option<String> {
+        let selection = self.selection.filter(|selection| !selection.is_empty())?;
+        let last_col = self.cols() - 1;
+        let block = selection.mode == SelectionMode::Block;
+        let mut text = String::new();
+        for line in selection.start.line.max(self.first_line())..=selection.end.line {
+            let (first, last) = if block {
+                selection.block_cols()
+            } else {
+                let first = if line == selection.start.line {
+                    selection.start.col
+                } else {
+                    0
+                };
+                let last = if line == selection.end.line {
+                    selection.end.col
+                } else {
+                    last_col
+                };
+                (first, last)
+            };
+            let mut segment = String::new();
+            for col in first..=last.min(last_col) {
+                let (cell, wide_continuation) = self.buffer_cell(Point::new(line, col));
+                if wide_continuation {
+                    continue;
+                }
+                segment.push_str(if cell.is_empty() { " " } else { &cell });
+            }
+            let joins_next = !block && last >= last_col && self.buffer_row_wrapped(line);
+            if joins_next {
+                text.push_str(&segment);
+            } else {
+                text.push_str(segment.trim_end());
+                if line != selection.end.line {
+                    text.push('\n');
+                }
+            }
+        }
+        Some(text)
+    }
+
+    /// Buffer point of a viewport cell. Buffer lines are numbered from the
+    /// first line the terminal showed.
+    pub(crate) fn viewport_point(&self, row: usize, col: usize) -> Point {
+        let line = (self.scrolled_lines + row).saturating_sub(self.scroll_offset());
+        Point::new(line, col)
+    }
+
+    /// Oldest buffer line still in the scrollback
+    pub(crate) fn first_line(&self) -> usize {
+        self.scrolled_lines.saturating_sub(self.history_len)
+    }
+
+    /// Bottom line of the live screen
+    pub(crate) fn last_line(&self) -> usize {
+        self.scrolled_lines + self.rows() - 1
+    }
+
+    // Runs `f` with the screen scrolled so buffer `line` is in view, passing
+    // the viewport row it ends up on. Returns `None` for lines that have
+    // dropped off the scrollback or don't exist yet.
+    fn with_buffer_line<R>(
+        &mut self,
+        line: usize,
+        f: impl FnOnce(&vt100::Screen, u16) -> R,
+    ) -> Option<R> {
+        if line < self.first_line() || line > self.last_line() {
+            return None;
+        }
+        let offset = self.scroll_offset();
+        let (scroll, row) = match self.scrolled_lines.checked_sub(line) {
+            Some(scroll) if scroll > 0 => (scroll, 0),
+            _ => (0, line - self.scrolled_lines),
+        };
+        self.parser.set_scrollback(scroll);
+        let result = f(self.parser.screen(), row as u16);
+        self.parser.set_scrollback(offset);
+        Some(result)
+    }
+
+    /// Contents of a buffer cell, and whether it's the right half of a wide
+    /// character
+    pub(crate) fn buffer_cell(&mut self, point: Point) -> (String, bool) {
+        self.with_buffer_line(point.line, |screen, row| {
+            screen
+                .cell(row, point.col as u16)
+                .map(|cell| (cell.contents(), cell.is_wide_continuation()))
+        })
+        .flatten()
+        .unwrap_or_default()
+    }
+
+    // Every cell of a buffer line, and whether it wraps into the next one
+    fn buffer_row(&mut self, line: usize) -> Option<(Vec<(String, bool)>, bool)> {
+        let cols = self.cols() as u16;
+        self.with_buffer_line(line, |screen, row| {
+            let cells = (0..cols)
+                .map(|col| {
+                    screen
+                        .cell(row, col)
+                        .map(|cell| (cell.contents(), cell.is_wide_continuation()))
+                        .unwrap_or_default()
+                })
+                .collect();
+            (cells, screen.row_wrapped(row))
+        })
+    }
+
+    /// Whether a buffer line continues on the next one
+    pub(crate) fn buffer_row_wrapped(&mut self, line: usize) -> bool {
+        self.with_buffer_line(line, |screen, row| screen.row_wrapped(row))
+            .unwrap_or(false)
+    }
+
+    fn is_word_cell(&mut self, point: Point) -> bool {
+        let (text, wide_continuation) = self.buffer_cell(point);
+        wide_continuation
+            || text
+                .chars()
+                .next()
+                .is_some_and(|c| !c.is_whitespace() && !self.word_delimiters.contains(c))
+    }
+
+    // The cell before `point` in reading order, when it's on the same
+    // logical line
+    fn previous_in_line(&mut self, point: Point) -> Option<Point> {
+        if point.col > 0 {
+            Some(Point::new(point.line, point.col - 1))
+        } else if point.line > self.first_line() && self.buffer_row_wrapped(point.line - 1) {
+            Some(Point::new(point.line - 1, self.cols() - 1))
+        } else {
+            None
+        }
+    }
+
+    fn next_in_line(&mut self, point: Point) -> Option<Point> {
+        if point.col + 1 < self.cols() {
+            Some(Point::new(point.line, point.col + 1))
+        } else if point.line < self.last_line() && self.buffer_row_wrapped(point.line) {
+            Some(Point::new(point.line + 1, 0))
+        } else {
+            None
+        }
+    }
+
+    fn word_start(&mut self, mut point: Point) -> Point {
+        if !self.is_word_cell(point) {
+            return self.wide_char_start(point);
+        }
+        while let Some(previous) = self.previous_in_line(point)
+            && self.is_word_cell(previous)
+        {
+            point = previous;
+        }
+        point
+    }
+
+    fn word_end(&mut self, mut point: Point) -> Point {
+        if !self.is_word_cell(point) {
+            return point;
+        }
+        while let Some(next) = self.next_in_line(point)
+            && self.is_word_cell(next)
+        {
+            point = next;
+        }
+        point
+    }
+
+    /// The URL, file reference, address or git hash covering a viewport
+    /// cell. The whole line is searched, following soft wraps in both
+    /// directions.
+    pub(crate) fn detect_at(&mut self, row: usize, col: usize) -> Option<DetectedMatch> {
+        let point = self.wide_char_start(self.viewport_point(row, col));
+        let (start, end) = (self.line_start(point), self.line_end(point));
+        self.detect_in_lines(start.line, end.line)
+            .into_iter()
+            .find(|found| (found.start..=found.end).contains(&point))
+    }
+
+    /// Every match with at least one cell in the viewport, top to bottom
+    pub(crate) fn visible_matches(&mut self) -> Vec<DetectedMatch> {
+        let top = self.viewport_point(0, 0);
+        let bottom = self.viewport_point(self.rows() - 1, 0);
+        let mut matches = Vec::new();
+        let mut line = self.line_start(top).line;
+        while line <= bottom.line {
+            let end = self.line_end(Point::new(line, 0)).line;
+            matches.extend(
+                self.detect_in_lines(line, end)
+                    .into_iter()
+                    .filter(|found| found.end >= top && found.start.line <= bottom.line),
+            );
+            line = end + 1;
+        }
+        matches
+    }
+
+    /// Viewport row and column of a buffer point, if it's on screen
+    pub(crate) fn viewport_cell(&self, point: Point) -> Option<(usize, usize)> {
+        let top = self.viewport_point(0, 0).line;
+        let row = point.line.checked_sub(top)?;
+        (row < self.rows()).then_some((row, point.col))
+    }
+
+    fn detect_in_lines(&mut self, first: usize, last: usize) -> Vec<DetectedMatch> {
+        let mut text = LineText::default();
+        for line in first..=last {
+            if let Some((row, _)) = self.buffer_row(line) {
+                text.push_row(line, &row);
+            }
+        }
+        let cells = text.cells;
+        find_matches(&text.chars.into_iter().collect::<String>())
+            .into_iter()
+            .map(|found| DetectedMatch {
+                kind: found.kind,
+                text: found.text,
+                start: cells[found.start],
+                end: cells[found.end - 1],
+            })
+            .collect()
+    }
+
+    pub(crate) fn match_contains(&self, found: &DetectedMatch, row: usize, col: usize) -> bool {
+        (found.start..=found.end).contains(&self.viewport_point(row, col))
+    }
+
+    /// Every match in the scrollback and on the screen, top to bottom.
+    /// Soft-wrapped rows are searched as one line.
+    pub(crate) fn search(&mut self, matcher: &Matcher) -> Vec<SearchMatch> {
+        self.search_from(matcher, self.first_line())
+    }
+
+    // Matches from the logical line starting at buffer line `from` down
+    fn search_from(&mut self, matcher: &Matcher, from: usize) -> Vec<SearchMatch> {
+        let mut matches = Vec::new();
+        let mut text = LineText::default();
+        let last_line = self.last_line();
+        for line in from..=last_line {
+            let Some((row, wrapped)) = self.buffer_row(line) else {
+                continue;
+            };
+            text.push_row(line, &row);
+            if wrapped && line < last_line {
+                continue;
+            }
+            text.trim_end();
+            matches.extend(
+                matcher
+                    .find_all(&text.chars)
+                    .into_iter()
+                    .map(|(start, end)| SearchMatch {
+                        start: text.cells[start],
+                        end: text.cells[end - 1],
+                    }),
+            );
+            text = LineText::default();
+        }
+        matches
+    }
+
+    /// Starts or replaces the search that navigation and highlights work
+    /// on. The current match is the last one starting above the bottom of
+    /// the viewport. Returns the number of matches.
+    pub(crate) fn start_search(&mut self, query: &SearchQuery) -> Result<usize, PatternError> {
+        let matcher = Matcher::new(query)?;
+        let matches = self.search(&matcher);
+        let bottom = self.viewport_point(self.rows() - 1, self.cols() - 1);
+        let current = matches
+            .iter()
+            .rposition(|found| found.start <= bottom)
+            .or((!matches.is_empty()).then_some(0));
+        let count = matches.len();
+        if let Some(current) = current {
+            self.reveal_line(matches[current].start.line);
+        }
+        self.search = Some(SearchState {
+            matcher,
+            matches,
+            current,
+            stale_from: None,
+        });
         self.has_changes = true;
+        Ok(count)
+    }
+
+    pub(crate) fn end_search(&mut self) {
+        if self.search.take().is_some() {
+            self.has_changes = true;
+        }
+    }
+
+    /// Collects the matches again on the lines output changed since the
+    /// last time, staying on the current match when it's still there
+    pub(crate) fn refresh_search(&mut self) {
+        let Some(search) = self.search.take_if(|search| search.stale_from.is_some()) else {
+            return;
+        };
+        // Start over from the beginning of the logical line the changes
+        // begin on, keeping the matches above it that are still in the
+        // scrollback
+        let first_line = self.first_line();
+        let mut from = search.stale_from.unwrap_or(0).max(first_line);
+        while from > first_line && self.buffer_row_wrapped(from - 1) {
+            from -= 1;
+        }
+        let mut matches: Vec<SearchMatch> = search
+            .matches
+            .iter()
+            .filter(|found| (first_line..from).contains(&found.start.line))
+            .copied()
+            .collect();
+        matches.extend(self.search_from(&search.matcher, from));
+        let current = search.current.map(|current| search.matches[current].start);
+        let current = current
+            .and_then(|start| matches.iter().rposition(|found| found.start <= start))
+            .or((!matches.is_empty()).then_some(0));
+        self.search = Some(SearchState {
+            matches,
+            current,
+            stale_from: None,
+            ..search
+        });
+        self.has_changes = true;
+    }
+
+    /// Moves to the next match towards newer output, wrapping around, and
+    /// scrolls it into view
+    pub(crate) fn search_next(&mut self) -> Option<SearchMatch> {
+        self.step_search(true)
+    }
+
+    /// Moves to the previous match towards older output
+    pub(crate) fn search_previous(&mut self) -> Option<SearchMatch> {
+        self.step_search(false)
+    }
+
+    fn step_search(&mut self, forward: bool) -> Option<SearchMatch> {
+        self.refresh_search();
+        let search = self.search.as_mut()?;
+        let count = search.matches.len();
+        let current = match search.current {
+            _ if count == 0 => return None,
+            Some(current) if forward => (current + 1) % count,
+            Some(current) => (current + count - 1) % count,
+            None => 0,
+        };
+        search.current = Some(current);
+        let found = search.matches[current];
+        self.reveal_line(found.start.line);
+        self.has_changes = true;
+        Some(found)
+    }
+
+    /// The current match's number, counting from 1, and the number of
+    /// matches
+    pub(crate) fn search_status(&self) -> Option<(Option<usize>, usize)> {
+        let search = self.search.as_ref()?;
+        Some((
+            search.current.map(|current| current + 1),
+            search.matches.len(),
+        ))
+    }
+
+    pub(crate) fn search_highlight(&self, row: usize, col: usize) -> Option<SearchHighlight> {
+        let search = self.search.as_ref()?;
+        let point = self.viewport_point(row, col);
+        let index = search
+            .matches
+            .partition_point(|found| found.start <= point)
+            .checked_sub(1)?;
+        if point > search.matches[index].end {
+            return None;
+        }
+        Some(if search.current == Some(index) {
+            SearchHighlight::Current
+        } else {
+            SearchHighlight::Match
+        })
+    }
+
+    /// Scrolls as little as possible to bring buffer `line` into view
+    pub(crate) fn scroll_to_line(&mut self, line: usize) {
+        let top = self.viewport_point(0, 0).line;
+        let offset = if line < top {
+            self.scrolled_lines - line
+        } else if line >= top + self.rows() {
+            (self.scrolled_lines + self.rows() - 1).saturating_sub(line)
+        } else {
+            return;
+        };
+        self.set_scroll_offset(offset.min(self.history_len));
+    }
+
+    // Scrolls so buffer `line` is in view, centered if it wasn't already
+    fn reveal_line(&mut self, line: usize) {
+        let top = self.viewport_point(0, 0).line;
+        if (top..top + self.rows()).contains(&line) {
+            return;
+        }
+        let offset = (self.scrolled_lines + self.rows() / 2).saturating_sub(line);
+        self.set_scroll_offset(offset.min(self.history_len));
+    }
+
+    fn line_start(&mut self, point: Point) -> Point {
+        let mut line = point.line;
+        while line > self.first_line() && self.buffer_row_wrapped(line - 1) {
+            line -= 1;
+        }
+        Point::new(line, 0)
+    }
+
+    fn line_end(&mut self, point: Point) -> Point {
+        let mut line = point.line;
+        while line < self.last_line() && self.buffer_row_wrapped(line) {
+            line += 1;
+        }
+        Point::new(line, self.cols() - 1)
+    }
+
+    // Selecting the right half of a wide character selects all of it
+    fn wide_char_start(&mut self, point: Point) -> Point {
+        if point.col > 0 && self.buffer_cell(point).1 {
+            Point::new(point.line, point.col - 1)
+        } else {
+            point
+        }
+    }
+
+    fn update_selection_bounds(&mut self) {
+        let Some(selection) = self.selection else {
+            return;
+        };
+        let (first, last) = selection.ordered();
+        let (start, end) = match selection.mode {
+            SelectionMode::Linear | SelectionMode::Block => (self.wide_char_start(first), last),
+            SelectionMode::Word => (self.word_start(first), self.word_end(last)),
+            SelectionMode::Line => (self.line_start(first), self.line_end(last)),
+        };
+        self.selection = Some(Selection {
+            start,
+            end,
+            ..selection
+        });
+        self.has_changes = true;
+    }
+
+    /// Which OSC 52 requests reach the front end
+    pub(crate) fn set_clipboard_policy(&mut self, policy: ClipboardPolicy) {
+        self.clipboard_policy = policy;
+    }
+
+    /// Drains the OSC 52 requests the front end has to carry out.
+    pub(crate) fn take_clipboard_events(&mut self) -> Vec<ClipboardEvent> {
+        std::mem::take(&mut self.clipboard_events)
+    }
+
+    fn request_clipboard(&mut self, request: ClipboardRequest, access: ClipboardAccess) {
+        let needs_confirmation = match access {
+            ClipboardAccess::Allow => false,
+            ClipboardAccess::Ask => true,
+            ClipboardAccess::Deny => return,
+        };
+        self.clipboard_events.push(ClipboardEvent {
+            request,
+            needs_confirmation,
+        });
+    }
+
+    /// Drains the replies produced by the bytes processed so far. Front ends
+    /// write these to the PTY master just like keyboard input.
+    pub(crate) fn take_responses(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.responses)
+    }
+
+    fn apply_sequence(&mut self, sequence: Sequence) {
+        match sequence {
+            Sequence::SetPaletteColor { index, color } => {
+                self.palette[index as usize] = Some(color);
+            }
+            Sequence::ResetPaletteColors(indices) => {
+                if indices.is_empty() {
+                    self.palette = [None; 256];
+                }
+                for index in indices {
+                    self.palette[index as usize] = None;
+                }
+            }
+            Sequence::SetDynamicColor { target, color } => match target {
+                DynamicColor::Foreground => self.default_fg = color,
+                DynamicColor::Background => self.default_bg = color,
+                DynamicColor::Cursor => self.cursor_color = Some(color),
+            },
+            Sequence::ResetDynamicColor(target) => match target {
+                DynamicColor::Foreground => self.default_fg = self.theme.foreground,
+                DynamicColor::Background => self.default_bg = self.theme.background,
+                DynamicColor::Cursor => self.cursor_color = self.theme.cursor,
+            },
+            Sequence::QueryPaletteColor {
+                index,
+                bell_terminated,
+            } => {
+                let color =
+                    self.palette[index as usize].unwrap_or(self.theme.palette[index as usize]);
+                self.respond_osc(
+                    &format!("4;{index};{}", format_color_spec(color)),
+                    bell_terminated,
+                );
+            }
+            Sequence::QueryDynamicColor {
+                target,
+                bell_terminated,
+            } => {
+                let color = match target {
+                    DynamicColor::Foreground => self.default_fg,
+                    DynamicColor::Background => self.default_bg,
+                    DynamicColor::Cursor => self.cursor_color.unwrap_or(self.default_fg),
+                };
+                self.respond_osc(
+                    &format!("{};{}", target.osc_code(), format_color_spec(color)),
+                    bell_terminated,
+                );
+            }
+            Sequence::PrimaryDeviceAttributes => {
+                self.responses.extend_from_slice(PRIMARY_DEVICE_ATTRIBUTES);
+            }
+            Sequence::SecondaryDeviceAttributes => {
+                // Terminal type 0, then the version packed as xterm does (0.1.0 -> 100)
+                let reply = format!("\x1b[>0;{};0c", packed_version());
+                self.responses.extend_from_slice(reply.as_bytes());
+            }
+            Sequence::StatusReport => self.responses.extend_from_slice(b"\x1b[0n"),
+            Sequence::CursorPositionReport { private } => {
+                let (row, col) = self.cursor_pos();
+                let marker = if private { "?" } else { "" };
+                let reply = format!("\x1b[{marker}{};{}R", row + 1, col + 1);
+                self.responses.extend_from_slice(reply.as_bytes());
+            }
+            Sequence::TerminalVersion => {
+                let reply = format!("\x1bP>|shitty {}\x1b\\", env!("CARGO_PKG_VERSION"));
+                self.responses.extend_from_slice(reply.as_bytes());
+            }
+            Sequence::SetTitle { title, icon_name } => {
+                if let Some(title) = title {
+                    self.title = title;
+                }
+                if let Some(icon_name) = icon_name {
+                    self.icon_name = icon_name;
+                }
+            }
+            Sequence::PushTitle(parts) => {
+                if self.title_stack.len() == TITLE_STACK_LIMIT {
+                    self.title_stack.remove(0);
+                }
+                let title = parts.title.then(|| self.title.clone());
+                let icon_name = parts.icon_name.then(|| self.icon_name.clone());
+                self.title_stack.push((title, icon_name));
+            }
+            Sequence::PopTitle(parts) => self.pop_title(parts),
+            Sequence::PushKeyboardFlags(flags) => {
+                let stack = self.keyboard_flag_stack();
+                if stack.len() == KEYBOARD_FLAG_STACK_LIMIT {
+                    stack.remove(0);
+                }
+                stack.push(flags);
+            }
+            Sequence::PopKeyboardFlags(count) => {
+                let stack = self.keyboard_flag_stack();
+                stack.truncate(stack.len().saturating_sub(count));
+            }
+            Sequence::SetKeyboardFlags { flags, update } => {
+                let current = self.keyboard_flags();
+                let flags = match update {
+                    FlagsUpdate::Replace => flags,
+                    FlagsUpdate::Add => current | flags,
+                    FlagsUpdate::Remove => current & !flags,
+                };
+                let stack = self.keyboard_flag_stack();
+                match stack.last_mut() {
+                    Some(top) => *top = flags,
+                    None => stack.push(flags),
+                }
+            }
+            Sequence::QueryKeyboardFlags => {
+                let reply = format!("\x1b[?{}u", self.keyboard_flags());
+                self.responses.extend_from_slice(reply.as_bytes());
+            }
+            Sequence::SetClipboard { targets: _, text } => {
+                let access = self.clipboard_policy.write;
+                self.request_clipboard(ClipboardRequest::Store(text), access);
+            }
+            Sequence::QueryClipboard {
+                targets,
+                bell_terminated,
+            } => {
+                let request = ClipboardRequest::Load {
+                    targets,
+                    bell_terminated,
+                };
+                self.request_clipboard(request, self.clipboard_policy.read);
+            }
+            Sequence::OpenHyperlink { id, uri } => {
+                let link = Hyperlink { id, uri };
+                let index = match self.hyperlinks.iter().position(|known| *known == link) {
+                    Some(index) => index,
+                    None => {
+                        self.hyperlinks.push(link);
+                        self.hyperlinks.len() - 1
+                    }
+                };
+                self.active_hyperlink = Some(index as u32);
+            }
+            Sequence::CloseHyperlink => self.active_hyperlink = None,
+            Sequence::SetWorkingDirectory(dir) => self.reported_dir = Some(dir),
+            Sequence::PromptMark(mark) => {
+                // Full-screen programs don't run commands of their own
+                if self.screen_index() == 0 {
+                    self.commands.mark(mark, self.cursor_point());
+                }
+            }
+            // Linked characters are placed while feeding vt100
+            Sequence::Print(_) => {}
+            Sequence::SwitchScreen => {
+                // Whatever was on the alternate screen is gone or about to be
+                self.link_cells.retain(|&(screen, _, _), _| screen == 0);
+                self.selection = None;
+            }
+            Sequence::SetModifyOtherKeys(level) => self.modify_other_keys = level,
+            Sequence::QueryModifyOtherKeys => {
+                let reply = format!("\x1b[>4;{}m", self.modify_other_keys);
+                self.responses.extend_from_slice(reply.as_bytes());
+            }
+        }
+    }
+
+    // Like xterm, restores the parts both the pop asks for and the push
+    // saved
+    fn pop_title(&mut self, parts: TitleParts) {
+        let Some((title, icon_name)) = self.title_stack.pop() else {
+            return;
+        };
+        if let Some(title) = title.filter(|_| parts.title) {
+            self.title = title;
+        }
+        if let Some(icon_name) = icon_name.filter(|_| parts.icon_name) {
+            self.icon_name = icon_name;
+        }
+    }
+
+    fn respond_osc(&mut self, body: &str, bell_terminated: bool) {
+        self.responses.extend_from_slice(b"\x1b]");
+        self.responses.extend_from_slice(body.as_bytes());
+        if bell_terminated {
+            self.responses.push(0x07);
+        } else {
+            self.responses.extend_from_slice(b"\x1b\\");
+        }
     }
 
     /// Check if there are any changes since the last render
@@ -78,6 +1232,8 @@ impl TerminalGrid {
         self.has_changes = false;
     }
 
+    /// Returns the cell at `row`/`col` of the viewport, which includes
+    /// scrollback lines when the view is scrolled up.
     pub(crate) fn get_cell(&self, row: usize, col: usize) -> Option<CellInfo> {
         let screen = self.parser.screen();
         let size = screen.size(); // (rows, cols)
@@ -86,8 +1242,15 @@ impl TerminalGrid {
         }
 
         let cell = screen.cell(row as u16, col as u16)?;
+        let text = cell.contents();
+        let point = self.viewport_point(row, col);
+        let link = self
+            .link_cells
+            .get(&(self.screen_index(), point.line, col))
+            .filter(|(_, c)| text.starts_with(*c))
+            .map(|&(link, _)| link);
         Some(CellInfo {
-            text: cell.contents(),
+            text,
             fg: self.resolve_color(cell.fgcolor(), true),
             bg: self.resolve_color(cell.bgcolor(), false),
             bold: cell.bold(),
@@ -96,6 +1259,7 @@ impl TerminalGrid {
             inverse: cell.inverse(),
             wide: cell.is_wide(),
             wide_continuation: cell.is_wide_continuation(),
+            link,
         })
     }
 
@@ -118,17 +1282,49 @@ impl TerminalGrid {
                 }
             }
             vt100::Color::Idx(idx) => {
-                if let Some(color) = self.palette[idx as usize] {
-                    color
-                } else {
-                    xterm_256_color(idx)
-                }
+                self.palette[idx as usize].unwrap_or(self.theme.palette[idx as usize])
             }
             vt100::Color::Rgb(r, g, b) => Color32::from_rgb(r, g, b),
         }
     }
 }
 
+fn packed_version() -> u32 {
+    let part = |s: &str| s.parse::<u32>().unwrap_or(0);
+    part(env!("CARGO_PKG_VERSION_MAJOR")) * 10_000
+        + part(env!("CARGO_PKG_VERSION_MINOR")) * 100
+        + part(env!("CARGO_PKG_VERSION_PATCH"))
+}
+
+/// The text of a logical line, with the buffer cell of every char
+#[derive(Default)]
+struct LineText {
+    chars: Vec<char>,
+    cells: Vec<Point>,
+}
+
+impl LineText {
+    fn push_row(&mut self, line: usize, row: &[(String, bool)]) {
+        for (col, (cell, wide_continuation)) in row.iter().enumerate() {
+            if *wide_continuation {
+                continue;
+            }
+            for c in if cell.is_empty() { " " } else { cell }.chars() {
+                self.chars.push(c);
+                self.cells.push(Point::new(line, col));
+            }
+        }
+    }
+
+    // Drops the blank cells that pad a line out to the screen width
+    fn trim_end(&mut self) {
+        while self.chars.last() == Some(&' ') {
+            self.chars.pop();
+            self.cells.pop();
+        }
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct CellInfo {
     pub text: String,
@@ -142,4 +1338,695 @@ pub(crate) struct CellInfo {
     pub inverse: bool,
     pub wide: bool,
     pub wide_continuation: bool,
+    /// OSC 8 link index, resolved with `TerminalGrid::hyperlink`
+    pub link: Option<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::color::{DEFAULT_BG, xterm_256_color};
+    use crate::terminal::detect::MatchKind;
+    use crate::terminal::search::Matcher;
+
+    fn grid_with_lines(cols: usize, rows: usize, lines: usize) -> TerminalGrid {
+        let mut grid = TerminalGrid::with_scrollback(cols, rows, 100);
+        for i in 0..lines {
+            grid.process_pty_bytes(format!("line{i}\r\n").as_bytes());
+        }
+        grid
+    }
+
+    fn row_text(grid: &TerminalGrid, row: usize) -> String {
+        (0..grid.cols())
+            .filter_map(|col| grid.get_cell(row, col))
+            .map(|cell| cell.text)
+            .collect::<String>()
+            .trim_end()
+            .to_string()
+    }
+
+    #[test]
+    fn lines_scrolled_off_the_top_are_kept() {
+        let mut grid = grid_with_lines(10, 3, 5);
+        assert_eq!(grid.scrollback_len(), 3);
+        assert_eq!(row_text(&grid, 0), "line3");
+
+        grid.scroll_up(1);
+        assert_eq!(grid.scroll_offset(), 1);
+        assert_eq!(row_text(&grid, 0), "line2");
+
+        grid.scroll_to_top();
+        assert_eq!(grid.scroll_offset(), 3);
+        assert_eq!(row_text(&grid, 0), "line0");
+
+        grid.scroll_to_bottom();
+        assert!(!grid.is_scrolled());
+        assert_eq!(row_text(&grid, 0), "line3");
+    }
+
+    #[test]
+    fn scrolling_is_clamped_to_the_buffer() {
+        let mut grid = grid_with_lines(10, 3, 5);
+        grid.scroll_up(1000);
+        assert_eq!(grid.scroll_offset(), 3);
+        grid.scroll_down(1000);
+        assert_eq!(grid.scroll_offset(), 0);
+    }
+
+    #[test]
+    fn view_scrolled_back_past_a_screen_is_readable() {
+        let mut grid = grid_with_lines(10, 3, 20);
+        grid.scroll_up(10);
+        assert_eq!(row_text(&grid, 0), "line8");
+        assert_eq!(row_text(&grid, 2), "line10");
+        grid.scroll_to_top();
+        assert_eq!(row_text(&grid, 0), "line0");
+    }
+
+    #[test]
+    fn scrollback_len_does_not_move_the_viewport() {
+        let mut grid = grid_with_lines(10, 3, 8);
+        grid.scroll_up(2);
+        assert_eq!(grid.scrollback_len(), 6);
+        assert_eq!(grid.scroll_offset(), 2);
+    }
+
+    fn select(
+        grid: &mut TerminalGrid,
+        mode: SelectionMode,
+        from: (usize, usize),
+        to: (usize, usize),
+    ) {
+        grid.start_selection(mode, from.0, from.1);
+        grid.extend_selection(to.0, to.1);
+    }
+
+    #[test]
+    fn linear_selection_copies_across_rows() {
+        let mut grid = TerminalGrid::new(10, 3);
+        grid.process_pty_bytes(b"hello   \r\nworld");
+        select(&mut grid, SelectionMode::Linear, (0, 2), (1, 2));
+        assert_eq!(grid.selected_text().as_deref(), Some("llo\nwor"));
+        assert!(grid.is_selected(0, 9));
+        assert!(!grid.is_selected(1, 3));
+    }
+
+    #[test]
+    fn a_plain_click_selects_nothing() {
+        let mut grid = TerminalGrid::new(10, 3);
+        grid.process_pty_bytes(b"hello");
+        grid.start_selection(SelectionMode::Linear, 0, 1);
+        assert!(!grid.is_selected(0, 1));
+        assert_eq!(grid.selected_text(), None);
+    }
+
+    #[test]
+    fn soft_wrapped_rows_are_joined() {
+        let mut grid = TerminalGrid::new(5, 3);
+        grid.process_pty_bytes(b"abcdefgh");
+        select(&mut grid, SelectionMode::Linear, (0, 0), (1, 4));
+        assert_eq!(grid.selected_text().as_deref(), Some("abcdefgh"));
+    }
+
+    #[test]
+    fn double_click_selects_a_word_across_a_wrap() {
+        let mut grid = TerminalGrid::new(8, 3);
+        grid.process_pty_bytes(b"ls /usr/local/bin");
+        grid.start_selection(SelectionMode::Word, 1, 0);
+        assert_eq!(grid.selected_text().as_deref(), Some("/usr/local/bin"));
+
+        grid.set_word_delimiters(" /");
+        grid.start_selection(SelectionMode::Word, 1, 0);
+        assert_eq!(grid.selected_text().as_deref(), Some("local"));
+    }
+
+    #[test]
+    fn triple_click_selects_the_logical_line() {
+        let mut grid = TerminalGrid::new(5, 4);
+        grid.process_pty_bytes(b"one\r\ntwo three\r\nfour");
+        grid.start_selection(SelectionMode::Line, 2, 1);
+        assert_eq!(grid.selected_text().as_deref(), Some("two three"));
+        assert!(grid.is_selected(1, 0));
+        assert!(!grid.is_selected(0, 0));
+        assert!(!grid.is_selected(3, 0));
+    }
+
+    #[test]
+    fn block_selection_takes_the_same_columns_from_each_row() {
+        let mut grid = TerminalGrid::new(10, 3);
+        grid.process_pty_bytes(b"abcdef\r\nghijkl\r\nmnopqr");
+        select(&mut grid, SelectionMode::Block, (2, 3), (0, 1));
+        assert_eq!(grid.selected_text().as_deref(), Some("bcd\nhij\nnop"));
+        assert!(!grid.is_selected(1, 0));
+    }
+
+    #[test]
+    fn wide_characters_are_selected_whole() {
+        let mut grid = TerminalGrid::new(10, 2);
+        grid.process_pty_bytes("a中文b".as_bytes());
+        // Starting on the right half of 中 still includes it
+        select(&mut grid, SelectionMode::Linear, (0, 2), (0, 4));
+        assert_eq!(grid.selected_text().as_deref(), Some("中文"));
+    }
+
+    #[test]
+    fn selection_stays_on_its_text_when_scrolling() {
+        let mut grid = grid_with_lines(10, 3, 5);
+        select(&mut grid, SelectionMode::Linear, (0, 0), (0, 4));
+        assert_eq!(grid.selected_text().as_deref(), Some("line3"));
+
+        grid.scroll_up(1);
+        assert!(grid.is_selected(1, 0));
+        assert!(!grid.is_selected(0, 0));
+        grid.process_pty_bytes(b"line5\r\n");
+        assert_eq!(grid.selected_text().as_deref(), Some("line3"));
+    }
+
+    #[test]
+    fn selection_can_start_in_the_scrollback() {
+        let mut grid = grid_with_lines(10, 3, 5);
+        grid.scroll_to_top();
+        select(&mut grid, SelectionMode::Linear, (0, 0), (1, 4));
+        grid.scroll_to_bottom();
+        assert_eq!(grid.selected_text().as_deref(), Some("line0\nline1"));
+    }
+
+    #[test]
+    fn selection_survives_a_full_scrollback() {
+        let mut grid = TerminalGrid::with_scrollback(10, 3, 4);
+        for i in 0..10 {
+            grid.process_pty_bytes(format!("line{i}\r\n").as_bytes());
+        }
+        select(&mut grid, SelectionMode::Linear, (0, 0), (0, 4));
+        assert_eq!(grid.selected_text().as_deref(), Some("line8"));
+
+        grid.process_pty_bytes(b"line10\r\nline11\r\n");
+        assert_eq!(grid.selected_text().as_deref(), Some("line8"));
+        grid.scroll_to_top();
+        assert!(grid.is_selected(2, 0));
+    }
+
+    #[test]
+    fn lines_are_counted_with_a_full_scrollback() {
+        let mut grid = TerminalGrid::with_scrollback(10, 3, 4);
+        for i in 0..10 {
+            grid.process_pty_bytes(format!("line{i}\r\n").as_bytes());
+        }
+        assert_eq!(grid.scrollback_len(), 4);
+        assert_eq!(grid.cursor_point().line, 10);
+        // Batches the full scrollback can count, one line or two at a time
+        grid.process_pty_bytes(b"line10\r\n");
+        assert_eq!(grid.cursor_point().line, 11);
+        grid.process_pty_bytes(b"line11\r\nline12\r\n");
+        assert_eq!(grid.cursor_point().line, 13);
+        grid.scroll_up(1);
+        grid.process_pty_bytes(b"line13\r\n");
+        assert_eq!(grid.cursor_point().line, 14);
+        assert_eq!(row_text(&grid, 0), "line10");
+    }
+
+    #[test]
+    fn output_that_overruns_the_scrollback_ends_the_selection() {
+        let mut grid = TerminalGrid::with_scrollback(10, 3, 4);
+        for i in 0..10 {
+            grid.process_pty_bytes(format!("line{i}\r\n").as_bytes());
+        }
+        select(&mut grid, SelectionMode::Linear, (0, 0), (0, 4));
+        let before = grid.cursor_point().line;
+
+        let burst: String = (10..20).map(|i| format!("line{i}\r\n")).collect();
+        grid.process_pty_bytes(burst.as_bytes());
+        assert_eq!(grid.selected_text(), None);
+        // Every line anchored before the burst is out of reach
+        assert!(grid.cursor_point().line >= before + 10);
+
+        select(&mut grid, SelectionMode::Linear, (0, 0), (0, 5));
+        assert_eq!(grid.selected_text().as_deref(), Some("line18"));
+        grid.scroll_to_top();
+        assert_eq!(row_text(&grid, 0), "line14");
+    }
+
+    #[test]
+    fn scrolled_view_stays_on_its_lines_while_output_arrives() {
+        let mut grid = grid_with_lines(10, 5, 7);
+        grid.scroll_up(2);
+        assert_eq!(row_text(&grid, 0), "line1");
+        grid.process_pty_bytes(b"line7\r\nline8\r\n");
+        assert_eq!(row_text(&grid, 0), "line1");
+        assert_eq!(grid.scroll_offset(), 4);
+    }
+
+    fn link_uri(grid: &TerminalGrid, row: usize, col: usize) -> Option<String> {
+        let link = grid.get_cell(row, col)?.link?;
+        Some(grid.hyperlink(link)?.uri.clone())
+    }
+
+    #[test]
+    fn hyperlinked_cells_carry_their_link() {
+        let mut grid = TerminalGrid::new(10, 3);
+        grid.process_pty_bytes(b"a \x1b]8;;http://x\x1b\\link\x1b]8;;\x1b\\ b");
+        assert_eq!(link_uri(&grid, 0, 1), None);
+        assert_eq!(link_uri(&grid, 0, 2).as_deref(), Some("http://x"));
+        assert_eq!(link_uri(&grid, 0, 5).as_deref(), Some("http://x"));
+        assert_eq!(link_uri(&grid, 0, 7), None);
+    }
+
+    #[test]
+    fn links_follow_wraps_and_scrolling() {
+        let mut grid = TerminalGrid::new(4, 2);
+        grid.process_pty_bytes(b"abc\x1b]8;id=1;file:///tmp\x07def\x1b]8;;\x07");
+        // "d" fills the last column, "e" and "f" wrap onto the next row
+        assert_eq!(link_uri(&grid, 0, 3).as_deref(), Some("file:///tmp"));
+        assert_eq!(link_uri(&grid, 1, 0).as_deref(), Some("file:///tmp"));
+        assert_eq!(link_uri(&grid, 1, 1).as_deref(), Some("file:///tmp"));
+
+        grid.process_pty_bytes(b"\r\nnext");
+        assert_eq!(link_uri(&grid, 0, 0).as_deref(), Some("file:///tmp"));
+        grid.scroll_up(1);
+        assert_eq!(link_uri(&grid, 0, 3).as_deref(), Some("file:///tmp"));
+    }
+
+    #[test]
+    fn overwritten_link_cells_lose_their_link() {
+        let mut grid = TerminalGrid::new(10, 2);
+        grid.process_pty_bytes(b"\x1b]8;;http://x\x07link\x1b]8;;\x07");
+        grid.process_pty_bytes(b"\x1b[H\x1b[2J");
+        assert_eq!(link_uri(&grid, 0, 0), None);
+        grid.process_pty_bytes(b"\x1b[Hl");
+        assert_eq!(link_uri(&grid, 0, 0).as_deref(), Some("http://x"));
+        grid.process_pty_bytes(b"\x1b[Hx");
+        assert_eq!(link_uri(&grid, 0, 0), None);
+    }
+
+    #[test]
+    fn detection_follows_soft_wraps() {
+        let mut grid = TerminalGrid::new(10, 3);
+        grid.process_pty_bytes(b"go https://example.com/x now");
+        let found = grid.detect_at(1, 2).unwrap();
+        assert_eq!(found.kind, MatchKind::Url);
+        assert_eq!(found.text, "https://example.com/x");
+        assert!(grid.match_contains(&found, 0, 3));
+        assert!(grid.match_contains(&found, 2, 3));
+        assert!(!grid.match_contains(&found, 2, 4));
+        assert_eq!(grid.detect_at(0, 1), None);
+    }
+
+    #[test]
+    fn detection_finds_file_references_and_hashes() {
+        let mut grid = TerminalGrid::new(40, 3);
+        grid.process_pty_bytes(b"src/\xe4\xb8\xad.rs:3:9\r\ncommit 9cce8ee");
+        let found = grid.detect_at(0, 5).unwrap();
+        assert_eq!(
+            found.kind,
+            MatchKind::Path {
+                line: Some(3),
+                col: Some(9)
+            }
+        );
+        assert_eq!(found.text, "src/\u{4e2d}.rs");
+        assert_eq!(grid.detect_at(1, 8).unwrap().kind, MatchKind::GitSha);
+    }
+
+    #[test]
+    fn visible_matches_include_lines_wrapped_in_from_above() {
+        let mut grid = TerminalGrid::with_scrollback(10, 3, 100);
+        grid.process_pty_bytes(b"see https://example.com/x\r\n10.0.0.1\r\nlast");
+        // The URL's first row has scrolled off the top
+        assert!(grid.scroll_offset() == 0 && grid.scrollback_len() == 2);
+        let matches = grid.visible_matches();
+        let texts: Vec<&str> = matches.iter().map(|found| found.text.as_str()).collect();
+        assert_eq!(texts, ["https://example.com/x", "10.0.0.1"]);
+        assert_eq!(grid.viewport_cell(matches[0].start), None);
+        assert_eq!(grid.viewport_cell(matches[1].start), Some((1, 0)));
+
+        grid.scroll_up(2);
+        assert_eq!(grid.visible_matches().len(), 1);
+        assert_eq!(grid.viewport_cell(matches[0].start), Some((0, 4)));
+    }
+
+    fn query(text: &str, regex: bool) -> SearchQuery {
+        SearchQuery {
+            text: text.to_string(),
+            regex,
+            case_sensitive: true,
+        }
+    }
+
+    #[test]
+    fn search_covers_scrollback_and_soft_wraps() {
+        let mut grid = TerminalGrid::with_scrollback(8, 3, 100);
+        grid.process_pty_bytes(b"error: one\r\nok\r\nno  errors\r\nlast");
+        let matcher = Matcher::new(&query("error", false)).unwrap();
+        assert_eq!(
+            grid.search(&matcher),
+            [
+                SearchMatch {
+                    start: Point::new(0, 0),
+                    end: Point::new(0, 4),
+                },
+                SearchMatch {
+                    start: Point::new(3, 4),
+                    end: Point::new(4, 0),
+                },
+            ]
+        );
+        // Padding at the end of a line isn't searched
+        let matcher = Matcher::new(&query(r"\w $", true)).unwrap();
+        assert_eq!(grid.search(&matcher), []);
+    }
+
+    #[test]
+    fn search_navigation_scrolls_to_the_current_match() {
+        let mut grid = grid_with_lines(10, 5, 8);
+        assert_eq!(grid.start_search(&query("line[01]", true)), Ok(2));
+        assert_eq!(grid.search_status(), Some((Some(2), 2)));
+        assert_eq!(grid.scroll_offset(), 4);
+        assert_eq!(grid.search_highlight(1, 4), Some(SearchHighlight::Current));
+        assert_eq!(grid.search_highlight(0, 4), Some(SearchHighlight::Match));
+        assert_eq!(grid.search_highlight(0, 5), None);
+
+        grid.search_next();
+        assert_eq!(grid.search_status(), Some((Some(1), 2)));
+        assert_eq!(grid.search_highlight(0, 0), Some(SearchHighlight::Current));
+        grid.search_previous();
+        assert_eq!(grid.search_status(), Some((Some(2), 2)));
+
+        grid.end_search();
+        assert_eq!(grid.search_highlight(1, 0), None);
+    }
+
+    #[test]
+    fn search_picks_up_new_output() {
+        let mut grid = TerminalGrid::new(10, 3);
+        grid.process_pty_bytes(b"foo\r\n");
+        assert_eq!(grid.start_search(&query("foo", false)), Ok(1));
+        grid.process_pty_bytes(b"foo foo");
+        grid.refresh_search();
+        assert_eq!(grid.search_status(), Some((Some(1), 3)));
+        assert!(grid.start_search(&query("(", true)).is_err());
+    }
+
+    #[test]
+    fn search_refresh_rescans_only_the_live_screen() {
+        let mut grid = TerminalGrid::with_scrollback(6, 2, 4);
+        grid.process_pty_bytes(b"ab\r\nab\r\nxx");
+        assert_eq!(grid.start_search(&query("ab", false)), Ok(2));
+        // A match in the scrollback is kept as collected, so one that
+        // couldn't be found again shows the scrollback wasn't searched
+        grid.search.as_mut().unwrap().matches[0].end.col = 5;
+
+        // "xx" soft-wraps on to a new line and scrolls into the
+        // scrollback; the logical line is searched again as a whole
+        grid.process_pty_bytes(b"xxxxab\r\nab");
+        grid.refresh_search();
+        let matches = &grid.search.as_ref().unwrap().matches;
+        assert_eq!(matches.len(), 4);
+        assert_eq!(matches[0].end, Point::new(0, 5));
+        assert_eq!(matches[2].start, Point::new(3, 0));
+
+        // Lines that drop off the scrollback take their matches along
+        grid.process_pty_bytes(b"\r\n\r\n");
+        grid.refresh_search();
+        assert_eq!(grid.search_status(), Some((Some(1), 3)));
+
+        // A resize can change every line
+        grid.search.as_mut().unwrap().matches[0].end.col = 5;
+        grid.resize(5, 2);
+        grid.refresh_search();
+        assert_eq!(grid.search.as_ref().unwrap().matches[0].end.col, 1);
+    }
+
+    #[test]
+    fn working_directory_follows_osc_7() {
+        let mut grid = TerminalGrid::new(10, 2);
+        assert_eq!(grid.reported_dir(), None);
+        grid.process_pty_bytes(b"\x1b]7;file://box/srv\x1b\\\x1b]7;file:///tmp\x07");
+        assert_eq!(
+            grid.reported_dir().map(|dir| dir.path.as_path()),
+            Some(std::path::Path::new("/tmp"))
+        );
+    }
+
+    #[test]
+    fn prompt_marks_find_the_last_output_and_failures() {
+        let mut grid = TerminalGrid::new(10, 5);
+        grid.process_pty_bytes(b"\x1b]133;A\x07$ \x1b]133;B\x07ls\r\n\x1b]133;C\x07");
+        grid.process_pty_bytes(b"one\r\ntwo\r\n\x1b]133;D;2\x07\x1b]133;A\x07$ ");
+        assert!(grid.select_last_output());
+        assert_eq!(grid.selected_text().as_deref(), Some("one\ntwo"));
+        assert!(grid.failed_prompt_at(0));
+        assert!(!grid.failed_prompt_at(3));
+
+        grid.process_pty_bytes(b"\x1b]133;B\x07true\r\n\x1b]133;C\x07\x1b]133;D;0\x07");
+        assert!(!grid.select_last_output());
+    }
+
+    #[test]
+    fn prompt_jumps_move_the_view_between_prompts() {
+        let mut grid = TerminalGrid::new(10, 4);
+        for _ in 0..3 {
+            grid.process_pty_bytes(b"\x1b]133;A\x07$ x\r\nout\r\n");
+        }
+        grid.process_pty_bytes(b"\x1b]133;A\x07$ ");
+        assert_eq!(row_text(&grid, 0), "out");
+
+        assert!(grid.scroll_to_previous_prompt());
+        assert_eq!(grid.scroll_offset(), 1);
+        assert!(grid.scroll_to_previous_prompt());
+        assert_eq!(grid.scroll_offset(), 3);
+        assert!(!grid.scroll_to_previous_prompt());
+
+        assert!(grid.scroll_to_next_prompt());
+        assert_eq!(grid.scroll_offset(), 1);
+        assert!(grid.scroll_to_next_prompt());
+        assert_eq!(grid.scroll_offset(), 0);
+        assert!(!grid.scroll_to_next_prompt());
+    }
+
+    #[test]
+    fn osc_palette_overrides_indexed_colors_until_reset() {
+        let mut grid = TerminalGrid::new(10, 2);
+        grid.process_pty_bytes(b"\x1b]4;1;#102030\x07\x1b[31mx");
+        assert_eq!(
+            grid.get_cell(0, 0).unwrap().fg,
+            Color32::from_rgb(0x10, 0x20, 0x30)
+        );
+
+        grid.process_pty_bytes(b"\x1b]104;1\x07");
+        assert_eq!(grid.get_cell(0, 0).unwrap().fg, xterm_256_color(1));
+    }
+
+    #[test]
+    fn osc_default_colors_apply_and_reset() {
+        let mut grid = TerminalGrid::new(10, 2);
+        grid.process_pty_bytes(b"\x1b]11;rgb:28/28/28\x1b\\\x1b]12;#ff0000\x07");
+        assert_eq!(grid.default_bg(), Color32::from_rgb(0x28, 0x28, 0x28));
+        assert_eq!(grid.cursor_color(), Some(Color32::from_rgb(255, 0, 0)));
+
+        grid.process_pty_bytes(b"\x1b]111\x07\x1b]112\x07");
+        assert_eq!(grid.default_bg(), DEFAULT_BG);
+        assert_eq!(grid.cursor_color(), None);
+    }
+
+    #[test]
+    fn theme_changes_keep_colors_the_program_set() {
+        let mut grid = TerminalGrid::new(10, 2);
+        grid.process_pty_bytes(b"\x1b]11;#282828\x07\x1b]4;2;#00ff00\x07\x1b[31mr\x1b[32mg");
+        let mut theme = Theme {
+            foreground: Color32::from_rgb(1, 2, 3),
+            background: Color32::from_rgb(4, 5, 6),
+            cursor: Some(Color32::from_rgb(7, 8, 9)),
+            ..Theme::default()
+        };
+        theme.palette[1] = Color32::from_rgb(200, 0, 0);
+        grid.set_theme(theme.clone());
+
+        assert_eq!(grid.default_bg(), Color32::from_rgb(0x28, 0x28, 0x28));
+        assert_eq!(grid.cursor_color(), theme.cursor);
+        assert_eq!(
+            grid.get_cell(0, 0).unwrap().fg,
+            Color32::from_rgb(200, 0, 0)
+        );
+        assert_eq!(
+            grid.get_cell(0, 1).unwrap().fg,
+            Color32::from_rgb(0, 255, 0)
+        );
+        assert_eq!(
+            grid.get_cell(0, 2).unwrap().bg,
+            Color32::from_rgb(0x28, 0x28, 0x28)
+        );
+
+        grid.process_pty_bytes(b"\x1b]111\x07");
+        assert_eq!(grid.default_bg(), theme.background);
+    }
+
+    #[test]
+    fn cursor_position_report_reflects_preceding_output() {
+        let mut grid = TerminalGrid::new(20, 5);
+        grid.process_pty_bytes(b"\x1b[3;4Habc\x1b[6n\r\n\x1b[?6n");
+        assert_eq!(grid.take_responses(), b"\x1b[3;7R\x1b[?4;1R");
+        assert!(grid.take_responses().is_empty());
+    }
+
+    #[test]
+    fn device_attribute_and_status_queries_are_answered() {
+        let mut grid = TerminalGrid::new(20, 5);
+        grid.process_pty_bytes(b"\x1b[c\x1b[5n");
+        assert_eq!(grid.take_responses(), b"\x1b[?62;22c\x1b[0n");
+
+        grid.process_pty_bytes(b"\x1b[>c");
+        assert_eq!(grid.take_responses(), b"\x1b[>0;100;0c");
+
+        grid.process_pty_bytes(b"\x1b[>q");
+        let reply = String::from_utf8(grid.take_responses()).unwrap();
+        assert!(reply.starts_with("\x1bP>|shitty ") && reply.ends_with("\x1b\\"));
+    }
+
+    #[test]
+    fn color_queries_report_current_colors() {
+        let mut grid = TerminalGrid::new(20, 5);
+        grid.process_pty_bytes(b"\x1b]11;#102030\x07\x1b]11;?\x07\x1b]4;1;?\x1b\\");
+        assert_eq!(
+            grid.take_responses(),
+            b"\x1b]11;rgb:1010/2020/3030\x07\x1b]4;1;rgb:8080/0000/0000\x1b\\"
+        );
+    }
+
+    #[test]
+    fn title_and_icon_name_follow_osc_sequences() {
+        let mut grid = TerminalGrid::new(20, 5);
+        assert_eq!(grid.title(), "");
+        grid.process_pty_bytes(b"\x1b]0;zsh\x07\x1b]2;vim\x07");
+        assert_eq!(grid.title(), "vim");
+        assert_eq!(grid.icon_name(), "zsh");
+    }
+
+    #[test]
+    fn title_stack_restores_pushed_titles() {
+        let mut grid = TerminalGrid::new(20, 5);
+        grid.process_pty_bytes(b"\x1b]0;shell\x07\x1b[22;0t\x1b]0;htop\x07");
+        assert_eq!(grid.title(), "htop");
+
+        // Popping only the title leaves the icon name alone
+        grid.process_pty_bytes(b"\x1b[23;2t");
+        assert_eq!(grid.title(), "shell");
+        assert_eq!(grid.icon_name(), "htop");
+
+        // Popping an empty stack is a no-op
+        grid.process_pty_bytes(b"\x1b[23;0t");
+        assert_eq!(grid.title(), "shell");
+    }
+
+    #[test]
+    fn title_stack_pushes_only_the_parts_asked_for() {
+        let mut grid = TerminalGrid::new(20, 5);
+        grid.process_pty_bytes(b"\x1b]1;icon\x07\x1b]2;title\x07");
+
+        // 22;1 saves the icon name alone, so popping both restores only it
+        grid.process_pty_bytes(b"\x1b[22;1t\x1b]0;vim\x07\x1b[23;0t");
+        assert_eq!(grid.icon_name(), "icon");
+        assert_eq!(grid.title(), "vim");
+
+        // 22;2 saves the title alone
+        grid.process_pty_bytes(b"\x1b]0;shell\x07\x1b[22;2t\x1b]0;less\x07\x1b[23;0t");
+        assert_eq!(grid.title(), "shell");
+        assert_eq!(grid.icon_name(), "less");
+
+        // A pop of the title leaves a pushed icon name unused
+        grid.process_pty_bytes(b"\x1b[22;0t\x1b]0;top\x07\x1b[23;2t");
+        assert_eq!(grid.title(), "shell");
+        assert_eq!(grid.icon_name(), "top");
+    }
+
+    #[test]
+    fn keyboard_modes_follow_decckm_and_deckpam() {
+        let mut grid = TerminalGrid::new(20, 5);
+        assert_eq!(grid.keyboard_modes(), KeyboardModes::default());
+        grid.process_pty_bytes(b"\x1b[?1h\x1b=");
+        let modes = grid.keyboard_modes();
+        assert!(modes.application_cursor && modes.application_keypad);
+        grid.process_pty_bytes(b"\x1b[?1l\x1b>");
+        assert_eq!(grid.keyboard_modes(), KeyboardModes::default());
+        grid.process_pty_bytes(b"\x1b[?2004h");
+        assert!(grid.keyboard_modes().bracketed_paste);
+    }
+
+    #[test]
+    fn modify_other_keys_level_is_tracked_and_reported() {
+        let mut grid = TerminalGrid::new(20, 5);
+        grid.process_pty_bytes(b"\x1b[>4;2m");
+        assert_eq!(grid.keyboard_modes().modify_other_keys, 2);
+        grid.process_pty_bytes(b"\x1b[?4m");
+        assert_eq!(grid.take_responses(), b"\x1b[>4;2m");
+        grid.process_pty_bytes(b"\x1b[>4m");
+        assert_eq!(grid.keyboard_modes().modify_other_keys, 0);
+    }
+
+    #[test]
+    fn clipboard_writes_are_allowed_and_reads_denied_by_default() {
+        let mut grid = TerminalGrid::new(10, 2);
+        grid.process_pty_bytes(b"\x1b]52;c;aGk=\x07\x1b]52;c;?\x07");
+        assert_eq!(
+            grid.take_clipboard_events(),
+            vec![ClipboardEvent {
+                request: ClipboardRequest::Store("hi".to_string()),
+                needs_confirmation: false,
+            }]
+        );
+        assert!(grid.take_responses().is_empty());
+    }
+
+    #[test]
+    fn clipboard_policy_can_ask_or_deny() {
+        let mut grid = TerminalGrid::new(10, 2);
+        grid.set_clipboard_policy(ClipboardPolicy {
+            write: ClipboardAccess::Deny,
+            read: ClipboardAccess::Ask,
+        });
+        grid.process_pty_bytes(b"\x1b]52;c;aGk=\x07\x1b]52;p;?\x1b\\");
+        assert_eq!(
+            grid.take_clipboard_events(),
+            vec![ClipboardEvent {
+                request: ClipboardRequest::Load {
+                    targets: "p".to_string(),
+                    bell_terminated: false,
+                },
+                needs_confirmation: true,
+            }]
+        );
+    }
+
+    #[test]
+    fn kitty_keyboard_flags_form_a_stack_per_screen() {
+        let mut grid = TerminalGrid::new(20, 5);
+        grid.process_pty_bytes(b"\x1b[>1u\x1b[>3u\x1b[?u");
+        assert_eq!(grid.keyboard_modes().kitty_flags, 3);
+        assert_eq!(grid.take_responses(), b"\x1b[?3u");
+
+        // The alternate screen starts out with an empty stack
+        grid.process_pty_bytes(b"\x1b[?1049h");
+        assert_eq!(grid.keyboard_modes().kitty_flags, 0);
+        grid.process_pty_bytes(b"\x1b[=8;2u");
+        assert_eq!(grid.keyboard_modes().kitty_flags, 8);
+        grid.process_pty_bytes(b"\x1b[?1049l");
+        assert_eq!(grid.keyboard_modes().kitty_flags, 3);
+
+        grid.process_pty_bytes(b"\x1b[=2;3u");
+        assert_eq!(grid.keyboard_modes().kitty_flags, 1);
+        grid.process_pty_bytes(b"\x1b[<u");
+        assert_eq!(grid.keyboard_modes().kitty_flags, 1);
+        // Popping more entries than there are empties the stack
+        grid.process_pty_bytes(b"\x1b[<5u");
+        assert_eq!(grid.keyboard_modes().kitty_flags, 0);
+    }
+
+    #[test]
+    fn page_scrolling_keeps_one_line_of_context() {
+        let mut grid = grid_with_lines(10, 4, 20);
+        grid.scroll_page_up();
+        assert_eq!(grid.scroll_offset(), 3);
+        grid.scroll_page_down();
+        assert_eq!(grid.scroll_offset(), 0);
+    }
 }
@@ -0,0 +1,170 @@
+// Keyboard hints: every match on the screen gets a short label, and typing
+// a label picks its match.
+
+use crate::terminal::detect::DetectedMatch;
+
+/// Home row first, so the most common labels are the easiest to type
+pub(crate) const HINT_ALPHABET: &str = "asdfghjklqwertyuiopzxcvbnm";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Hint {
+    pub(crate) label: String,
+    pub(crate) found: DetectedMatch,
+}
+
+/// What typing a label ends up doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HintAction {
+    /// The match kind's click action
+    Default,
+    Copy,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum HintInput {
+    /// More letters are needed
+    Pending,
+    Picked(DetectedMatch, HintAction),
+    /// The typed letters don't start any label
+    NoMatch,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct HintsMode {
+    hints: Vec<Hint>,
+    typed: String,
+}
+
+impl HintsMode {
+    /// Labels the matches, shortest labels first from the bottom of the
+    /// screen, where the latest output is
+    pub(crate) fn new(matches: Vec<DetectedMatch>) -> Self {
+        let labels = hint_labels(matches.len());
+        let hints = matches
+            .into_iter()
+            .rev()
+            .zip(labels)
+            .map(|(found, label)| Hint { label, found })
+            .collect();
+        Self {
+            hints,
+            typed: String::new(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.hints.is_empty()
+    }
+
+    pub(crate) fn typed(&self) -> &str {
+        &self.typed
+    }
+
+    /// Hints whose label starts with what was typed so far
+    pub(crate) fn visible_hints(&self) -> impl Iterator<Item = &Hint> {
+        self.hints
+            .iter()
+            .filter(|hint| hint.label.starts_with(&self.typed))
+    }
+
+    /// Adds a letter to the typed label. Typing a label's last letter in
+    /// upper case copies the match instead.
+    pub(crate) fn type_char(&mut self, c: char) -> HintInput {
+        let action = if c.is_uppercase() {
+            HintAction::Copy
+        } else {
+            HintAction::Default
+        };
+        self.typed.extend(c.to_lowercase());
+        let input = {
+            let mut candidates = self.visible_hints();
+            match (candidates.next(), candidates.next()) {
+                (None, _) => HintInput::NoMatch,
+                (Some(hint), None) if hint.label == self.typed => {
+                    HintInput::Picked(hint.found.clone(), action)
+                }
+                _ => HintInput::Pending,
+            }
+        };
+        if input == HintInput::NoMatch {
+            self.typed.clear();
+        }
+        input
+    }
+
+    pub(crate) fn backspace(&mut self) {
+        self.typed.pop();
+    }
+}
+
+/// `count` distinct labels of equal length, so none is a prefix of another
+pub(crate) fn hint_labels(count: usize) -> Vec<String> {
+    let alphabet: Vec<char> = HINT_ALPHABET.chars().collect();
+    let mut len = 1;
+    while alphabet.len().pow(len) < count {
+        len += 1;
+    }
+    (0..count)
+        .map(|mut index| {
+            let mut label = vec![' '; len as usize];
+            for slot in label.iter_mut().rev() {
+                *slot = alphabet[index % alphabet.len()];
+                index /= alphabet.len();
+            }
+            label.into_iter().collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::detect::MatchKind;
+    use crate::terminal::selection::Point;
+
+    fn url(line: usize) -> DetectedMatch {
+        DetectedMatch {
+            kind: MatchKind::Url,
+            text: format!("http://{line}"),
+            start: Point::new(line, 0),
+            end: Point::new(line, 7),
+        }
+    }
+
+    #[test]
+    fn labels_grow_only_when_letters_run_out() {
+        assert_eq!(hint_labels(3), ["a", "s", "d"]);
+        let labels = hint_labels(30);
+        assert_eq!(labels[..2], ["aa", "as"]);
+        assert_eq!(labels[26], "sa");
+        assert!(labels.iter().all(|label| label.len() == 2));
+    }
+
+    #[test]
+    fn the_bottom_match_gets_the_first_label() {
+        let hints = HintsMode::new(vec![url(0), url(1)]);
+        assert_eq!(
+            hints.clone().type_char('a'),
+            HintInput::Picked(url(1), HintAction::Default)
+        );
+        assert_eq!(
+            hints.clone().type_char('S'),
+            HintInput::Picked(url(0), HintAction::Copy)
+        );
+        assert_eq!(hints.clone().type_char('x'), HintInput::NoMatch);
+    }
+
+    #[test]
+    fn typing_narrows_longer_labels() {
+        let mut hints = HintsMode::new((0..30).map(url).collect());
+        assert_eq!(hints.type_char('a'), HintInput::Pending);
+        assert_eq!(hints.visible_hints().count(), 26);
+        hints.backspace();
+        assert_eq!(hints.visible_hints().count(), 30);
+        assert_eq!(hints.type_char('s'), HintInput::Pending);
+        assert_eq!(
+            hints.type_char('a'),
+            HintInput::Picked(url(3), HintAction::Default)
+        );
+    }
+}
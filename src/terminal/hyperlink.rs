@@ -0,0 +1,102 @@
+// OSC 8 hyperlinks and the command that opens them.
+
+use std::io;
+use std::process::{Command, Stdio};
+use std::thread;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Hyperlink {
+    /// Cells with the same id and URI belong to one link, even when the
+    /// program printed them separately
+    pub(crate) id: Option<String>,
+    pub(crate) uri: String,
+}
+
+/// Runs a program with the URI as its last argument to open a link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LinkOpener {
+    pub(crate) program: String,
+    pub(crate) args: Vec<String>,
+}
+
+impl Default for LinkOpener {
+    fn default() -> Self {
+        let program = if cfg!(target_os = "macos") {
+            "open"
+        } else {
+            "xdg-open"
+        };
+        Self {
+            program: program.to_string(),
+            args: Vec::new(),
+        }
+    }
+}
+
+impl LinkOpener {
+    pub(crate) fn open(&self, uri: &str) -> io::Result<()> {
+        // Keep URIs from output from passing themselves off as options
+        if uri.starts_with('-') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "URI starts with '-'",
+            ));
+        }
+        let mut args = self.args.clone();
+        args.push(uri.to_string());
+        spawn_detached(&self.program, &args)
+    }
+}
+
+/// Starts a helper program without tying it to the terminal's stdio, and
+/// reaps it in the background.
+pub(crate) fn spawn_detached(program: &str, args: &[String]) -> io::Result<()> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    thread::spawn(move || {
+        let _ = child.wait();
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn opener_passes_the_uri_to_its_command() {
+        let out = std::env::temp_dir().join(format!("shitty-opener-{}", std::process::id()));
+        let opener = LinkOpener {
+            program: "sh".to_string(),
+            args: vec![
+                "-c".to_string(),
+                "printf %s \"$1\" > \"$0\"".to_string(),
+                out.display().to_string(),
+            ],
+        };
+        opener.open("https://example.com/a b").unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut opened = String::new();
+        while opened.is_empty() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+            opened = std::fs::read_to_string(&out).unwrap_or_default();
+        }
+        let _ = std::fs::remove_file(&out);
+        assert_eq!(opened, "https://example.com/a b");
+    }
+
+    #[test]
+    fn uris_that_look_like_options_are_refused() {
+        let opener = LinkOpener {
+            program: "true".to_string(),
+            args: Vec::new(),
+        };
+        assert!(opener.open("--help").is_err());
+    }
+}
@@ -5,163 +5,1286 @@ pub(crate) use egui_keymap::*;
 #[cfg(target_os = "macos")]
 pub(crate) use macos_keymap::*;
 
+/// A key as the terminal sees it, independent of the UI toolkit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Key {
+    Char(char),
+    Enter,
+    Tab,
+    Backspace,
+    Escape,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    /// F1 through F20
+    F(u8),
+    Keypad(KeypadKey),
+}
+
+/// Numeric keypad keys, which only differ from the main keys in
+/// application keypad mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum KeypadKey {
+    Digit(u8),
+    Decimal,
+    Divide,
+    Multiply,
+    Subtract,
+    Add,
+    Enter,
+    Equal,
+}
+
+impl KeypadKey {
+    // (normal mode text, final byte of the SS3 form in application mode)
+    fn encodings(self) -> (u8, u8) {
+        match self {
+            Self::Digit(n) => (b'0' + n.min(9), b'p' + n.min(9)),
+            Self::Decimal => (b'.', b'n'),
+            Self::Divide => (b'/', b'o'),
+            Self::Multiply => (b'*', b'j'),
+            Self::Subtract => (b'-', b'm'),
+            Self::Add => (b'+', b'k'),
+            Self::Enter => (b'\r', b'M'),
+            Self::Equal => (b'=', b'X'),
+        }
+    }
+}
+
+/// Input modes the running program controls through escape sequences
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct KeyboardModes {
+    /// DECCKM: cursor keys send `SS3 X` instead of `CSI X`
+    pub(crate) application_cursor: bool,
+    /// DECKPAM: the numeric keypad sends `SS3` sequences
+    pub(crate) application_keypad: bool,
+    /// Progressive enhancement flags of the kitty keyboard protocol
+    pub(crate) kitty_flags: u8,
+    /// xterm modifyOtherKeys level; the kitty protocol wins when both are on
+    pub(crate) modify_other_keys: u8,
+    /// Mode 2004: pastes are wrapped in `CSI 200 ~` / `CSI 201 ~`
+    pub(crate) bracketed_paste: bool,
+}
+
+impl KeyboardModes {
+    /// Whether text keys have to be encoded as escape codes too
+    pub(crate) fn reports_all_keys(self) -> bool {
+        self.kitty_flags & KITTY_REPORT_ALL_KEYS != 0
+    }
+}
+
+// kitty keyboard protocol enhancement flags, set with `CSI > flags u`
+pub(crate) const KITTY_DISAMBIGUATE: u8 = 0b1;
+pub(crate) const KITTY_REPORT_EVENTS: u8 = 0b10;
+pub(crate) const KITTY_REPORT_ALTERNATES: u8 = 0b100;
+pub(crate) const KITTY_REPORT_ALL_KEYS: u8 = 0b1000;
+pub(crate) const KITTY_REPORT_TEXT: u8 = 0b1_0000;
+pub(crate) const KITTY_ALL_FLAGS: u8 = 0b1_1111;
+
+/// Whether a key went down, auto-repeated or went up
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum KeyEventKind {
+    #[default]
+    Press,
+    Repeat,
+    Release,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct KeyModifiers {
+    pub(crate) shift: bool,
+    pub(crate) alt: bool,
+    pub(crate) ctrl: bool,
+    /// Alt is held on the right side only; platforms that can't tell the
+    /// sides apart leave this unset
+    pub(crate) right_alt: bool,
+}
+
+impl KeyModifiers {
+    /// The xterm modifier parameter: 1 plus a bit per modifier
+    fn xterm_param(self) -> u8 {
+        1 + self.shift as u8 + ((self.alt as u8) << 1) + ((self.ctrl as u8) << 2)
+    }
+
+    fn any(self) -> bool {
+        self.shift || self.alt || self.ctrl
+    }
+}
+
+/// What Alt does to keys that produce text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AltMode {
+    /// Prefix the key with ESC, the meta convention readline and zsh expect
+    Escape,
+    /// Set the high bit of ASCII keys (8-bit meta), sent UTF-8 encoded
+    EightBit,
+    /// Leave Alt to the keyboard layout so AltGr/Option characters come through
+    Compose,
+}
+
+/// Turns keys into the bytes xterm would send, according to user options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct KeyEncoder {
+    pub(crate) left_alt: AltMode,
+    pub(crate) right_alt: AltMode,
+}
+
+impl Default for KeyEncoder {
+    fn default() -> Self {
+        // Right Alt doubles as AltGr on many layouts
+        Self {
+            left_alt: AltMode::Escape,
+            right_alt: AltMode::Compose,
+        }
+    }
+}
+
+impl KeyEncoder {
+    /// How Alt applies to text keys, or `None` when Alt isn't held
+    pub(crate) fn alt_mode(&self, mods: KeyModifiers) -> Option<AltMode> {
+        mods.alt.then_some(if mods.right_alt {
+            self.right_alt
+        } else {
+            self.left_alt
+        })
+    }
+
+    /// Writes the bytes xterm sends for `key`. Returns false when the key has
+    /// no encoding, so the caller can fall back to the text the toolkit
+    /// produced.
+    pub(crate) fn encode(
+        &self,
+        key: Key,
+        mods: KeyModifiers,
+        modes: KeyboardModes,
+        out: &mut Vec<u8>,
+    ) -> bool {
+        self.encode_event(key, mods, KeyEventKind::Press, modes, out)
+    }
+
+    /// Like `encode`, for any kind of key event. Repeats and releases only
+    /// have encodings of their own under the kitty keyboard protocol.
+    pub(crate) fn encode_event(
+        &self,
+        key: Key,
+        mods: KeyModifiers,
+        kind: KeyEventKind,
+        modes: KeyboardModes,
+        out: &mut Vec<u8>,
+    ) -> bool {
+        let flags = modes.kitty_flags;
+        let kind = match kind {
+            _ if flags & KITTY_REPORT_EVENTS != 0 => kind,
+            KeyEventKind::Release => return false,
+            _ => KeyEventKind::Press,
+        };
+        if uses_kitty_encoding(key, mods, kind, flags) {
+            return push_kitty_key(key, mods, kind, flags, out);
+        }
+        if kind == KeyEventKind::Release {
+            return false;
+        }
+        if flags == 0
+            && let Some(code) = modify_other_keys_code(key, mods, modes.modify_other_keys)
+        {
+            let sequence = format!("\x1b[27;{};{code}~", mods.xterm_param());
+            out.extend_from_slice(sequence.as_bytes());
+            return true;
+        }
+        self.encode_legacy(key, mods, modes, out)
+    }
+
+    fn encode_legacy(
+        &self,
+        key: Key,
+        mods: KeyModifiers,
+        modes: KeyboardModes,
+        out: &mut Vec<u8>,
+    ) -> bool {
+        let cursor_key = |final_byte, out: &mut Vec<u8>| {
+            push_cursor_key(final_byte, mods, modes.application_cursor, out)
+        };
+        match key {
+            Key::Char(c) => {
+                if mods.ctrl
+                    && let Some(byte) = ctrl_byte(c)
+                {
+                    self.push_text_key(&[byte], mods, out);
+                } else {
+                    let mut buf = [0u8; 4];
+                    self.push_text_key(c.encode_utf8(&mut buf).as_bytes(), mods, out);
+                }
+            }
+            Key::Enter => self.push_text_key(b"\r", mods, out),
+            Key::Tab if mods.shift => out.extend_from_slice(b"\x1b[Z"),
+            Key::Tab => self.push_text_key(b"\t", mods, out),
+            Key::Backspace if mods.ctrl => self.push_text_key(&[0x08], mods, out),
+            Key::Backspace => self.push_text_key(&[0x7f], mods, out),
+            Key::Escape => self.push_text_key(&[0x1b], mods, out),
+            Key::Up => cursor_key(b'A', out),
+            Key::Down => cursor_key(b'B', out),
+            Key::Right => cursor_key(b'C', out),
+            Key::Left => cursor_key(b'D', out),
+            Key::Home => cursor_key(b'H', out),
+            Key::End => cursor_key(b'F', out),
+            Key::Insert => push_tilde_key(2, mods, out),
+            Key::Delete => push_tilde_key(3, mods, out),
+            Key::PageUp => push_tilde_key(5, mods, out),
+            Key::PageDown => push_tilde_key(6, mods, out),
+            Key::F(n @ 1..=4) => {
+                let final_byte = b'P' + (n - 1);
+                if mods.any() {
+                    push_csi_modified(1, mods, final_byte, out);
+                } else {
+                    out.extend_from_slice(&[0x1b, b'O', final_byte]);
+                }
+            }
+            Key::F(n @ 5..=20) => {
+                // xterm skips 16, 22, 27 and 30 in the function key numbering
+                const CODES: [u8; 16] = [
+                    15, 17, 18, 19, 20, 21, 23, 24, 25, 26, 28, 29, 31, 32, 33, 34,
+                ];
+                push_tilde_key(CODES[(n - 5) as usize], mods, out);
+            }
+            Key::F(_) => return false,
+            Key::Keypad(key) => {
+                let (text, app_final) = key.encodings();
+                if modes.application_keypad {
+                    out.extend_from_slice(&[0x1b, b'O', app_final]);
+                } else {
+                    out.push(text);
+                }
+            }
+        }
+        true
+    }
+
+    // Keys that send plain bytes get Alt applied as meta rather than as a
+    // modifier parameter.
+    fn push_text_key(&self, bytes: &[u8], mods: KeyModifiers, out: &mut Vec<u8>) {
+        match (self.alt_mode(mods), bytes) {
+            (Some(AltMode::Escape), _) => {
+                out.push(0x1b);
+                out.extend_from_slice(bytes);
+            }
+            (Some(AltMode::EightBit), &[byte]) if byte.is_ascii() => {
+                let mut buf = [0u8; 4];
+                let meta = char::from(byte | 0x80);
+                out.extend_from_slice(meta.encode_utf8(&mut buf).as_bytes());
+            }
+            _ => out.extend_from_slice(bytes),
+        }
+    }
+}
+
+/// Writes pasted text the way the program asked for it. Escape sequences
+/// and control characters other than tab and newlines are dropped, so the
+/// text can't end the bracket early or drive the terminal.
+pub(crate) fn encode_paste(text: &str, bracketed: bool, out: &mut Vec<u8>) {
+    let mut filter = PasteFilter::default();
+    let mut parser = vte::Parser::new();
+    for byte in text.bytes() {
+        parser.advance(&mut filter, byte);
+    }
+    let mut text = filter.text;
+    if !bracketed {
+        // Programs that don't know about pastes see them as typed lines
+        text = text.replace("\r\n", "\r").replace('\n', "\r");
+    }
+    if bracketed {
+        out.extend_from_slice(b"\x1b[200~");
+    }
+    out.extend_from_slice(text.as_bytes());
+    if bracketed {
+        out.extend_from_slice(b"\x1b[201~");
+    }
+}
+
+#[derive(Default)]
+struct PasteFilter {
+    text: String,
+}
+
+impl vte::Perform for PasteFilter {
+    fn print(&mut self, c: char) {
+        // C1 controls arrive UTF-8 encoded, which vte prints as text
+        if !c.is_control() {
+            self.text.push(c);
+        }
+    }
+
+    fn execute(&mut self, byte: u8) {
+        if matches!(byte, b'\t' | b'\n' | b'\r') {
+            self.text.push(char::from(byte));
+        }
+    }
+}
+
+// The kitty protocol leaves keys on their legacy encoding unless a flag asks
+// otherwise. Enter, Tab and Backspace stay legacy even when disambiguating so
+// a shell remains usable after a program exits without popping its flags.
+fn uses_kitty_encoding(key: Key, mods: KeyModifiers, kind: KeyEventKind, flags: u8) -> bool {
+    let legacy_text_key = matches!(key, Key::Enter | Key::Tab | Key::Backspace);
+    if flags & KITTY_REPORT_ALL_KEYS != 0 {
+        return true;
+    }
+    match kind {
+        KeyEventKind::Release => return !legacy_text_key,
+        // Repeated text still comes through as text
+        KeyEventKind::Repeat if !legacy_text_key && !matches!(key, Key::Char(_)) => return true,
+        _ => {}
+    }
+    if flags & KITTY_DISAMBIGUATE == 0 {
+        return false;
+    }
+    match key {
+        Key::Char(_) => mods.ctrl || mods.alt,
+        Key::Escape | Key::Keypad(_) => true,
+        Key::F(n) => n > 12,
+        _ => false,
+    }
+}
+
+// `CSI code[:shifted] ; mods[:event] ; text final`, dropping trailing
+// fields that hold their default
+fn push_kitty_key(
+    key: Key,
+    mods: KeyModifiers,
+    kind: KeyEventKind,
+    flags: u8,
+    out: &mut Vec<u8>,
+) -> bool {
+    let Some((code, final_byte)) = kitty_key_code(key) else {
+        return false;
+    };
+    let mut key_field = code.to_string();
+    let mut text_field = String::new();
+    if let Key::Char(c) = key {
+        if flags & KITTY_REPORT_ALTERNATES != 0 && mods.shift && c as u32 != code {
+            key_field.push_str(&format!(":{}", c as u32));
+        }
+        let reports_text = flags & KITTY_REPORT_ALL_KEYS != 0 && flags & KITTY_REPORT_TEXT != 0;
+        if reports_text && kind != KeyEventKind::Release && !mods.ctrl && !mods.alt {
+            text_field = (c as u32).to_string();
+        }
+    }
+    let mut mods_field = String::new();
+    if mods.any() || kind != KeyEventKind::Press {
+        mods_field = mods.xterm_param().to_string();
+    }
+    match kind {
+        KeyEventKind::Press => {}
+        KeyEventKind::Repeat => mods_field.push_str(":2"),
+        KeyEventKind::Release => mods_field.push_str(":3"),
+    }
+
+    let mut fields = vec![key_field, mods_field, text_field];
+    while fields.len() > 1 && fields.last().is_some_and(String::is_empty) {
+        fields.pop();
+    }
+    // Letter-terminated keys are `CSI A` rather than `CSI 1 A`
+    if final_byte != b'u' && final_byte != b'~' && fields.len() == 1 {
+        fields.clear();
+    }
+    out.extend_from_slice(b"\x1b[");
+    out.extend_from_slice(fields.join(";").as_bytes());
+    out.push(final_byte);
+    true
+}
+
+// The key number and final byte the kitty protocol reports a key with.
+// Text keys are reported by their unshifted character.
+fn kitty_key_code(key: Key) -> Option<(u32, u8)> {
+    let code = match key {
+        Key::Char(c) => {
+            let mut lower = c.to_lowercase();
+            let base = match (lower.next(), lower.next()) {
+                (Some(base), None) => base,
+                _ => c,
+            };
+            (base as u32, b'u')
+        }
+        Key::Escape => (27, b'u'),
+        Key::Enter => (13, b'u'),
+        Key::Tab => (9, b'u'),
+        Key::Backspace => (127, b'u'),
+        Key::Insert => (2, b'~'),
+        Key::Delete => (3, b'~'),
+        Key::PageUp => (5, b'~'),
+        Key::PageDown => (6, b'~'),
+        Key::Up => (1, b'A'),
+        Key::Down => (1, b'B'),
+        Key::Right => (1, b'C'),
+        Key::Left => (1, b'D'),
+        Key::Home => (1, b'H'),
+        Key::End => (1, b'F'),
+        Key::F(1) => (1, b'P'),
+        Key::F(2) => (1, b'Q'),
+        Key::F(3) => (13, b'~'),
+        Key::F(4) => (1, b'S'),
+        Key::F(n @ 5..=12) => {
+            const CODES: [u32; 8] = [15, 17, 18, 19, 20, 21, 23, 24];
+            (CODES[(n - 5) as usize], b'~')
+        }
+        // F13 onwards live in the Unicode private use area
+        Key::F(n @ 13..=20) => (57376 + u32::from(n - 13), b'u'),
+        Key::F(_) => return None,
+        Key::Keypad(key) => {
+            let code = match key {
+                KeypadKey::Digit(n) => 57399 + u32::from(n.min(9)),
+                KeypadKey::Decimal => 57409,
+                KeypadKey::Divide => 57410,
+                KeypadKey::Multiply => 57411,
+                KeypadKey::Subtract => 57412,
+                KeypadKey::Add => 57413,
+                KeypadKey::Enter => 57414,
+                KeypadKey::Equal => 57415,
+            };
+            (code, b'u')
+        }
+    };
+    Some(code)
+}
+
+// The character code modifyOtherKeys reports a key with, when the level
+// calls for `CSI 27 ; mod ; code ~`. Level 1 only covers chords with no
+// distinct legacy encoding; level 2 covers every modified key except
+// shifted text.
+fn modify_other_keys_code(key: Key, mods: KeyModifiers, level: u8) -> Option<u32> {
+    let (code, level_1) = match key {
+        Key::Char(c) => {
+            // Toolkits don't agree on the case of Ctrl chords
+            let c = match (c.is_ascii_alphabetic(), mods.shift) {
+                (true, true) => c.to_ascii_uppercase(),
+                (true, false) => c.to_ascii_lowercase(),
+                (false, _) => c,
+            };
+            let level_1 = mods.ctrl && (mods.shift || ctrl_byte(c).is_none());
+            if !level_1 && !mods.ctrl && !mods.alt {
+                return None;
+            }
+            (c as u32, level_1)
+        }
+        Key::Enter => (13, mods.ctrl),
+        Key::Tab => (9, mods.ctrl),
+        Key::Escape => (27, mods.ctrl),
+        Key::Backspace => (127, false),
+        _ => return None,
+    };
+    let applies = match level {
+        1 => level_1,
+        2 => mods.any(),
+        _ => false,
+    };
+    applies.then_some(code)
+}
+
+/// The C0 control byte xterm sends for Ctrl plus `c`, if there is one.
+/// Besides `@` through `~` this covers the VT220 digit row aliases and
+/// Ctrl+Space, Ctrl+/ and Ctrl+?.
+pub(crate) fn ctrl_byte(c: char) -> Option<u8> {
+    match c {
+        ' ' | '2' => Some(0x00),
+        '3'..='7' => Some(c as u8 - b'3' + 0x1b),
+        '8' | '?' => Some(0x7f),
+        '/' => Some(0x1f),
+        '@'..='~' => Some(c as u8 & 0x1f),
+        _ => None,
+    }
+}
+
+// `CSI X` unmodified (`SS3 X` under DECCKM), `CSI 1 ; mod X` with modifiers
+fn push_cursor_key(final_byte: u8, mods: KeyModifiers, application: bool, out: &mut Vec<u8>) {
+    if mods.any() {
+        push_csi_modified(1, mods, final_byte, out);
+    } else {
+        let intro = if application { b'O' } else { b'[' };
+        out.extend_from_slice(&[0x1b, intro, final_byte]);
+    }
+}
+
+// `CSI code ~` unmodified, `CSI code ; mod ~` with modifiers
+fn push_tilde_key(code: u8, mods: KeyModifiers, out: &mut Vec<u8>) {
+    if mods.any() {
+        push_csi_modified(code, mods, b'~', out);
+    } else {
+        out.extend_from_slice(format!("\x1b[{code}~").as_bytes());
+    }
+}
+
+fn push_csi_modified(code: u8, mods: KeyModifiers, final_byte: u8, out: &mut Vec<u8>) {
+    out.extend_from_slice(format!("\x1b[{code};{}", mods.xterm_param()).as_bytes());
+    out.push(final_byte);
+}
+
 #[cfg(not(target_os = "macos"))]
 pub mod egui_keymap {
+    use super::{
+        AltMode, Key, KeyEncoder, KeyEventKind, KeyModifiers, KeyboardModes, encode_paste,
+    };
     use eframe::egui;
 
+    // egui doesn't tell keypad keys apart from the main ones, so keypad
+    // application mode never applies on this path.
+    // egui can't tell left and right Alt apart either, so the left Alt
+    // setting applies to both.
     pub(crate) fn append_input_from_event(
+        encoder: &KeyEncoder,
         event: &egui::Event,
         mods: egui::Modifiers,
+        modes: KeyboardModes,
         out: &mut Vec<u8>,
     ) {
         match event {
-            egui::Event::Text(text) => {
-                if !mods.ctrl {
+            // egui withholds text while Ctrl is held; those chords are
+            // encoded from their Key event below instead
+            egui::Event::Text(text) if !mods.ctrl => {
+                let mods = key_modifiers(mods);
+                let alt_as_meta = encoder
+                    .alt_mode(mods)
+                    .is_some_and(|mode| mode != AltMode::Compose);
+                if alt_as_meta || modes.reports_all_keys() {
+                    for c in text.chars() {
+                        encoder.encode(Key::Char(c), mods, modes, out);
+                    }
+                } else {
                     out.extend_from_slice(text.as_bytes());
                 }
             }
+            // egui turns Ctrl+C, Ctrl+X and Ctrl+V into clipboard commands
+            // without a Key event, Shift or not. Ctrl+Shift+C and Ctrl+Shift+V
+            // are the terminal's copy and paste (the copy is the caller's to
+            // make); without Shift a terminal owes the keys to the program.
+            egui::Event::Paste(text) if mods.shift => {
+                encode_paste(text, modes.bracketed_paste, out);
+            }
+            egui::Event::Copy if mods.shift => {}
+            egui::Event::Copy | egui::Event::Cut | egui::Event::Paste(_) => {
+                let c = match event {
+                    egui::Event::Copy => 'c',
+                    egui::Event::Cut => 'x',
+                    _ => 'v',
+                };
+                let mut mods = key_modifiers(mods);
+                mods.ctrl = true;
+                encoder.encode(Key::Char(c), mods, modes, out);
+            }
             egui::Event::Key {
                 key,
                 pressed,
+                repeat,
                 modifiers,
                 ..
-            } if *pressed => {
-                if *key == egui::Key::Escape {
-                    out.push(0x1b);
-                } else if modifiers.ctrl {
-                    if let Some(byte) = ctrl_key_byte(*key) {
-                        out.push(byte);
-                    }
-                } else {
-                    let _ = push_key_bytes(*key, out);
+            } => {
+                let kind = match (*pressed, *repeat) {
+                    (false, _) => KeyEventKind::Release,
+                    (true, true) => KeyEventKind::Repeat,
+                    (true, false) => KeyEventKind::Press,
+                };
+                if let Some(key) = terminal_key(*key, *modifiers, kind) {
+                    encoder.encode_event(key, key_modifiers(*modifiers), kind, modes, out);
                 }
             }
             _ => {}
         }
     }
 
-    fn ctrl_key_byte(key: egui::Key) -> Option<u8> {
-        let name = key.name();
-        let bytes = name.as_bytes();
-        if bytes.len() == 1 {
-            let b = bytes[0];
-            if b.is_ascii_uppercase() {
-                return Some(b - b'A' + 1);
-            }
+    fn key_modifiers(modifiers: egui::Modifiers) -> KeyModifiers {
+        KeyModifiers {
+            shift: modifiers.shift,
+            alt: modifiers.alt,
+            ctrl: modifiers.ctrl,
+            right_alt: false,
         }
-        None
     }
 
-    fn push_key_bytes(key: egui::Key, out: &mut Vec<u8>) -> bool {
-        match key {
-            egui::Key::Enter => out.push(b'\r'),
-            egui::Key::Backspace => out.push(0x7f),
-            egui::Key::Tab => out.push(b'\t'),
-            egui::Key::ArrowUp => out.extend_from_slice(b"\x1b[A"),
-            egui::Key::ArrowDown => out.extend_from_slice(b"\x1b[B"),
-            egui::Key::ArrowRight => out.extend_from_slice(b"\x1b[C"),
-            egui::Key::ArrowLeft => out.extend_from_slice(b"\x1b[D"),
+    // Printable keys arrive as Text events, so they only map to a Key here
+    // when Ctrl suppresses that text, or on release, which has no text.
+    fn terminal_key(key: egui::Key, modifiers: egui::Modifiers, kind: KeyEventKind) -> Option<Key> {
+        let key = match key {
+            egui::Key::Enter => Key::Enter,
+            egui::Key::Tab => Key::Tab,
+            egui::Key::Backspace => Key::Backspace,
+            egui::Key::Escape => Key::Escape,
+            egui::Key::ArrowUp => Key::Up,
+            egui::Key::ArrowDown => Key::Down,
+            egui::Key::ArrowRight => Key::Right,
+            egui::Key::ArrowLeft => Key::Left,
+            egui::Key::Home => Key::Home,
+            egui::Key::End => Key::End,
+            egui::Key::PageUp => Key::PageUp,
+            egui::Key::PageDown => Key::PageDown,
+            egui::Key::Insert => Key::Insert,
+            egui::Key::Delete => Key::Delete,
             _ => {
                 let name = key.name();
-                let Some(rest) = name.strip_prefix('F') else {
-                    return false;
-                };
-                let Ok(n) = rest.parse::<u8>() else {
-                    return false;
-                };
-                if !(1..=10).contains(&n) {
-                    return false;
+                if let Some(n) = name.strip_prefix('F').and_then(|n| n.parse::<u8>().ok()) {
+                    return (1..=20).contains(&n).then_some(Key::F(n));
                 }
-                if n <= 4 {
-                    out.extend_from_slice(&[0x1b, b'O', b'P' + (n - 1)]);
-                } else {
-                    let code = [15u8, 17, 18, 19, 20, 21][(n - 5) as usize];
-                    out.extend_from_slice(b"\x1b[");
-                    out.push(b'0' + (code / 10));
-                    out.push(b'0' + (code % 10));
-                    out.push(b'~');
+                if !modifiers.ctrl && kind != KeyEventKind::Release {
+                    return None;
                 }
+                return printable_char(key, modifiers.shift).map(Key::Char);
             }
-        }
-        true
+        };
+        Some(key)
+    }
+
+    // The character a printable key stands for, with Shift applied to the
+    // keys whose shifted character has its own control code (US layout)
+    fn printable_char(key: egui::Key, shift: bool) -> Option<char> {
+        let c = match key {
+            egui::Key::Space => ' ',
+            egui::Key::Num2 if shift => '@',
+            egui::Key::Num6 if shift => '^',
+            egui::Key::Minus if shift => '_',
+            egui::Key::Minus => '-',
+            egui::Key::OpenBracket => '[',
+            egui::Key::CloseBracket => ']',
+            egui::Key::OpenCurlyBracket => '{',
+            egui::Key::CloseCurlyBracket => '}',
+            egui::Key::Backslash => '\\',
+            egui::Key::Pipe => '|',
+            egui::Key::Slash => '/',
+            egui::Key::Questionmark => '?',
+            egui::Key::Backtick => '`',
+            _ => {
+                // Letters and digits are named by their single character
+                let mut chars = key.name().chars();
+                let c = chars.next()?;
+                if chars.next().is_some() || !c.is_ascii_alphanumeric() {
+                    return None;
+                }
+                c
+            }
+        };
+        Some(c)
     }
 }
 
 #[cfg(target_os = "macos")]
 pub mod macos_keymap {
+    use super::{
+        AltMode, Key, KeyEncoder, KeyEventKind, KeyModifiers, KeyboardModes, KeypadKey, ctrl_byte,
+    };
     use objc2::rc::autoreleasepool;
     use objc2_app_kit::{NSEvent, NSEventModifierFlags, NSEventType};
 
-    pub(crate) fn append_input_from_nsevent(event: &NSEvent, out: &mut Vec<u8>) {
-        if event.r#type() != NSEventType::KeyDown {
-            return;
-        }
+    // Device-dependent bits of the modifier flags (NX_DEVICELALTKEYMASK and
+    // NX_DEVICERALTKEYMASK in IOKit's IOLLEvent.h)
+    const LEFT_OPTION_MASK: usize = 0x20;
+    const RIGHT_OPTION_MASK: usize = 0x40;
 
-        let modifiers = event.modifierFlags();
-        let key_code = event.keyCode();
+    pub(crate) fn append_input_from_nsevent(
+        encoder: &KeyEncoder,
+        event: &NSEvent,
+        modes: KeyboardModes,
+        out: &mut Vec<u8>,
+    ) {
+        let kind = match event.r#type() {
+            NSEventType::KeyDown if event.isARepeat() => KeyEventKind::Repeat,
+            NSEventType::KeyDown => KeyEventKind::Press,
+            NSEventType::KeyUp => KeyEventKind::Release,
+            _ => return,
+        };
 
-        // Handle special keys first
-        let handled = match key_code {
-            53 => {
-                // Escape
-                out.push(0x1b);
-                true
-            }
-            36 => {
-                // Enter
-                out.push(b'\r');
-                true
-            }
-            51 => {
-                // Backspace
-                out.push(0x7f);
-                true
-            }
-            48 => {
-                // Tab
-                out.push(b'\t');
-                true
-            }
-            126 => {
-                // ArrowUp
-                out.extend_from_slice(b"\x1b[A");
-                true
-            }
-            125 => {
-                // ArrowDown
-                out.extend_from_slice(b"\x1b[B");
-                true
-            }
-            124 => {
-                // ArrowRight
-                out.extend_from_slice(b"\x1b[C");
-                true
-            }
-            123 => {
-                // ArrowLeft
-                out.extend_from_slice(b"\x1b[D");
-                true
-            }
-            _ => false,
+        let modifiers = event.modifierFlags();
+        let raw_flags = modifiers.bits();
+        let mods = KeyModifiers {
+            shift: modifiers.contains(NSEventModifierFlags::Shift),
+            alt: modifiers.contains(NSEventModifierFlags::Option),
+            ctrl: modifiers.contains(NSEventModifierFlags::Control),
+            right_alt: raw_flags & RIGHT_OPTION_MASK != 0 && raw_flags & LEFT_OPTION_MASK == 0,
         };
 
-        if handled {
+        // Handle special keys first
+        if let Some(key) = special_key(event.keyCode())
+            && encoder.encode_event(key, mods, kind, modes, out)
+        {
             return;
         }
 
-        let chars = event.characters().unwrap();
+        // Option as meta and Ctrl chords need the plain key, not the
+        // character the layout composes for them
+        let alt_as_meta = encoder
+            .alt_mode(mods)
+            .is_some_and(|mode| mode != AltMode::Compose);
+        let chars = if alt_as_meta || mods.ctrl || kind == KeyEventKind::Release {
+            event.charactersIgnoringModifiers()
+        } else {
+            event.characters()
+        };
+        let Some(chars) = chars else {
+            return;
+        };
         let chars_str = autoreleasepool(|pool| unsafe { chars.to_str(pool).to_string() });
 
-        if modifiers.contains(NSEventModifierFlags::Control)
+        if kind == KeyEventKind::Release {
+            if let Some(first_char) = chars_str.chars().next() {
+                encoder.encode_event(Key::Char(first_char), mods, kind, modes, out);
+            }
+            return;
+        }
+
+        if mods.ctrl
             && let Some(first_char) = chars_str.chars().next()
-            && first_char.is_ascii_alphabetic()
+            && ctrl_byte(first_char).is_some()
         {
-            out.push(first_char.to_ascii_uppercase() as u8 - b'A' + 1);
+            encoder.encode_event(Key::Char(first_char), mods, kind, modes, out);
             return;
         }
 
-        if !chars_str.is_empty() {
+        if alt_as_meta || modes.reports_all_keys() {
+            for c in chars_str.chars() {
+                encoder.encode_event(Key::Char(c), mods, kind, modes, out);
+            }
+        } else if !chars_str.is_empty() {
             out.extend_from_slice(chars_str.as_bytes());
         }
     }
+
+    // Virtual key codes from HIToolbox/Events.h
+    fn special_key(key_code: u16) -> Option<Key> {
+        let key = match key_code {
+            53 => Key::Escape,
+            36 => Key::Enter,
+            51 => Key::Backspace,
+            48 => Key::Tab,
+            126 => Key::Up,
+            125 => Key::Down,
+            124 => Key::Right,
+            123 => Key::Left,
+            115 => Key::Home,
+            119 => Key::End,
+            116 => Key::PageUp,
+            121 => Key::PageDown,
+            114 => Key::Insert,
+            117 => Key::Delete,
+            122 => Key::F(1),
+            120 => Key::F(2),
+            99 => Key::F(3),
+            118 => Key::F(4),
+            96 => Key::F(5),
+            97 => Key::F(6),
+            98 => Key::F(7),
+            100 => Key::F(8),
+            101 => Key::F(9),
+            109 => Key::F(10),
+            103 => Key::F(11),
+            111 => Key::F(12),
+            105 => Key::F(13),
+            107 => Key::F(14),
+            113 => Key::F(15),
+            106 => Key::F(16),
+            64 => Key::F(17),
+            79 => Key::F(18),
+            80 => Key::F(19),
+            90 => Key::F(20),
+            82 => Key::Keypad(KeypadKey::Digit(0)),
+            83 => Key::Keypad(KeypadKey::Digit(1)),
+            84 => Key::Keypad(KeypadKey::Digit(2)),
+            85 => Key::Keypad(KeypadKey::Digit(3)),
+            86 => Key::Keypad(KeypadKey::Digit(4)),
+            87 => Key::Keypad(KeypadKey::Digit(5)),
+            88 => Key::Keypad(KeypadKey::Digit(6)),
+            89 => Key::Keypad(KeypadKey::Digit(7)),
+            91 => Key::Keypad(KeypadKey::Digit(8)),
+            92 => Key::Keypad(KeypadKey::Digit(9)),
+            65 => Key::Keypad(KeypadKey::Decimal),
+            75 => Key::Keypad(KeypadKey::Divide),
+            67 => Key::Keypad(KeypadKey::Multiply),
+            78 => Key::Keypad(KeypadKey::Subtract),
+            69 => Key::Keypad(KeypadKey::Add),
+            76 => Key::Keypad(KeypadKey::Enter),
+            81 => Key::Keypad(KeypadKey::Equal),
+            _ => return None,
+        };
+        Some(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NONE: KeyModifiers = KeyModifiers {
+        shift: false,
+        alt: false,
+        ctrl: false,
+        right_alt: false,
+    };
+    const SHIFT: KeyModifiers = KeyModifiers {
+        shift: true,
+        ..NONE
+    };
+    const ALT: KeyModifiers = KeyModifiers { alt: true, ..NONE };
+    const CTRL: KeyModifiers = KeyModifiers { ctrl: true, ..NONE };
+    const CTRL_ALT: KeyModifiers = KeyModifiers {
+        alt: true,
+        ctrl: true,
+        ..NONE
+    };
+    const CTRL_SHIFT: KeyModifiers = KeyModifiers {
+        shift: true,
+        ctrl: true,
+        ..NONE
+    };
+
+    const APP_CURSOR: KeyboardModes = KeyboardModes {
+        application_cursor: true,
+        application_keypad: false,
+        kitty_flags: 0,
+        modify_other_keys: 0,
+        bracketed_paste: false,
+    };
+    const APP_KEYPAD: KeyboardModes = KeyboardModes {
+        application_cursor: false,
+        application_keypad: true,
+        kitty_flags: 0,
+        modify_other_keys: 0,
+        bracketed_paste: false,
+    };
+
+    const fn kitty(flags: u8) -> KeyboardModes {
+        KeyboardModes {
+            application_cursor: false,
+            application_keypad: false,
+            kitty_flags: flags,
+            modify_other_keys: 0,
+            bracketed_paste: false,
+        }
+    }
+
+    const fn modify_other_keys(level: u8) -> KeyboardModes {
+        KeyboardModes {
+            application_cursor: false,
+            application_keypad: false,
+            kitty_flags: 0,
+            modify_other_keys: level,
+            bracketed_paste: false,
+        }
+    }
+
+    fn encode_with(
+        encoder: KeyEncoder,
+        key: Key,
+        mods: KeyModifiers,
+        modes: KeyboardModes,
+    ) -> Vec<u8> {
+        let mut out = Vec::new();
+        assert!(
+            encoder.encode(key, mods, modes, &mut out),
+            "{key:?} has no encoding"
+        );
+        out
+    }
+
+    fn encode_in(key: Key, mods: KeyModifiers, modes: KeyboardModes) -> Vec<u8> {
+        encode_with(KeyEncoder::default(), key, mods, modes)
+    }
+
+    fn encode(key: Key, mods: KeyModifiers) -> Vec<u8> {
+        encode_in(key, mods, KeyboardModes::default())
+    }
+
+    #[test]
+    fn keys_encode_like_xterm() {
+        let cases: &[(Key, KeyModifiers, &[u8])] = &[
+            (Key::Enter, NONE, b"\r"),
+            (Key::Tab, NONE, b"\t"),
+            (Key::Tab, SHIFT, b"\x1b[Z"),
+            (Key::Backspace, NONE, b"\x7f"),
+            (Key::Backspace, CTRL, b"\x08"),
+            (Key::Escape, NONE, b"\x1b"),
+            (Key::Up, NONE, b"\x1b[A"),
+            (Key::Down, NONE, b"\x1b[B"),
+            (Key::Right, NONE, b"\x1b[C"),
+            (Key::Left, NONE, b"\x1b[D"),
+            (Key::Home, NONE, b"\x1b[H"),
+            (Key::End, NONE, b"\x1b[F"),
+            (Key::Insert, NONE, b"\x1b[2~"),
+            (Key::Delete, NONE, b"\x1b[3~"),
+            (Key::PageUp, NONE, b"\x1b[5~"),
+            (Key::PageDown, NONE, b"\x1b[6~"),
+            (Key::F(1), NONE, b"\x1bOP"),
+            (Key::F(4), NONE, b"\x1bOS"),
+            (Key::F(5), NONE, b"\x1b[15~"),
+            (Key::F(10), NONE, b"\x1b[21~"),
+            (Key::F(11), NONE, b"\x1b[23~"),
+            (Key::F(12), NONE, b"\x1b[24~"),
+            (Key::F(13), NONE, b"\x1b[25~"),
+            (Key::F(15), NONE, b"\x1b[28~"),
+            (Key::F(17), NONE, b"\x1b[31~"),
+            (Key::F(20), NONE, b"\x1b[34~"),
+            (Key::Char('a'), CTRL, b"\x01"),
+            (Key::Char('Z'), CTRL, b"\x1a"),
+            (Key::Char('x'), NONE, b"x"),
+        ];
+        for &(key, mods, expected) in cases {
+            assert_eq!(encode(key, mods), expected, "{key:?} with {mods:?}");
+        }
+    }
+
+    #[test]
+    fn modified_keys_use_the_csi_1_mod_form() {
+        let cases: &[(Key, KeyModifiers, &[u8])] = &[
+            (Key::Up, CTRL, b"\x1b[1;5A"),
+            (Key::Left, ALT, b"\x1b[1;3D"),
+            (Key::Right, CTRL_SHIFT, b"\x1b[1;6C"),
+            (Key::Home, SHIFT, b"\x1b[1;2H"),
+            (Key::End, CTRL, b"\x1b[1;5F"),
+            (Key::F(1), SHIFT, b"\x1b[1;2P"),
+            (Key::F(5), SHIFT, b"\x1b[15;2~"),
+            (Key::F(12), CTRL, b"\x1b[24;5~"),
+            (Key::Delete, CTRL, b"\x1b[3;5~"),
+            (Key::PageUp, ALT, b"\x1b[5;3~"),
+        ];
+        for &(key, mods, expected) in cases {
+            assert_eq!(encode(key, mods), expected, "{key:?} with {mods:?}");
+        }
+    }
+
+    #[test]
+    fn application_cursor_mode_switches_unmodified_cursor_keys_to_ss3() {
+        let cases: &[(Key, KeyModifiers, &[u8])] = &[
+            (Key::Up, NONE, b"\x1bOA"),
+            (Key::Down, NONE, b"\x1bOB"),
+            (Key::Right, NONE, b"\x1bOC"),
+            (Key::Left, NONE, b"\x1bOD"),
+            (Key::Home, NONE, b"\x1bOH"),
+            (Key::End, NONE, b"\x1bOF"),
+            (Key::Up, CTRL, b"\x1b[1;5A"),
+            (Key::PageUp, NONE, b"\x1b[5~"),
+        ];
+        for &(key, mods, expected) in cases {
+            assert_eq!(
+                encode_in(key, mods, APP_CURSOR),
+                expected,
+                "{key:?} with {mods:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn keypad_keys_follow_the_keypad_mode() {
+        let cases: &[(KeypadKey, &[u8], &[u8])] = &[
+            (KeypadKey::Digit(0), b"0", b"\x1bOp"),
+            (KeypadKey::Digit(9), b"9", b"\x1bOy"),
+            (KeypadKey::Decimal, b".", b"\x1bOn"),
+            (KeypadKey::Divide, b"/", b"\x1bOo"),
+            (KeypadKey::Multiply, b"*", b"\x1bOj"),
+            (KeypadKey::Subtract, b"-", b"\x1bOm"),
+            (KeypadKey::Add, b"+", b"\x1bOk"),
+            (KeypadKey::Enter, b"\r", b"\x1bOM"),
+            (KeypadKey::Equal, b"=", b"\x1bOX"),
+        ];
+        for &(key, normal, application) in cases {
+            assert_eq!(encode(Key::Keypad(key), NONE), normal, "{key:?}");
+            assert_eq!(
+                encode_in(Key::Keypad(key), NONE, APP_KEYPAD),
+                application,
+                "{key:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn ctrl_maps_to_the_full_c0_range() {
+        let cases: &[(char, u8)] = &[
+            (' ', 0x00),
+            ('@', 0x00),
+            ('2', 0x00),
+            ('a', 0x01),
+            ('A', 0x01),
+            ('z', 0x1a),
+            ('[', 0x1b),
+            ('3', 0x1b),
+            ('{', 0x1b),
+            ('\\', 0x1c),
+            ('4', 0x1c),
+            ('|', 0x1c),
+            (']', 0x1d),
+            ('5', 0x1d),
+            ('}', 0x1d),
+            ('^', 0x1e),
+            ('6', 0x1e),
+            ('~', 0x1e),
+            ('_', 0x1f),
+            ('7', 0x1f),
+            ('/', 0x1f),
+            ('8', 0x7f),
+            ('?', 0x7f),
+            ('`', 0x00),
+        ];
+        for &(c, byte) in cases {
+            assert_eq!(ctrl_byte(c), Some(byte), "Ctrl+{c}");
+            assert_eq!(encode(Key::Char(c), CTRL), [byte], "Ctrl+{c}");
+        }
+    }
+
+    #[test]
+    fn ctrl_without_a_control_code_sends_the_key_itself() {
+        for c in ['1', '9', '0', '-', '.', 'é'] {
+            assert_eq!(ctrl_byte(c), None, "Ctrl+{c}");
+            assert_eq!(encode(Key::Char(c), CTRL), c.to_string().as_bytes());
+        }
+    }
+
+    fn encode_kitty(key: Key, mods: KeyModifiers, kind: KeyEventKind, flags: u8) -> Vec<u8> {
+        let mut out = Vec::new();
+        KeyEncoder::default().encode_event(key, mods, kind, kitty(flags), &mut out);
+        out
+    }
+
+    #[test]
+    fn kitty_disambiguation_only_changes_ambiguous_keys() {
+        let cases: &[(Key, KeyModifiers, &[u8])] = &[
+            (Key::Char('i'), CTRL, b"\x1b[105;5u"),
+            (Key::Char('I'), CTRL_SHIFT, b"\x1b[105;6u"),
+            (Key::Char('['), CTRL, b"\x1b[91;5u"),
+            (Key::Char('x'), ALT, b"\x1b[120;3u"),
+            (Key::Escape, NONE, b"\x1b[27u"),
+            (Key::Keypad(KeypadKey::Digit(1)), NONE, b"\x1b[57400u"),
+            (Key::F(13), NONE, b"\x1b[57376u"),
+            // Unambiguous keys keep their legacy encoding
+            (Key::Tab, NONE, b"\t"),
+            (Key::Enter, CTRL, b"\r"),
+            (Key::Char('a'), NONE, b"a"),
+            (Key::Char('A'), SHIFT, b"A"),
+            (Key::Up, CTRL, b"\x1b[1;5A"),
+        ];
+        for &(key, mods, expected) in cases {
+            assert_eq!(
+                encode_kitty(key, mods, KeyEventKind::Press, KITTY_DISAMBIGUATE),
+                expected,
+                "{key:?} with {mods:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn kitty_event_types_are_reported_when_requested() {
+        let flags = KITTY_DISAMBIGUATE | KITTY_REPORT_EVENTS;
+        let release = KeyEventKind::Release;
+        assert_eq!(
+            encode_kitty(Key::Char('a'), NONE, release, flags),
+            b"\x1b[97;1:3u"
+        );
+        assert_eq!(
+            encode_kitty(Key::Up, NONE, KeyEventKind::Repeat, flags),
+            b"\x1b[1;1:2A"
+        );
+        assert_eq!(
+            encode_kitty(Key::Left, SHIFT, release, flags),
+            b"\x1b[1;2:3D"
+        );
+        // Enter, Tab and Backspace releases need the report all keys flag
+        assert_eq!(encode_kitty(Key::Enter, NONE, release, flags), b"");
+        // Without the flag, repeats are presses and releases send nothing
+        assert_eq!(
+            encode_kitty(Key::Up, NONE, KeyEventKind::Repeat, KITTY_DISAMBIGUATE),
+            b"\x1b[A"
+        );
+        assert_eq!(encode_kitty(Key::Char('a'), NONE, release, 0), b"");
+    }
+
+    #[test]
+    fn kitty_report_all_keys_encodes_text_keys() {
+        let all = KITTY_REPORT_ALL_KEYS;
+        let press = KeyEventKind::Press;
+        assert_eq!(encode_kitty(Key::Char('a'), NONE, press, all), b"\x1b[97u");
+        assert_eq!(encode_kitty(Key::Enter, NONE, press, all), b"\x1b[13u");
+        assert_eq!(encode_kitty(Key::Tab, SHIFT, press, all), b"\x1b[9;2u");
+        assert_eq!(encode_kitty(Key::Backspace, NONE, press, all), b"\x1b[127u");
+        assert_eq!(encode_kitty(Key::Up, NONE, press, all), b"\x1b[A");
+        assert_eq!(encode_kitty(Key::F(3), NONE, press, all), b"\x1b[13~");
+        assert_eq!(
+            encode_kitty(Key::Char('A'), SHIFT, press, all | KITTY_REPORT_ALTERNATES),
+            b"\x1b[97:65;2u"
+        );
+        assert_eq!(
+            encode_kitty(Key::Char('a'), NONE, press, all | KITTY_REPORT_TEXT),
+            b"\x1b[97;;97u"
+        );
+        assert_eq!(
+            encode_kitty(Key::Char('A'), SHIFT, press, all | KITTY_REPORT_TEXT),
+            b"\x1b[97;2;65u"
+        );
+    }
+
+    #[test]
+    fn modify_other_keys_level_1_covers_ambiguous_chords() {
+        let cases: &[(Key, KeyModifiers, &[u8])] = &[
+            (Key::Char('A'), CTRL_SHIFT, b"\x1b[27;6;65~"),
+            (Key::Char('1'), CTRL, b"\x1b[27;5;49~"),
+            (Key::Char(','), CTRL, b"\x1b[27;5;44~"),
+            (Key::Enter, CTRL, b"\x1b[27;5;13~"),
+            (Key::Tab, CTRL, b"\x1b[27;5;9~"),
+            // Chords with a control code of their own keep it
+            (Key::Char('a'), CTRL, b"\x01"),
+            (Key::Char('['), CTRL, b"\x1b"),
+            (Key::Char('x'), ALT, b"\x1bx"),
+            (Key::Tab, SHIFT, b"\x1b[Z"),
+            (Key::Up, CTRL, b"\x1b[1;5A"),
+        ];
+        for &(key, mods, expected) in cases {
+            assert_eq!(
+                encode_in(key, mods, modify_other_keys(1)),
+                expected,
+                "{key:?} with {mods:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn modify_other_keys_level_2_covers_all_modified_keys() {
+        let cases: &[(Key, KeyModifiers, &[u8])] = &[
+            (Key::Char('A'), CTRL, b"\x1b[27;5;97~"),
+            (Key::Char('a'), CTRL_SHIFT, b"\x1b[27;6;65~"),
+            (Key::Char('x'), ALT, b"\x1b[27;3;120~"),
+            (Key::Char('x'), CTRL_ALT, b"\x1b[27;7;120~"),
+            (Key::Tab, SHIFT, b"\x1b[27;2;9~"),
+            (Key::Backspace, CTRL, b"\x1b[27;5;127~"),
+            (Key::Escape, ALT, b"\x1b[27;3;27~"),
+            // Plain and shifted text is still just text
+            (Key::Char('a'), NONE, b"a"),
+            (Key::Char('A'), SHIFT, b"A"),
+            (Key::Enter, NONE, b"\r"),
+            (Key::PageUp, CTRL, b"\x1b[5;5~"),
+        ];
+        for &(key, mods, expected) in cases {
+            assert_eq!(
+                encode_in(key, mods, modify_other_keys(2)),
+                expected,
+                "{key:?} with {mods:?}"
+            );
+        }
+    }
+
+    fn paste(text: &str, bracketed: bool) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode_paste(text, bracketed, &mut out);
+        out
+    }
+
+    #[test]
+    fn bracketed_paste_wraps_the_text() {
+        assert_eq!(
+            paste("echo hi\nls\n", true),
+            b"\x1b[200~echo hi\nls\n\x1b[201~"
+        );
+    }
+
+    #[test]
+    fn plain_paste_sends_newlines_as_carriage_returns() {
+        assert_eq!(paste("a\r\nb\nc\td", false), b"a\rb\rc\td");
+    }
+
+    #[test]
+    fn paste_drops_escape_sequences_and_controls() {
+        assert_eq!(
+            paste("ok\x1b[201~; rm -rf ~\x1b]0;x\x07\x03 é", true),
+            "\x1b[200~ok; rm -rf ~ é\x1b[201~".as_bytes()
+        );
+        assert_eq!(paste("a\u{9b}201~b", false), b"a201~b");
+    }
+
+    #[test]
+    fn alt_sends_an_escape_prefix_by_default() {
+        let cases: &[(Key, KeyModifiers, &[u8])] = &[
+            (Key::Char('b'), ALT, b"\x1bb"),
+            (Key::Char('.'), ALT, b"\x1b."),
+            (Key::Char('B'), ALT, b"\x1bB"),
+            (Key::Char('x'), CTRL_ALT, b"\x1b\x18"),
+            (Key::Backspace, ALT, b"\x1b\x7f"),
+            (Key::Enter, ALT, b"\x1b\r"),
+            // Keys with a CSI form keep carrying Alt in the modifier parameter
+            (Key::Left, ALT, b"\x1b[1;3D"),
+        ];
+        for &(key, mods, expected) in cases {
+            assert_eq!(encode(key, mods), expected, "{key:?} with {mods:?}");
+        }
+    }
+
+    #[test]
+    fn eight_bit_meta_sets_the_high_bit() {
+        let encoder = KeyEncoder {
+            left_alt: AltMode::EightBit,
+            ..KeyEncoder::default()
+        };
+        let modes = KeyboardModes::default();
+        assert_eq!(
+            encode_with(encoder, Key::Char('b'), ALT, modes),
+            "\u{e2}".as_bytes()
+        );
+        // Non-ASCII text can't carry the meta bit and is sent unchanged
+        assert_eq!(
+            encode_with(encoder, Key::Char('é'), ALT, modes),
+            "é".as_bytes()
+        );
+    }
+
+    #[test]
+    fn alt_is_configured_per_side() {
+        let right_alt = KeyModifiers {
+            right_alt: true,
+            ..ALT
+        };
+        let modes = KeyboardModes::default();
+        // By default right Alt is left to the layout (AltGr)
+        assert_eq!(encode_in(Key::Char('@'), right_alt, modes), b"@");
+
+        let encoder = KeyEncoder {
+            left_alt: AltMode::Compose,
+            right_alt: AltMode::Escape,
+        };
+        assert_eq!(
+            encode_with(encoder, Key::Char('f'), right_alt, modes),
+            b"\x1bf"
+        );
+        assert_eq!(encode_with(encoder, Key::Char('f'), ALT, modes), b"f");
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn clipboard_chords_need_shift() {
+        use eframe::egui;
+
+        let translate = |event: egui::Event, shift: bool| {
+            let mods = egui::Modifiers {
+                shift,
+                ..egui::Modifiers::CTRL
+            };
+            let mut out = Vec::new();
+            egui_keymap::append_input_from_event(
+                &KeyEncoder::default(),
+                &event,
+                mods,
+                KeyboardModes::default(),
+                &mut out,
+            );
+            out
+        };
+        let paste = || egui::Event::Paste("ls".to_string());
+        assert_eq!(translate(egui::Event::Copy, false), b"\x03");
+        assert_eq!(translate(egui::Event::Copy, true), b"");
+        assert_eq!(translate(paste(), false), b"\x16");
+        assert_eq!(translate(paste(), true), b"ls");
+        assert_eq!(translate(egui::Event::Cut, false), b"\x18");
+    }
+
+    #[test]
+    fn function_keys_past_f20_are_not_encoded() {
+        let encoder = KeyEncoder::default();
+        let modes = KeyboardModes::default();
+        assert!(!encoder.encode(Key::F(21), NONE, modes, &mut Vec::new()));
+        assert!(!encoder.encode(Key::F(0), NONE, modes, &mut Vec::new()));
+    }
 }
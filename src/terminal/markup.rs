@@ -0,0 +1,394 @@
+// The JSON, YAML and XML property lists color schemes come in, read with
+// their crates into one tree of scalars, lists and maps, so reading a scheme
+// doesn't depend on the format its file is in.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::Deserialize;
+use yaml_rust2::Event;
+use yaml_rust2::parser::{MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::Marker;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Node {
+    /// Strings, numbers and booleans alike, as text
+    Scalar(String),
+    List(Vec<Node>),
+    /// Entries in the order they were written
+    Map(Vec<(String, Node)>),
+}
+
+impl Node {
+    /// The value under `key` of a map
+    pub(crate) fn get(&self, key: &str) -> Option<&Node> {
+        match self {
+            Node::Map(entries) => entries
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, node)| node),
+            _ => None,
+        }
+    }
+
+    /// The value at the end of a path of map keys
+    pub(crate) fn at(&self, path: &[&str]) -> Option<&Node> {
+        path.iter().try_fold(self, |node, key| node.get(key))
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Node::Scalar(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_list(&self) -> Option<&[Node]> {
+        match self {
+            Node::List(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SyntaxError {
+    /// Counted from 1, when the parser tells
+    pub(crate) line: Option<usize>,
+    pub(crate) message: String,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {line}: {}", self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+pub(crate) fn line_at(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].matches('\n').count() + 1
+}
+
+/// Parses JSON, with the comments and trailing commas Windows Terminal
+/// allows in its settings
+pub(crate) fn parse_json(text: &str) -> Result<Node, SyntaxError> {
+    let mut deserializer = serde_json_lenient::Deserializer::from_str(text);
+    deserializer.set_allow_comments(true);
+    deserializer.set_ignore_trailing_commas(true);
+    let value = serde_json_lenient::Value::deserialize(&mut deserializer)
+        .and_then(|value| deserializer.end().map(|()| value))
+        .map_err(|error| {
+            // The message ends with the position, which is given apart
+            let message = error.to_string();
+            let message = match message.rfind(" at line ") {
+                Some(end) => message[..end].to_string(),
+                None => message,
+            };
+            SyntaxError {
+                line: Some(error.line()),
+                message,
+            }
+        })?;
+    Ok(json_node(value))
+}
+
+fn json_node(value: serde_json_lenient::Value) -> Node {
+    use serde_json_lenient::Value;
+    match value {
+        Value::Null => Node::Scalar(String::new()),
+        Value::Bool(flag) => Node::Scalar(flag.to_string()),
+        Value::Number(number) => Node::Scalar(number.to_string()),
+        Value::String(text) => Node::Scalar(text),
+        Value::Array(items) => Node::List(items.into_iter().map(json_node).collect()),
+        Value::Object(entries) => Node::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key, json_node(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Parses the first document of a YAML file. Scalars keep the text they were
+/// written with, so a color like `000000` isn't read as the number 0. Aliases
+/// stand for a copy of what they refer to, and `<<` merge keys are applied.
+pub(crate) fn parse_yaml(text: &str) -> Result<Node, SyntaxError> {
+    let mut builder = YamlBuilder::default();
+    Parser::new_from_str(text)
+        .load(&mut builder, false)
+        .map_err(|error| SyntaxError {
+            line: Some(error.marker().line()),
+            message: error.info().to_string(),
+        })?;
+    match builder.error {
+        Some(error) => Err(error),
+        None => Ok(builder.root.unwrap_or(Node::Map(Vec::new()))),
+    }
+}
+
+// A collection whose end the parser hasn't reached yet, with its anchor
+enum OpenYaml {
+    List(Vec<Node>, usize),
+    // Entries so far, and the key waiting for its value
+    Map(Vec<(String, Node)>, Option<String>, usize),
+}
+
+#[derive(Default)]
+struct YamlBuilder {
+    open: Vec<OpenYaml>,
+    anchors: HashMap<usize, Node>,
+    root: Option<Node>,
+    error: Option<SyntaxError>,
+}
+
+impl MarkedEventReceiver for YamlBuilder {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        if self.error.is_some() {
+            return;
+        }
+        let result = match event {
+            Event::Scalar(text, _, anchor, _) => self.add(Node::Scalar(text), anchor),
+            Event::Alias(anchor) => match self.anchors.get(&anchor) {
+                Some(node) => self.add(node.clone(), 0),
+                None => Err("an alias refers to the node it's in".to_string()),
+            },
+            Event::SequenceStart(anchor, _) => {
+                self.open.push(OpenYaml::List(Vec::new(), anchor));
+                Ok(())
+            }
+            Event::MappingStart(anchor, _) => {
+                self.open.push(OpenYaml::Map(Vec::new(), None, anchor));
+                Ok(())
+            }
+            Event::SequenceEnd | Event::MappingEnd => match self.open.pop() {
+                Some(OpenYaml::List(items, anchor)) => self.add(Node::List(items), anchor),
+                Some(OpenYaml::Map(entries, _, anchor)) => self.add(Node::Map(entries), anchor),
+                None => Ok(()),
+            },
+            _ => Ok(()),
+        };
+        if let Err(message) = result {
+            self.error = Some(SyntaxError {
+                line: Some(mark.line()),
+                message,
+            });
+        }
+    }
+}
+
+impl YamlBuilder {
+    // Puts a finished node where it belongs: in the open list or map, or at
+    // the root
+    fn add(&mut self, node: Node, anchor: usize) -> Result<(), String> {
+        if anchor > 0 {
+            self.anchors.insert(anchor, node.clone());
+        }
+        match self.open.last_mut() {
+            None => {
+                self.root.get_or_insert(node);
+            }
+            Some(OpenYaml::List(items, _)) => items.push(node),
+            Some(OpenYaml::Map(entries, pending, _)) => match pending.take() {
+                None => match node {
+                    Node::Scalar(key) => *pending = Some(key),
+                    _ => return Err("keys must be scalars".to_string()),
+                },
+                Some(key) if key == "<<" => {
+                    let merged = match node {
+                        Node::List(maps) => maps,
+                        node => vec![node],
+                    };
+                    for map in merged {
+                        let Node::Map(merged_entries) = map else {
+                            return Err("`<<` takes a mapping or a list of them".to_string());
+                        };
+                        // Keys written in the mapping itself win
+                        for (key, value) in merged_entries {
+                            if !entries.iter().any(|(name, _)| *name == key) {
+                                entries.push((key, value));
+                            }
+                        }
+                    }
+                }
+                Some(key) => match entries.iter_mut().find(|(name, _)| *name == key) {
+                    Some((_, value)) => *value = node,
+                    None => entries.push((key, node)),
+                },
+            },
+        }
+        Ok(())
+    }
+}
+
+/// Parses an XML property list, as iTerm2 saves color presets in
+pub(crate) fn parse_plist(text: &str) -> Result<Node, SyntaxError> {
+    let value = plist::Value::from_reader_xml(text.as_bytes()).map_err(|error| SyntaxError {
+        line: None,
+        message: format!("not a property list: {error}"),
+    })?;
+    Ok(plist_node(value))
+}
+
+fn plist_node(value: plist::Value) -> Node {
+    use plist::Value;
+    match value {
+        Value::Array(items) => Node::List(items.into_iter().map(plist_node).collect()),
+        Value::Dictionary(entries) => Node::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key, plist_node(value)))
+                .collect(),
+        ),
+        Value::Boolean(flag) => Node::Scalar(flag.to_string()),
+        Value::Real(number) => Node::Scalar(number.to_string()),
+        Value::Integer(number) => Node::Scalar(number.to_string()),
+        Value::String(text) => Node::Scalar(text),
+        Value::Date(date) => Node::Scalar(date.to_xml_format()),
+        _ => Node::Scalar(String::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scalar(text: &str) -> Node {
+        Node::Scalar(text.to_string())
+    }
+
+    #[test]
+    fn json_with_comments_and_trailing_commas() {
+        let node = parse_json(
+            r##"{
+                // settings.json allows comments
+                "schemes": [
+                    { "name": "Tab\tby \"me\" \u00e9\ud83d\ude00", "red": "#C50F1F", },
+                ],
+                /* and block comments */
+                "size": 12.5, "on": true
+            }"##,
+        )
+        .unwrap();
+        let scheme = &node.get("schemes").unwrap().as_list().unwrap()[0];
+        assert_eq!(scheme.get("name"), Some(&scalar("Tab\tby \"me\" é😀")));
+        assert_eq!(scheme.get("red"), Some(&scalar("#C50F1F")));
+        assert_eq!(node.get("size"), Some(&scalar("12.5")));
+        assert_eq!(node.get("on"), Some(&scalar("true")));
+
+        let error = parse_json("{\n  \"a\": 1\n  \"b\": 2\n}").unwrap_err();
+        assert_eq!(error.to_string(), "line 3: expected `,` or `}`");
+        assert!(parse_json("[1, 2").is_err());
+        assert!(parse_json("{} x").is_err());
+    }
+
+    #[test]
+    fn yaml_mappings_lists_and_scalars() {
+        let node = parse_yaml(
+            "# Theme\n\
+             ---\n\
+             colors:\n\
+             \x20 primary:\n\
+             \x20   background: '0x1d1f21' # dark\n\
+             \x20   foreground: \"#c5c8c6\"\n\
+             \x20 selection: { text: CellBackground, background: '#373b41' }\n\
+             \x20 indexed_colors:\n\
+             \x20   - { index: 16, color: '0xde935f' }\n\
+             \x20   - index: 17\n\
+             \x20     color: '#a54242'\n\
+             name: \"It's #1\" # a comment\n\
+             tags: [dark, warm]\n",
+        )
+        .unwrap();
+        let colors = node.get("colors").unwrap();
+        assert_eq!(
+            colors.at(&["primary", "background"]),
+            Some(&scalar("0x1d1f21"))
+        );
+        assert_eq!(
+            colors.at(&["primary", "foreground"]),
+            Some(&scalar("#c5c8c6"))
+        );
+        assert_eq!(
+            colors.at(&["selection", "background"]),
+            Some(&scalar("#373b41"))
+        );
+        let indexed = colors.get("indexed_colors").unwrap().as_list().unwrap();
+        assert_eq!(indexed[0].get("color"), Some(&scalar("0xde935f")));
+        assert_eq!(indexed[1].get("index"), Some(&scalar("17")));
+        assert_eq!(indexed[1].get("color"), Some(&scalar("#a54242")));
+        assert_eq!(node.get("name"), Some(&scalar("It's #1")));
+        assert_eq!(
+            node.get("tags"),
+            Some(&Node::List(vec![scalar("dark"), scalar("warm")]))
+        );
+
+        let error = parse_yaml("a:\n  b: 1\n    c: 2\n").unwrap_err();
+        assert_eq!(error.line, Some(3));
+        assert_eq!(parse_yaml("a: 1\nnot a pair\n").unwrap_err().line, Some(3));
+        assert_eq!(parse_yaml("a: *nowhere\n").unwrap_err().line, Some(1));
+    }
+
+    #[test]
+    fn yaml_anchors_merge_keys_and_block_scalars() {
+        let node = parse_yaml(
+            "base: &base\n\
+             \x20 background: 000000\n\
+             \x20 foreground: &fg '#c5c8c6'\n\
+             primary:\n\
+             \x20 <<: *base\n\
+             \x20 background: 1d1f21\n\
+             cursor: *fg\n\
+             description: >\n\
+             \x20 Folded\n\
+             \x20 lines\n",
+        )
+        .unwrap();
+        assert_eq!(node.at(&["primary", "background"]), Some(&scalar("1d1f21")));
+        assert_eq!(
+            node.at(&["primary", "foreground"]),
+            Some(&scalar("#c5c8c6"))
+        );
+        assert_eq!(node.at(&["base", "background"]), Some(&scalar("000000")));
+        assert_eq!(node.get("cursor"), Some(&scalar("#c5c8c6")));
+        assert_eq!(node.get("description"), Some(&scalar("Folded lines\n")));
+    }
+
+    #[test]
+    fn plist_dicts_and_values() {
+        let node = parse_plist(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<!-- a comment -->
+	<key>Ansi 0 Color</key>
+	<dict>
+		<key>Red Component</key>
+		<real>0.5</real>
+		<key>Color Space</key>
+		<string>sRGB</string>
+	</dict>
+	<key>Name &amp; more</key>
+	<string>Solarized</string>
+	<key>Flags</key>
+	<array><true/><integer>3</integer></array>
+</dict>
+</plist>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            node.at(&["Ansi 0 Color", "Red Component"]),
+            Some(&scalar("0.5"))
+        );
+        assert_eq!(node.get("Name & more"), Some(&scalar("Solarized")));
+        assert_eq!(
+            node.get("Flags"),
+            Some(&Node::List(vec![scalar("true"), scalar("3")]))
+        );
+
+        let error = parse_plist("<plist>\n<dict>\n<string>x</string>\n</dict>").unwrap_err();
+        assert!(error.to_string().starts_with("not a property list"));
+    }
+}
@@ -1,4 +1,19 @@
+pub(crate) mod bindings;
+pub(crate) mod clipboard;
 pub(crate) mod color;
+pub(crate) mod cwd;
+pub(crate) mod detect;
 pub(crate) mod grid;
+pub(crate) mod hints;
+pub(crate) mod hyperlink;
 pub(crate) mod keymap;
+pub(crate) mod markup;
+pub(crate) mod mouse;
+pub(crate) mod prompt;
 pub(crate) mod pty;
+pub(crate) mod search;
+pub(crate) mod selection;
+pub(crate) mod sequences;
+pub(crate) mod shell;
+pub(crate) mod theme;
+pub(crate) mod vi_mode;
@@ -0,0 +1,262 @@
+// xterm mouse reporting. The tracking mode and encoding are whatever the
+// program last requested through DECSET 9/1000/1002/1003 and 1005/1006, which
+// vt100 already keeps on its screen.
+
+use vt100::{MouseProtocolEncoding, MouseProtocolMode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    WheelUp,
+    WheelDown,
+}
+
+impl MouseButton {
+    fn code(self) -> u32 {
+        match self {
+            Self::Left => 0,
+            Self::Middle => 1,
+            Self::Right => 2,
+            Self::WheelUp => 64,
+            Self::WheelDown => 65,
+        }
+    }
+
+    fn is_wheel(self) -> bool {
+        matches!(self, Self::WheelUp | Self::WheelDown)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MouseAction {
+    Press(MouseButton),
+    Release(MouseButton),
+    /// Pointer moved to another cell, with the button held down if any
+    Motion(Option<MouseButton>),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct MouseModifiers {
+    pub(crate) shift: bool,
+    pub(crate) alt: bool,
+    pub(crate) ctrl: bool,
+}
+
+impl MouseModifiers {
+    fn bits(self) -> u32 {
+        (self.shift as u32 * 4) | (self.alt as u32 * 8) | (self.ctrl as u32 * 16)
+    }
+}
+
+/// Encodes a mouse event at the zero-based `row`/`col` cell, or returns
+/// `None` when the current mode doesn't report it.
+pub(crate) fn encode_mouse_event(
+    mode: MouseProtocolMode,
+    encoding: MouseProtocolEncoding,
+    action: MouseAction,
+    mods: MouseModifiers,
+    row: usize,
+    col: usize,
+) -> Option<Vec<u8>> {
+    let reported = match (mode, action) {
+        (MouseProtocolMode::None, _) => false,
+        (MouseProtocolMode::Press, MouseAction::Press(_)) => true,
+        (MouseProtocolMode::Press, _) => false,
+        (_, MouseAction::Press(_)) => true,
+        (_, MouseAction::Release(button)) => !button.is_wheel(),
+        (MouseProtocolMode::ButtonMotion, MouseAction::Motion(held)) => held.is_some(),
+        (MouseProtocolMode::AnyMotion, MouseAction::Motion(_)) => true,
+        (MouseProtocolMode::PressRelease, MouseAction::Motion(_)) => false,
+    };
+    if !reported {
+        return None;
+    }
+
+    // X10 compatibility mode never reports modifiers
+    let mods = if mode == MouseProtocolMode::Press {
+        0
+    } else {
+        mods.bits()
+    };
+    let (button, released) = match action {
+        MouseAction::Press(button) => (button.code(), false),
+        MouseAction::Release(button) => (button.code(), true),
+        // Motion without a button uses the "release" button number
+        MouseAction::Motion(held) => (held.map_or(3, MouseButton::code) + 32, false),
+    };
+    let x = col as u32 + 1;
+    let y = row as u32 + 1;
+
+    if encoding == MouseProtocolEncoding::Sgr {
+        let suffix = if released { 'm' } else { 'M' };
+        return Some(format!("\x1b[<{};{x};{y}{suffix}", button | mods).into_bytes());
+    }
+
+    // The legacy encodings can't say which button was released
+    let button = if released { 3 } else { button } | mods;
+    let mut out = b"\x1b[M".to_vec();
+    for value in [button, x, y] {
+        let value = value + 32;
+        match encoding {
+            MouseProtocolEncoding::Utf8 => {
+                let ch = char::from_u32(value).filter(|_| value < 2048)?;
+                let mut buf = [0u8; 4];
+                out.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+            }
+            _ => out.push(u8::try_from(value).ok()?),
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NO_MODS: MouseModifiers = MouseModifiers {
+        shift: false,
+        alt: false,
+        ctrl: false,
+    };
+
+    fn encode(
+        mode: MouseProtocolMode,
+        encoding: MouseProtocolEncoding,
+        action: MouseAction,
+        row: usize,
+        col: usize,
+    ) -> Option<Vec<u8>> {
+        encode_mouse_event(mode, encoding, action, NO_MODS, row, col)
+    }
+
+    #[test]
+    fn nothing_is_reported_without_a_tracking_mode() {
+        let press = MouseAction::Press(MouseButton::Left);
+        assert_eq!(
+            encode(
+                MouseProtocolMode::None,
+                MouseProtocolEncoding::Sgr,
+                press,
+                0,
+                0
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn sgr_reports_press_and_release_with_the_button() {
+        let sgr = MouseProtocolEncoding::Sgr;
+        let mode = MouseProtocolMode::PressRelease;
+        assert_eq!(
+            encode(mode, sgr, MouseAction::Press(MouseButton::Right), 4, 9).unwrap(),
+            b"\x1b[<2;10;5M"
+        );
+        assert_eq!(
+            encode(mode, sgr, MouseAction::Release(MouseButton::Right), 4, 9).unwrap(),
+            b"\x1b[<2;10;5m"
+        );
+        assert_eq!(
+            encode(mode, sgr, MouseAction::Press(MouseButton::WheelDown), 0, 0).unwrap(),
+            b"\x1b[<65;1;1M"
+        );
+    }
+
+    #[test]
+    fn default_encoding_offsets_values_by_32() {
+        let default = MouseProtocolEncoding::Default;
+        let mode = MouseProtocolMode::PressRelease;
+        assert_eq!(
+            encode(mode, default, MouseAction::Press(MouseButton::Left), 0, 0).unwrap(),
+            b"\x1b[M\x20\x21\x21"
+        );
+        assert_eq!(
+            encode(mode, default, MouseAction::Release(MouseButton::Left), 1, 2).unwrap(),
+            b"\x1b[M\x23\x23\x22"
+        );
+        // Columns past 222 don't fit in a byte
+        assert_eq!(
+            encode(mode, default, MouseAction::Press(MouseButton::Left), 0, 300),
+            None
+        );
+    }
+
+    #[test]
+    fn utf8_encoding_extends_the_coordinate_range() {
+        let utf8 = MouseProtocolEncoding::Utf8;
+        let mode = MouseProtocolMode::PressRelease;
+        let mut expected = b"\x1b[M\x20".to_vec();
+        expected.extend_from_slice("\u{14d}".as_bytes());
+        expected.push(0x21);
+        assert_eq!(
+            encode(mode, utf8, MouseAction::Press(MouseButton::Left), 0, 300).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn motion_depends_on_the_tracking_mode() {
+        let sgr = MouseProtocolEncoding::Sgr;
+        let drag = MouseAction::Motion(Some(MouseButton::Left));
+        let hover = MouseAction::Motion(None);
+
+        assert_eq!(
+            encode(MouseProtocolMode::PressRelease, sgr, drag, 0, 0),
+            None
+        );
+        assert_eq!(
+            encode(MouseProtocolMode::ButtonMotion, sgr, drag, 0, 0).unwrap(),
+            b"\x1b[<32;1;1M"
+        );
+        assert_eq!(
+            encode(MouseProtocolMode::ButtonMotion, sgr, hover, 0, 0),
+            None
+        );
+        assert_eq!(
+            encode(MouseProtocolMode::AnyMotion, sgr, hover, 0, 0).unwrap(),
+            b"\x1b[<35;1;1M"
+        );
+    }
+
+    #[test]
+    fn x10_mode_reports_presses_only_and_drops_modifiers() {
+        let mode = MouseProtocolMode::Press;
+        let sgr = MouseProtocolEncoding::Sgr;
+        let ctrl = MouseModifiers {
+            ctrl: true,
+            ..NO_MODS
+        };
+        assert_eq!(
+            encode_mouse_event(mode, sgr, MouseAction::Press(MouseButton::Left), ctrl, 0, 0)
+                .unwrap(),
+            b"\x1b[<0;1;1M"
+        );
+        assert_eq!(
+            encode(mode, sgr, MouseAction::Release(MouseButton::Left), 0, 0),
+            None
+        );
+    }
+
+    #[test]
+    fn modifiers_are_added_to_the_button_code() {
+        let mods = MouseModifiers {
+            shift: true,
+            alt: true,
+            ctrl: true,
+        };
+        assert_eq!(
+            encode_mouse_event(
+                MouseProtocolMode::PressRelease,
+                MouseProtocolEncoding::Sgr,
+                MouseAction::Press(MouseButton::Middle),
+                mods,
+                0,
+                0
+            )
+            .unwrap(),
+            b"\x1b[<29;1;1M"
+        );
+    }
+}
@@ -0,0 +1,191 @@
+// OSC 133 semantic prompt marks. Shells that load the snippets in
+// `shell-integration/` send them around every prompt and command, which
+// tells the terminal where each command's prompt and output are.
+
+use super::selection::Point;
+
+/// `OSC 133 ; A`, `B`, `C` and `D`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PromptMark {
+    /// The shell is about to draw its prompt
+    PromptStart,
+    /// The prompt is drawn; the command is typed from here
+    CommandStart,
+    /// The command was entered and its output starts here
+    OutputStart,
+    /// The command finished, with its exit status when the shell sent one
+    CommandFinished(Option<i32>),
+}
+
+/// A prompt and the command run from it, in buffer points
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ShellCommand {
+    pub(crate) prompt: Point,
+    pub(crate) input: Option<Point>,
+    pub(crate) output: Option<Point>,
+    pub(crate) end: Option<Point>,
+    pub(crate) exit_code: Option<i32>,
+}
+
+impl ShellCommand {
+    fn new(prompt: Point) -> Self {
+        Self {
+            prompt,
+            input: None,
+            output: None,
+            end: None,
+            exit_code: None,
+        }
+    }
+
+    pub(crate) fn failed(&self) -> bool {
+        self.exit_code.is_some_and(|code| code != 0)
+    }
+}
+
+/// The commands seen on the main screen, oldest first
+#[derive(Debug, Default)]
+pub(crate) struct ShellCommands {
+    commands: Vec<ShellCommand>,
+}
+
+impl ShellCommands {
+    /// Records `mark`, sent with the cursor at `point`
+    pub(crate) fn mark(&mut self, mark: PromptMark, point: Point) {
+        if mark == PromptMark::PromptStart {
+            // A prompt drawn again in place replaces the old one, and a
+            // prompt further up would leave the later ones out of order
+            while self
+                .commands
+                .last()
+                .is_some_and(|command| command.prompt.line >= point.line)
+            {
+                self.commands.pop();
+            }
+            self.commands.push(ShellCommand::new(point));
+            return;
+        }
+        let Some(command) = self.commands.last_mut() else {
+            return;
+        };
+        match mark {
+            PromptMark::PromptStart => {}
+            PromptMark::CommandStart => command.input = Some(point),
+            PromptMark::OutputStart => command.output = Some(point),
+            // Shells also report the status of empty command lines, which
+            // ran nothing
+            PromptMark::CommandFinished(code) => {
+                if command.output.is_some() && command.end.is_none() {
+                    command.end = Some(point);
+                    command.exit_code = code;
+                }
+            }
+        }
+    }
+
+    /// Drops the commands whose prompt scrolled off the top of the buffer
+    pub(crate) fn forget_before(&mut self, line: usize) {
+        let dropped = self
+            .commands
+            .partition_point(|command| command.prompt.line < line);
+        self.commands.drain(..dropped);
+    }
+
+    /// Line of the closest prompt above `line`
+    pub(crate) fn prompt_before(&self, line: usize) -> Option<usize> {
+        let index = self
+            .commands
+            .partition_point(|command| command.prompt.line < line);
+        index
+            .checked_sub(1)
+            .map(|index| self.commands[index].prompt.line)
+    }
+
+    /// Line of the closest prompt below `line`
+    pub(crate) fn prompt_after(&self, line: usize) -> Option<usize> {
+        let index = self
+            .commands
+            .partition_point(|command| command.prompt.line <= line);
+        self.commands.get(index).map(|command| command.prompt.line)
+    }
+
+    /// The command whose prompt starts on `line`
+    pub(crate) fn at_line(&self, line: usize) -> Option<&ShellCommand> {
+        let index = self
+            .commands
+            .partition_point(|command| command.prompt.line < line);
+        self.commands
+            .get(index)
+            .filter(|command| command.prompt.line == line)
+    }
+
+    /// Where the output of the latest finished command starts and ends, the
+    /// end excluded. Without a finish mark the next prompt ends the output.
+    pub(crate) fn last_output(&self) -> Option<(Point, Point)> {
+        let mut next_prompt = None;
+        for command in self.commands.iter().rev() {
+            if let Some(output) = command.output
+                && let Some(end) = command.end.or(next_prompt)
+            {
+                return Some((output, end));
+            }
+            next_prompt = Some(command.prompt);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(commands: &mut ShellCommands, prompt: usize, output_lines: usize, code: i32) {
+        commands.mark(PromptMark::PromptStart, Point::new(prompt, 0));
+        commands.mark(PromptMark::CommandStart, Point::new(prompt, 2));
+        commands.mark(PromptMark::OutputStart, Point::new(prompt + 1, 0));
+        let end = Point::new(prompt + 1 + output_lines, 0);
+        commands.mark(PromptMark::CommandFinished(Some(code)), end);
+    }
+
+    #[test]
+    fn prompts_are_found_around_a_line() {
+        let mut commands = ShellCommands::default();
+        run(&mut commands, 0, 3, 0);
+        run(&mut commands, 4, 1, 1);
+        commands.mark(PromptMark::PromptStart, Point::new(6, 0));
+        assert_eq!(commands.prompt_before(4), Some(0));
+        assert_eq!(commands.prompt_before(5), Some(4));
+        assert_eq!(commands.prompt_before(0), None);
+        assert_eq!(commands.prompt_after(0), Some(4));
+        assert_eq!(commands.prompt_after(6), None);
+        assert!(commands.at_line(4).is_some_and(ShellCommand::failed));
+        assert!(!commands.at_line(0).unwrap().failed());
+        assert!(commands.at_line(1).is_none());
+    }
+
+    #[test]
+    fn last_output_skips_empty_command_lines() {
+        let mut commands = ShellCommands::default();
+        run(&mut commands, 0, 2, 0);
+        commands.mark(PromptMark::PromptStart, Point::new(3, 0));
+        commands.mark(PromptMark::CommandFinished(Some(0)), Point::new(4, 0));
+        commands.mark(PromptMark::PromptStart, Point::new(4, 0));
+        assert_eq!(
+            commands.last_output(),
+            Some((Point::new(1, 0), Point::new(3, 0)))
+        );
+        assert_eq!(commands.at_line(3).unwrap().exit_code, None);
+    }
+
+    #[test]
+    fn redrawn_prompts_replace_the_old_ones() {
+        let mut commands = ShellCommands::default();
+        run(&mut commands, 0, 1, 0);
+        commands.mark(PromptMark::PromptStart, Point::new(2, 0));
+        commands.mark(PromptMark::PromptStart, Point::new(2, 0));
+        assert_eq!(commands.prompt_after(0), Some(2));
+        assert_eq!(commands.prompt_after(2), None);
+        commands.forget_before(1);
+        assert_eq!(commands.prompt_before(2), None);
+    }
+}
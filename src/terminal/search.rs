@@ -0,0 +1,206 @@
+// Text search over the screen and scrollback. Patterns are plain text or a
+// regex in the syntax of the `regex` crate, which matches in time linear in
+// the line length whatever the pattern. Each logical line is searched on
+// its own, so `^` and `$` match at its ends.
+
+use std::fmt;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::terminal::selection::Point;
+
+// Keeps `(a{1000}){1000}` from compiling into a program of gigabytes
+const MAX_PROGRAM_SIZE: usize = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub(crate) struct SearchQuery {
+    pub(crate) text: String,
+    pub(crate) regex: bool,
+    pub(crate) case_sensitive: bool,
+}
+
+/// A match from its first to its last buffer cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SearchMatch {
+    pub(crate) start: Point,
+    pub(crate) end: Point,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SearchHighlight {
+    Match,
+    /// The match search navigation is on
+    Current,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PatternError {
+    pub(crate) message: String,
+    /// Char offset in the pattern
+    pub(crate) position: usize,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+/// A compiled search pattern
+#[derive(Debug, Clone)]
+pub(crate) struct Matcher {
+    regex: Regex,
+}
+
+impl Matcher {
+    pub(crate) fn new(query: &SearchQuery) -> Result<Self, PatternError> {
+        let pattern = if query.regex {
+            check_syntax(query)?;
+            query.text.clone()
+        } else {
+            regex::escape(&query.text)
+        };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!query.case_sensitive)
+            .size_limit(MAX_PROGRAM_SIZE)
+            .build()
+            .map_err(|err| PatternError {
+                message: match err {
+                    regex::Error::CompiledTooBig(_) => "pattern too large".to_string(),
+                    err => err.to_string(),
+                },
+                position: 0,
+            })?;
+        Ok(Self { regex })
+    }
+
+    /// Char ranges of the non-overlapping, non-empty matches in a line
+    pub(crate) fn find_all(&self, text: &[char]) -> Vec<(usize, usize)> {
+        let line: String = text.iter().collect();
+        // Byte offset of every char, to turn match offsets back into chars
+        let offsets: Vec<usize> = line.char_indices().map(|(offset, _)| offset).collect();
+        let char_index = |offset| offsets.binary_search(&offset).unwrap_or_else(|end| end);
+        self.regex
+            .find_iter(&line)
+            .filter(|found| !found.is_empty())
+            .map(|found| (char_index(found.start()), char_index(found.end())))
+            .collect()
+    }
+}
+
+// The regex crate describes syntax errors over several lines, with the
+// pattern drawn above a caret; the search bar has room for one
+fn check_syntax(query: &SearchQuery) -> Result<(), PatternError> {
+    let parsed = regex_syntax::ParserBuilder::new()
+        .case_insensitive(!query.case_sensitive)
+        .build()
+        .parse(&query.text);
+    let (message, offset) = match parsed {
+        Ok(_) => return Ok(()),
+        Err(regex_syntax::Error::Parse(err)) => (err.kind().to_string(), err.span().start.offset),
+        Err(regex_syntax::Error::Translate(err)) => {
+            (err.kind().to_string(), err.span().start.offset)
+        }
+        Err(err) => (err.to_string(), 0),
+    };
+    Err(PatternError {
+        message,
+        position: query.text[..offset].chars().count(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(pattern: &str, text: &str) -> Vec<String> {
+        let query = SearchQuery {
+            text: pattern.to_string(),
+            regex: true,
+            case_sensitive: true,
+        };
+        let chars: Vec<char> = text.chars().collect();
+        Matcher::new(&query)
+            .unwrap()
+            .find_all(&chars)
+            .into_iter()
+            .map(|(start, end)| chars[start..end].iter().collect())
+            .collect()
+    }
+
+    fn error(pattern: &str) -> PatternError {
+        let query = SearchQuery {
+            text: pattern.to_string(),
+            regex: true,
+            case_sensitive: true,
+        };
+        Matcher::new(&query).unwrap_err()
+    }
+
+    #[test]
+    fn plain_text_is_literal_and_folds_case_when_asked() {
+        let query = SearchQuery {
+            text: "a.B".to_string(),
+            regex: false,
+            case_sensitive: false,
+        };
+        let text: Vec<char> = "a.b axb A.B".chars().collect();
+        assert_eq!(
+            Matcher::new(&query).unwrap().find_all(&text),
+            [(0, 3), (8, 11)]
+        );
+    }
+
+    #[test]
+    fn classes_and_escapes() {
+        assert_eq!(find(r"[a-c]+\d", "xab1 c22 d3"), ["ab1", "c2"]);
+        assert_eq!(find(r"[^\s,]+", "one, two"), ["one", "two"]);
+        assert_eq!(find(r"\w+\.rs", "src/main.rs"), ["main.rs"]);
+        assert_eq!(find(r"[]-]", "a]b-c"), ["]", "-"]);
+        assert_eq!(find(r"\bcat\b", "cat concat cats cat"), ["cat", "cat"]);
+    }
+
+    #[test]
+    fn alternation_prefers_earlier_branches() {
+        assert_eq!(find("ab|abc|x", "abc x"), ["ab", "x"]);
+        assert_eq!(find("(?:abc|ab)c?", "abc"), ["abc"]);
+        assert_eq!(
+            find("error|warn(ing)?", "warning: error"),
+            ["warning", "error"]
+        );
+    }
+
+    #[test]
+    fn quantifiers_are_greedy_unless_lazy() {
+        assert_eq!(find("<.*>", "<a><b>"), ["<a><b>"]);
+        assert_eq!(find("<.*?>", "<a><b>"), ["<a>", "<b>"]);
+        assert_eq!(find(r"\d{2,3}", "1 12 12345"), ["12", "123", "45"]);
+        assert_eq!(find("x{2}", "xxxxx"), ["xx", "xx"]);
+        assert_eq!(find("a{0,2}", "aaa"), ["aa", "a"]);
+    }
+
+    #[test]
+    fn anchors_match_at_line_ends_only() {
+        assert_eq!(find("^a|b$", "aab ab"), ["a", "b"]);
+        assert_eq!(find("^$", ""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn nested_quantifiers_stay_fast() {
+        let text = "a".repeat(5000);
+        assert_eq!(find("(a*)*b", &text), Vec::<String>::new());
+    }
+
+    #[test]
+    fn syntax_errors_point_at_the_problem() {
+        assert_eq!(error("ab(c").message, "unclosed group");
+        assert_eq!(error("ab(c").position, 2);
+        assert_eq!(error("a)").position, 1);
+        assert_eq!(
+            error("*a").message,
+            "repetition operator missing expression"
+        );
+        assert_eq!(error(r"é\q").position, 1);
+        assert_eq!(error("x{1000}{1000}").message, "pattern too large");
+    }
+}
@@ -0,0 +1,131 @@
+// Mouse selection over the whole buffer, scrollback included. Points are
+// addressed by buffer line, counted from the first line the terminal showed,
+// so a selection stays on its text while the viewport and the output scroll.
+
+/// Characters that end a word for double-click selection
+pub(crate) const DEFAULT_WORD_DELIMITERS: &str = ",│`|:\"' ()[]{}<>\t";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SelectionMode {
+    /// Click and drag: everything between the two points, in reading order
+    Linear,
+    /// Double click: whole words at both ends
+    Word,
+    /// Triple click: whole lines, following soft wraps
+    Line,
+    /// Alt and drag: the rectangle spanned by the two points
+    Block,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Point {
+    pub(crate) line: usize,
+    pub(crate) col: usize,
+}
+
+impl Point {
+    pub(crate) fn new(line: usize, col: usize) -> Self {
+        Self { line, col }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Selection {
+    pub(crate) mode: SelectionMode,
+    /// Where the selection started
+    pub(crate) anchor: Point,
+    /// Where the pointer is now
+    pub(crate) cursor: Point,
+    // First and last selected points once words, lines and wide characters
+    // are taken into account
+    pub(crate) start: Point,
+    pub(crate) end: Point,
+    /// Started by a mouse click rather than from the keyboard
+    pub(crate) from_click: bool,
+}
+
+impl Selection {
+    pub(crate) fn new(mode: SelectionMode, point: Point) -> Self {
+        Self {
+            mode,
+            anchor: point,
+            cursor: point,
+            start: point,
+            end: point,
+            from_click: true,
+        }
+    }
+
+    /// A plain click selects nothing until the pointer moves to another
+    /// cell. From the keyboard, the cell under the cursor is selected.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.from_click && self.mode == SelectionMode::Linear && self.anchor == self.cursor
+    }
+
+    /// Anchor and cursor in reading order
+    pub(crate) fn ordered(&self) -> (Point, Point) {
+        if self.anchor <= self.cursor {
+            (self.anchor, self.cursor)
+        } else {
+            (self.cursor, self.anchor)
+        }
+    }
+
+    /// Columns covered on every line of a block selection
+    pub(crate) fn block_cols(&self) -> (usize, usize) {
+        let (a, b) = (self.anchor.col, self.cursor.col);
+        (a.min(b), a.max(b))
+    }
+
+    pub(crate) fn contains(&self, point: Point) -> bool {
+        if self.is_empty() || point.line < self.start.line || point.line > self.end.line {
+            return false;
+        }
+        if self.mode == SelectionMode::Block {
+            let (left, right) = self.block_cols();
+            return (left..=right).contains(&point.col);
+        }
+        (self.start..=self.end).contains(&point)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selection(mode: SelectionMode, anchor: Point, cursor: Point) -> Selection {
+        let mut selection = Selection::new(mode, anchor);
+        selection.cursor = cursor;
+        (selection.start, selection.end) = selection.ordered();
+        selection
+    }
+
+    #[test]
+    fn linear_selection_flows_across_lines() {
+        let sel = selection(SelectionMode::Linear, Point::new(3, 5), Point::new(1, 2));
+        assert!(sel.contains(Point::new(1, 2)));
+        assert!(sel.contains(Point::new(2, 0)));
+        assert!(sel.contains(Point::new(2, 79)));
+        assert!(sel.contains(Point::new(3, 5)));
+        assert!(!sel.contains(Point::new(1, 1)));
+        assert!(!sel.contains(Point::new(3, 6)));
+    }
+
+    #[test]
+    fn block_selection_is_a_rectangle() {
+        let sel = selection(SelectionMode::Block, Point::new(1, 8), Point::new(3, 2));
+        assert!(sel.contains(Point::new(2, 2)));
+        assert!(sel.contains(Point::new(1, 8)));
+        assert!(!sel.contains(Point::new(2, 9)));
+        assert!(!sel.contains(Point::new(2, 1)));
+        assert!(!sel.contains(Point::new(4, 4)));
+    }
+
+    #[test]
+    fn a_click_without_a_drag_selects_nothing() {
+        let point = Point::new(0, 4);
+        assert!(Selection::new(SelectionMode::Linear, point).is_empty());
+        assert!(!Selection::new(SelectionMode::Linear, point).contains(point));
+        assert!(!Selection::new(SelectionMode::Word, point).is_empty());
+    }
+}
@@ -0,0 +1,653 @@
+// vt100 silently drops the control sequences it doesn't understand, so a
+// second vte parser runs over the same byte stream and picks those out.
+
+use crate::terminal::clipboard::base64_decode;
+use crate::terminal::color::{Color32, parse_color_spec};
+use crate::terminal::cwd::ReportedDir;
+use crate::terminal::keymap::KITTY_ALL_FLAGS;
+use crate::terminal::prompt::PromptMark;
+
+/// The colors addressed by OSC 10/11/12 (and reset by OSC 110/111/112)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DynamicColor {
+    Foreground,
+    Background,
+    Cursor,
+}
+
+impl DynamicColor {
+    pub(crate) fn osc_code(self) -> u16 {
+        match self {
+            Self::Foreground => 10,
+            Self::Background => 11,
+            Self::Cursor => 12,
+        }
+    }
+
+    fn from_osc(code: u16) -> Option<Self> {
+        match code {
+            10 => Some(Self::Foreground),
+            11 => Some(Self::Background),
+            12 => Some(Self::Cursor),
+            _ => None,
+        }
+    }
+
+    fn next(self) -> Option<Self> {
+        match self {
+            Self::Foreground => Some(Self::Background),
+            Self::Background => Some(Self::Cursor),
+            Self::Cursor => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Sequence {
+    SetPaletteColor {
+        index: u8,
+        color: Color32,
+    },
+    /// An empty list resets the whole palette
+    ResetPaletteColors(Vec<u8>),
+    SetDynamicColor {
+        target: DynamicColor,
+        color: Color32,
+    },
+    ResetDynamicColor(DynamicColor),
+    /// `OSC 4 ; index ; ?`, answered with the same string terminator
+    QueryPaletteColor {
+        index: u8,
+        bell_terminated: bool,
+    },
+    /// `OSC 10/11/12 ; ?`
+    QueryDynamicColor {
+        target: DynamicColor,
+        bell_terminated: bool,
+    },
+    /// DA1, `CSI c`
+    PrimaryDeviceAttributes,
+    /// DA2, `CSI > c`
+    SecondaryDeviceAttributes,
+    /// DSR 5, `CSI 5 n`
+    StatusReport,
+    /// DSR 6, `CSI 6 n`, or DECXCPR, `CSI ? 6 n`, when `private` is set
+    CursorPositionReport {
+        private: bool,
+    },
+    /// XTVERSION, `CSI > q`
+    TerminalVersion,
+    /// OSC 0 sets both the title and the icon name, OSC 1 and 2 one each
+    SetTitle {
+        title: Option<String>,
+        icon_name: Option<String>,
+    },
+    /// XTWINOPS `CSI 22 ; Ps t`
+    PushTitle(TitleParts),
+    /// XTWINOPS `CSI 23 ; Ps t`
+    PopTitle(TitleParts),
+    /// kitty keyboard protocol `CSI > flags u`
+    PushKeyboardFlags(u8),
+    /// `CSI < count u`
+    PopKeyboardFlags(usize),
+    /// `CSI = flags ; mode u`
+    SetKeyboardFlags {
+        flags: u8,
+        update: FlagsUpdate,
+    },
+    /// `CSI ? u`
+    QueryKeyboardFlags,
+    /// `OSC 52 ; targets ; base64 text`
+    SetClipboard {
+        targets: String,
+        text: String,
+    },
+    /// `OSC 52 ; targets ; ?`
+    QueryClipboard {
+        targets: String,
+        bell_terminated: bool,
+    },
+    /// `OSC 8 ; params ; uri` with a non-empty URI
+    OpenHyperlink {
+        id: Option<String>,
+        uri: String,
+    },
+    /// `OSC 8 ; ;`
+    CloseHyperlink,
+    /// `OSC 7 ; file://host/path`
+    SetWorkingDirectory(ReportedDir),
+    /// `OSC 133 ; A/B/C/D`, the last with an optional exit status
+    PromptMark(PromptMark),
+    /// A character printed while a hyperlink is open, so the grid can tell
+    /// which cell it lands in
+    Print(char),
+    /// DECSET/DECRST 47, 1047 or 1049
+    SwitchScreen,
+    /// XTMODKEYS `CSI > 4 ; level m`
+    SetModifyOtherKeys(u8),
+    /// XTQMODKEYS `CSI ? 4 m`
+    QueryModifyOtherKeys,
+}
+
+/// How `CSI = flags ; mode u` combines the flags with the current ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FlagsUpdate {
+    Replace,
+    Add,
+    Remove,
+}
+
+/// Which strings a title stack operation applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TitleParts {
+    pub(crate) title: bool,
+    pub(crate) icon_name: bool,
+}
+
+impl TitleParts {
+    fn from_param(param: u16) -> Option<Self> {
+        let (title, icon_name) = match param {
+            0 => (true, true),
+            1 => (false, true),
+            2 => (true, false),
+            _ => return None,
+        };
+        Some(Self { title, icon_name })
+    }
+}
+
+/// Scans PTY output for sequences the grid has to handle itself.
+pub(crate) struct SequenceScanner {
+    parser: vte::Parser,
+    collector: Collector,
+}
+
+impl SequenceScanner {
+    pub(crate) fn new() -> Self {
+        Self {
+            parser: vte::Parser::new(),
+            collector: Collector::default(),
+        }
+    }
+
+    /// Feeds one byte and returns any sequences it completed.
+    pub(crate) fn advance(&mut self, byte: u8) -> Option<Vec<Sequence>> {
+        self.parser.advance(&mut self.collector, byte);
+        if self.collector.sequences.is_empty() {
+            None
+        } else {
+            Some(std::mem::take(&mut self.collector.sequences))
+        }
+    }
+}
+
+#[derive(Default)]
+struct Collector {
+    sequences: Vec<Sequence>,
+    in_hyperlink: bool,
+}
+
+impl Collector {
+    fn osc_palette(&mut self, params: &[&[u8]], bell_terminated: bool) {
+        for pair in params.chunks(2) {
+            let [index, spec] = pair else {
+                break;
+            };
+            let Some(index) = parse_number::<u8>(index) else {
+                continue;
+            };
+            if *spec == b"?" {
+                self.sequences.push(Sequence::QueryPaletteColor {
+                    index,
+                    bell_terminated,
+                });
+            } else if let Some(color) = parse_color(spec) {
+                self.sequences
+                    .push(Sequence::SetPaletteColor { index, color });
+            }
+        }
+    }
+
+    fn osc_dynamic_colors(&mut self, first: DynamicColor, specs: &[&[u8]], bell_terminated: bool) {
+        // `OSC 10 ; fg ; bg` sets consecutive colors starting at the given one
+        let mut target = Some(first);
+        for spec in specs {
+            let Some(current) = target else {
+                break;
+            };
+            if *spec == b"?" {
+                self.sequences.push(Sequence::QueryDynamicColor {
+                    target: current,
+                    bell_terminated,
+                });
+            } else if let Some(color) = parse_color(spec) {
+                self.sequences.push(Sequence::SetDynamicColor {
+                    target: current,
+                    color,
+                });
+            }
+            target = current.next();
+        }
+    }
+}
+
+impl vte::Perform for Collector {
+    fn print(&mut self, c: char) {
+        if self.in_hyperlink {
+            self.sequences.push(Sequence::Print(c));
+        }
+    }
+
+    fn osc_dispatch(&mut self, params: &[&[u8]], bell_terminated: bool) {
+        let Some(code) = params.first().and_then(|code| parse_number::<u16>(code)) else {
+            return;
+        };
+        let args = &params[1..];
+        match code {
+            0..=2 => {
+                // Titles may contain `;`, which vte treats as a separator
+                let text = String::from_utf8_lossy(&args.join(&b';')).into_owned();
+                self.sequences.push(Sequence::SetTitle {
+                    title: (code != 1).then(|| text.clone()),
+                    icon_name: (code != 2).then_some(text),
+                });
+            }
+            4 => self.osc_palette(args, bell_terminated),
+            7 => {
+                // Paths may contain `;`, which vte treats as a separator
+                if let Some(dir) = ReportedDir::parse(&args.join(&b';')) {
+                    self.sequences.push(Sequence::SetWorkingDirectory(dir));
+                }
+            }
+            10..=12 => {
+                if let Some(target) = DynamicColor::from_osc(code) {
+                    self.osc_dynamic_colors(target, args, bell_terminated);
+                }
+            }
+            8 => {
+                let [params, uri @ ..] = args else {
+                    return;
+                };
+                // URIs may contain `;`, which vte treats as a separator
+                let uri = String::from_utf8_lossy(&uri.join(&b';')).into_owned();
+                self.in_hyperlink = !uri.is_empty();
+                if uri.is_empty() {
+                    self.sequences.push(Sequence::CloseHyperlink);
+                    return;
+                }
+                let id = params
+                    .split(|&byte| byte == b':')
+                    .find_map(|param| param.strip_prefix(b"id="))
+                    .filter(|id| !id.is_empty())
+                    .map(|id| String::from_utf8_lossy(id).into_owned());
+                self.sequences.push(Sequence::OpenHyperlink { id, uri });
+            }
+            52 => {
+                let [targets, data, ..] = args else {
+                    return;
+                };
+                let targets = String::from_utf8_lossy(targets).into_owned();
+                if *data == b"?" {
+                    self.sequences.push(Sequence::QueryClipboard {
+                        targets,
+                        bell_terminated,
+                    });
+                } else if let Some(bytes) = base64_decode(data) {
+                    self.sequences.push(Sequence::SetClipboard {
+                        targets,
+                        text: String::from_utf8_lossy(&bytes).into_owned(),
+                    });
+                }
+            }
+            133 => {
+                let Some(kind) = args.first() else {
+                    return;
+                };
+                let mark = match *kind {
+                    b"A" => PromptMark::PromptStart,
+                    b"B" => PromptMark::CommandStart,
+                    b"C" => PromptMark::OutputStart,
+                    b"D" => PromptMark::CommandFinished(
+                        args.get(1).and_then(|code| parse_number::<i32>(code)),
+                    ),
+                    _ => return,
+                };
+                self.sequences.push(Sequence::PromptMark(mark));
+            }
+            104 => {
+                let indices = args
+                    .iter()
+                    .filter_map(|index| parse_number::<u8>(index))
+                    .collect();
+                self.sequences.push(Sequence::ResetPaletteColors(indices));
+            }
+            110..=112 => {
+                if let Some(target) = DynamicColor::from_osc(code - 100) {
+                    self.sequences.push(Sequence::ResetDynamicColor(target));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn csi_dispatch(
+        &mut self,
+        params: &vte::Params,
+        intermediates: &[u8],
+        _ignore: bool,
+        action: char,
+    ) {
+        let mut values = params
+            .iter()
+            .map(|param| param.first().copied().unwrap_or(0));
+        let first = values.next().unwrap_or(0);
+        let second = values.next().unwrap_or(0);
+        let switches_screen = || {
+            params
+                .iter()
+                .any(|param| matches!(param.first(), Some(47 | 1047 | 1049)))
+        };
+        let sequence = match (intermediates, action) {
+            ([b'?'], 'h' | 'l') if switches_screen() => Sequence::SwitchScreen,
+            ([], 'c') if first == 0 => Sequence::PrimaryDeviceAttributes,
+            ([b'>'], 'c') if first == 0 => Sequence::SecondaryDeviceAttributes,
+            ([], 'n') if first == 5 => Sequence::StatusReport,
+            ([], 'n') if first == 6 => Sequence::CursorPositionReport { private: false },
+            ([b'?'], 'n') if first == 6 => Sequence::CursorPositionReport { private: true },
+            ([b'>'], 'q') if first == 0 => Sequence::TerminalVersion,
+            ([], 't') if first == 22 || first == 23 => {
+                let Some(parts) = TitleParts::from_param(second) else {
+                    return;
+                };
+                if first == 22 {
+                    Sequence::PushTitle(parts)
+                } else {
+                    Sequence::PopTitle(parts)
+                }
+            }
+            ([b'>'], 'u') => Sequence::PushKeyboardFlags(keyboard_flags(first)),
+            ([b'<'], 'u') => Sequence::PopKeyboardFlags(usize::from(first.max(1))),
+            ([b'='], 'u') => {
+                let update = match second {
+                    0 | 1 => FlagsUpdate::Replace,
+                    2 => FlagsUpdate::Add,
+                    3 => FlagsUpdate::Remove,
+                    _ => return,
+                };
+                Sequence::SetKeyboardFlags {
+                    flags: keyboard_flags(first),
+                    update,
+                }
+            }
+            ([b'?'], 'u') => Sequence::QueryKeyboardFlags,
+            ([b'>'], 'm') if first == 4 => Sequence::SetModifyOtherKeys(second.min(2) as u8),
+            ([b'?'], 'm') if first == 4 => Sequence::QueryModifyOtherKeys,
+            _ => return,
+        };
+        self.sequences.push(sequence);
+    }
+}
+
+// Flags this terminal doesn't know are dropped so queries report what it does
+fn keyboard_flags(param: u16) -> u8 {
+    (param & u16::from(KITTY_ALL_FLAGS)) as u8
+}
+
+fn parse_number<T: std::str::FromStr>(bytes: &[u8]) -> Option<T> {
+    std::str::from_utf8(bytes).ok()?.parse().ok()
+}
+
+fn parse_color(bytes: &[u8]) -> Option<Color32> {
+    parse_color_spec(std::str::from_utf8(bytes).ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(bytes: &[u8]) -> Vec<Sequence> {
+        let mut scanner = SequenceScanner::new();
+        bytes
+            .iter()
+            .filter_map(|&byte| scanner.advance(byte))
+            .flatten()
+            .collect()
+    }
+
+    #[test]
+    fn osc_4_sets_several_palette_entries() {
+        assert_eq!(
+            scan(b"\x1b]4;1;rgb:ff/00/00;2;#00ff00\x07"),
+            vec![
+                Sequence::SetPaletteColor {
+                    index: 1,
+                    color: Color32::from_rgb(255, 0, 0)
+                },
+                Sequence::SetPaletteColor {
+                    index: 2,
+                    color: Color32::from_rgb(0, 255, 0)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn osc_10_with_extra_specs_sets_following_colors() {
+        assert_eq!(
+            scan(b"\x1b]10;#ffffff;#000000\x1b\\"),
+            vec![
+                Sequence::SetDynamicColor {
+                    target: DynamicColor::Foreground,
+                    color: Color32::WHITE
+                },
+                Sequence::SetDynamicColor {
+                    target: DynamicColor::Background,
+                    color: Color32::BLACK
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn resets_are_reported() {
+        assert_eq!(
+            scan(b"\x1b]104\x07\x1b]104;3;5\x07\x1b]112\x07"),
+            vec![
+                Sequence::ResetPaletteColors(vec![]),
+                Sequence::ResetPaletteColors(vec![3, 5]),
+                Sequence::ResetDynamicColor(DynamicColor::Cursor),
+            ]
+        );
+    }
+
+    #[test]
+    fn color_queries_remember_their_terminator() {
+        assert_eq!(
+            scan(b"\x1b]4;7;?\x07\x1b]11;?\x1b\\"),
+            vec![
+                Sequence::QueryPaletteColor {
+                    index: 7,
+                    bell_terminated: true
+                },
+                Sequence::QueryDynamicColor {
+                    target: DynamicColor::Background,
+                    bell_terminated: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn device_queries_are_recognized() {
+        assert_eq!(
+            scan(b"\x1b[c\x1b[0c\x1b[>c\x1b[5n\x1b[6n\x1b[?6n\x1b[>0q"),
+            vec![
+                Sequence::PrimaryDeviceAttributes,
+                Sequence::PrimaryDeviceAttributes,
+                Sequence::SecondaryDeviceAttributes,
+                Sequence::StatusReport,
+                Sequence::CursorPositionReport { private: false },
+                Sequence::CursorPositionReport { private: true },
+                Sequence::TerminalVersion,
+            ]
+        );
+    }
+
+    #[test]
+    fn title_sequences_keep_embedded_separators() {
+        assert_eq!(
+            scan(b"\x1b]0;vim; main.rs\x07\x1b]2;make\x07"),
+            vec![
+                Sequence::SetTitle {
+                    title: Some("vim; main.rs".to_string()),
+                    icon_name: Some("vim; main.rs".to_string()),
+                },
+                Sequence::SetTitle {
+                    title: Some("make".to_string()),
+                    icon_name: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn title_stack_operations_are_recognized() {
+        let both = TitleParts {
+            title: true,
+            icon_name: true,
+        };
+        let title_only = TitleParts {
+            title: true,
+            icon_name: false,
+        };
+        assert_eq!(
+            scan(b"\x1b[22t\x1b[22;2t\x1b[23;0t\x1b[22;9t"),
+            vec![
+                Sequence::PushTitle(both),
+                Sequence::PushTitle(title_only),
+                Sequence::PopTitle(both),
+            ]
+        );
+    }
+
+    #[test]
+    fn osc_52_sets_and_queries_the_clipboard() {
+        assert_eq!(
+            scan(b"\x1b]52;c;aGVsbG8=\x07\x1b]52;;?\x1b\\\x1b]52;c;!!\x07"),
+            vec![
+                Sequence::SetClipboard {
+                    targets: "c".to_string(),
+                    text: "hello".to_string(),
+                },
+                Sequence::QueryClipboard {
+                    targets: String::new(),
+                    bell_terminated: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn hyperlinked_text_is_reported_character_by_character() {
+        assert_eq!(
+            scan(b"a\x1b]8;id=x:k=v;http://e.com/?a=1;b\x1b\\h\xc3\xa9\x1b]8;;\x1b\\z"),
+            vec![
+                Sequence::OpenHyperlink {
+                    id: Some("x".to_string()),
+                    uri: "http://e.com/?a=1;b".to_string(),
+                },
+                Sequence::Print('h'),
+                Sequence::Print('é'),
+                Sequence::CloseHyperlink,
+            ]
+        );
+    }
+
+    #[test]
+    fn working_directory_reports_are_recognized() {
+        assert_eq!(
+            scan(b"\x1b]7;file://box/tmp/a;b%20c\x07\x1b]7;/tmp\x07"),
+            vec![Sequence::SetWorkingDirectory(ReportedDir {
+                host: "box".to_string(),
+                path: "/tmp/a;b c".into(),
+            })]
+        );
+    }
+
+    #[test]
+    fn prompt_marks_are_recognized() {
+        assert_eq!(
+            scan(
+                b"\x1b]133;A\x07\x1b]133;B\x1b\\\x1b]133;C;aid=7\x07\
+                  \x1b]133;D;127\x07\x1b]133;D\x07\x1b]133;Z\x07"
+            ),
+            vec![
+                Sequence::PromptMark(PromptMark::PromptStart),
+                Sequence::PromptMark(PromptMark::CommandStart),
+                Sequence::PromptMark(PromptMark::OutputStart),
+                Sequence::PromptMark(PromptMark::CommandFinished(Some(127))),
+                Sequence::PromptMark(PromptMark::CommandFinished(None)),
+            ]
+        );
+    }
+
+    #[test]
+    fn screen_switches_are_reported() {
+        assert_eq!(
+            scan(b"\x1b[?1049h\x1b[?47l\x1b[?1;1047h\x1b[?1h"),
+            vec![Sequence::SwitchScreen; 3]
+        );
+    }
+
+    #[test]
+    fn kitty_keyboard_sequences_are_recognized() {
+        assert_eq!(
+            scan(b"\x1b[>5u\x1b[>255u\x1b[<u\x1b[<3u\x1b[=2;2u\x1b[=1u\x1b[=4;9u\x1b[?u"),
+            vec![
+                Sequence::PushKeyboardFlags(5),
+                Sequence::PushKeyboardFlags(31),
+                Sequence::PopKeyboardFlags(1),
+                Sequence::PopKeyboardFlags(3),
+                Sequence::SetKeyboardFlags {
+                    flags: 2,
+                    update: FlagsUpdate::Add
+                },
+                Sequence::SetKeyboardFlags {
+                    flags: 1,
+                    update: FlagsUpdate::Replace
+                },
+                Sequence::QueryKeyboardFlags,
+            ]
+        );
+    }
+
+    #[test]
+    fn modify_other_keys_sequences_are_recognized() {
+        assert_eq!(
+            scan(b"\x1b[>4;2m\x1b[>4m\x1b[>4;1m\x1b[?4m\x1b[>1;2m"),
+            vec![
+                Sequence::SetModifyOtherKeys(2),
+                Sequence::SetModifyOtherKeys(0),
+                Sequence::SetModifyOtherKeys(1),
+                Sequence::QueryModifyOtherKeys,
+            ]
+        );
+    }
+
+    #[test]
+    fn unrelated_csi_sequences_are_ignored() {
+        assert!(scan(b"\x1b[2J\x1b[1;31m\x1b[?25h\x1b[3n\x1b[u").is_empty());
+    }
+
+    #[test]
+    fn sequences_are_reported_on_their_final_byte() {
+        let mut scanner = SequenceScanner::new();
+        let bytes = b"ab\x1b]11;#102030\x07cd";
+        let completed_at: Vec<usize> = bytes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &byte)| scanner.advance(byte).map(|_| i))
+            .collect();
+        assert_eq!(completed_at, vec![bytes.len() - 3]);
+    }
+}
@@ -0,0 +1,194 @@
+// Which shell to start in the PTY and how.
+
+use crate::cli::Options;
+use std::ffi::OsString;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Value of `TERM` for the shell. vt100 covers what xterm's terminfo entry
+/// asks of a terminal, and the entry is installed nearly everywhere.
+pub(crate) const TERM: &str = "xterm-256color";
+
+/// Value of `TERM_PROGRAM`, so programs can tell which terminal they run in
+pub(crate) const TERM_PROGRAM: &str = "shitty";
+
+/// Shell of last resort, present on every Unix
+const FALLBACK_SHELL: &str = "/bin/sh";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct ShellConfig {
+    /// Program to run. Without one, `$SHELL`, then the user's passwd entry,
+    /// then `/bin/sh` is used.
+    pub(crate) program: Option<PathBuf>,
+    pub(crate) args: Vec<String>,
+    /// Start a login shell, which shells recognize by a `-` in front of
+    /// their argv[0]
+    pub(crate) login: bool,
+    /// Directory to start in, instead of the terminal's own
+    pub(crate) working_dir: Option<PathBuf>,
+    /// Variables added to the environment the shell inherits. They are set
+    /// after `TERM`, `COLORTERM` and `TERM_PROGRAM`, so they may replace them.
+    pub(crate) env: Vec<(String, String)>,
+}
+
+impl ShellConfig {
+    /// The shell program to run
+    pub(crate) fn program(&self) -> PathBuf {
+        resolve_program(
+            self.program.as_deref(),
+            std::env::var_os("SHELL"),
+            passwd_shell,
+        )
+    }
+
+    /// Applies the command-line options over the configuration. A command
+    /// given with `-e` runs in place of the shell, never as a login shell.
+    pub(crate) fn with_options(mut self, options: &Options) -> Self {
+        if let Some((program, args)) = options.command.split_first() {
+            self.program = Some(program.into());
+            self.args = args.to_vec();
+            self.login = false;
+        }
+        if let Some(dir) = &options.working_directory {
+            self.working_dir = Some(dir.clone());
+        }
+        self
+    }
+
+    /// A command that starts the shell, for the caller to attach to the PTY
+    pub(crate) fn command(&self) -> Command {
+        let program = self.program();
+        let mut command = Command::new(&program);
+        command
+            .arg0(argv0(&program, self.login))
+            .args(&self.args)
+            .env("TERM", TERM)
+            .env("COLORTERM", "truecolor")
+            .env("TERM_PROGRAM", TERM_PROGRAM)
+            .env("TERM_PROGRAM_VERSION", env!("CARGO_PKG_VERSION"))
+            .envs(self.env.iter().map(|(name, value)| (name, value)));
+        // A directory that has gone away shouldn't keep the shell from
+        // starting
+        if let Some(dir) = self.working_dir.as_deref().filter(|dir| dir.is_dir()) {
+            command.current_dir(dir);
+        }
+        command
+    }
+}
+
+fn resolve_program(
+    configured: Option<&Path>,
+    env_shell: Option<OsString>,
+    passwd_shell: impl FnOnce() -> Option<PathBuf>,
+) -> PathBuf {
+    configured
+        .map(Path::to_path_buf)
+        .or_else(|| {
+            env_shell
+                .filter(|shell| !shell.is_empty())
+                .map(PathBuf::from)
+        })
+        .or_else(passwd_shell)
+        .unwrap_or_else(|| PathBuf::from(FALLBACK_SHELL))
+}
+
+/// Login shell of the current user from the passwd database
+fn passwd_shell() -> Option<PathBuf> {
+    let user = nix::unistd::User::from_uid(nix::unistd::getuid()).ok()??;
+    Some(user.shell).filter(|shell| !shell.as_os_str().is_empty())
+}
+
+fn argv0(program: &Path, login: bool) -> OsString {
+    let name = program
+        .file_name()
+        .unwrap_or(program.as_os_str())
+        .to_os_string();
+    if !login {
+        return name;
+    }
+    let mut dashed = OsString::from("-");
+    dashed.push(name);
+    dashed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn shell_is_resolved_in_order() {
+        let passwd = || Some(PathBuf::from("/bin/bash"));
+        let env = || Some(OsString::from("/usr/bin/fish"));
+        let configured = Some(Path::new("/bin/zsh"));
+        assert_eq!(
+            resolve_program(configured, env(), passwd),
+            Path::new("/bin/zsh")
+        );
+        assert_eq!(
+            resolve_program(None, env(), passwd),
+            Path::new("/usr/bin/fish")
+        );
+        assert_eq!(
+            resolve_program(None, Some(OsString::new()), passwd),
+            Path::new("/bin/bash")
+        );
+        assert_eq!(resolve_program(None, None, || None), Path::new("/bin/sh"));
+    }
+
+    #[test]
+    fn login_shells_get_a_dash_in_argv0() {
+        assert_eq!(argv0(Path::new("/bin/zsh"), false), "zsh");
+        assert_eq!(argv0(Path::new("/bin/zsh"), true), "-zsh");
+        assert_eq!(argv0(Path::new("fish"), true), "-fish");
+    }
+
+    #[test]
+    fn command_line_options_replace_the_shell() {
+        let configured = ShellConfig {
+            program: Some(PathBuf::from("/bin/zsh")),
+            args: vec!["-i".to_string()],
+            login: true,
+            working_dir: Some(PathBuf::from("/srv")),
+            env: Vec::new(),
+        };
+        let options = Options {
+            command: vec!["htop".to_string(), "-d".to_string(), "5".to_string()],
+            ..Options::default()
+        };
+        let shell = configured.clone().with_options(&options);
+        assert_eq!(shell.program, Some(PathBuf::from("htop")));
+        assert_eq!(shell.args, ["-d", "5"]);
+        assert!(!shell.login);
+        assert_eq!(shell.working_dir, Some(PathBuf::from("/srv")));
+        assert_eq!(
+            configured.clone().with_options(&Options::default()),
+            configured
+        );
+    }
+
+    #[test]
+    fn command_carries_args_and_environment() {
+        let config = ShellConfig {
+            program: Some(PathBuf::from("/bin/sh")),
+            args: vec!["-c".to_string(), "true".to_string()],
+            login: false,
+            working_dir: Some(PathBuf::from("/nonexistent/dir")),
+            env: vec![("TERM".to_string(), "xterm".to_string())],
+        };
+        let command = config.command();
+        assert_eq!(command.get_program(), "/bin/sh");
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["-c", "true"]);
+        assert_eq!(command.get_current_dir(), None);
+        let env = |name: &str| {
+            command
+                .get_envs()
+                .find(|(key, _)| *key == name)
+                .and_then(|(_, value)| value)
+        };
+        assert_eq!(env("TERM"), Some(OsStr::new("xterm")));
+        assert_eq!(env("COLORTERM"), Some(OsStr::new("truecolor")));
+        assert_eq!(env("TERM_PROGRAM"), Some(OsStr::new(TERM_PROGRAM)));
+    }
+}
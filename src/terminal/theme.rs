@@ -0,0 +1,649 @@
+// Color schemes in the files other terminals keep them in, so a theme shared
+// across a team looks the same here. Each format is read into a `Theme`;
+// colors a file leaves out keep their defaults.
+
+use super::color::{Color32, Theme, parse_color_spec};
+use super::markup::{Node, SyntaxError, line_at, parse_json, parse_plist, parse_yaml};
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::Path;
+use toml_edit::{Document, Item, Table, Value};
+
+/// Alacritty's names for the eight normal and eight bright colors
+const ALACRITTY_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// Windows Terminal's names for the 16 ANSI colors
+const WINDOWS_TERMINAL_NAMES: [&str; 16] = [
+    "black",
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "purple",
+    "cyan",
+    "white",
+    "brightBlack",
+    "brightRed",
+    "brightGreen",
+    "brightYellow",
+    "brightBlue",
+    "brightPurple",
+    "brightCyan",
+    "brightWhite",
+];
+
+/// The base16 color each of the first 22 palette entries takes, as
+/// base16-shell sets them. The six past the ANSI colors hold the accents
+/// that have no ANSI slot.
+const BASE16_PALETTE: [usize; 22] = [
+    0x0, 0x8, 0xb, 0xa, 0xd, 0xe, 0xc, 0x5, 0x3, 0x8, 0xb, 0xa, 0xd, 0xe, 0xc, 0x7, 0x9, 0xf, 0x1,
+    0x2, 0x4, 0x6,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ThemeError(String);
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<SyntaxError> for ThemeError {
+    fn from(error: SyntaxError) -> Self {
+        Self(error.to_string())
+    }
+}
+
+/// Reads a theme, telling the format from the file's extension
+pub(crate) fn load_theme(path: &Path) -> Result<Theme, ThemeError> {
+    let text = std::fs::read_to_string(path).map_err(|error| ThemeError(error.to_string()))?;
+    let extension = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    parse_theme(&text, &extension)
+}
+
+/// Reads a theme in the format files with `extension` hold: Alacritty TOML
+/// or YAML, base16 YAML, a kitty `.conf`, an iTerm2 `.itermcolors` preset or
+/// a Windows Terminal scheme
+pub(crate) fn parse_theme(text: &str, extension: &str) -> Result<Theme, ThemeError> {
+    let (theme, found) = match extension {
+        "toml" => alacritty(&toml_node(text)?)?,
+        "yml" | "yaml" => {
+            let root = parse_yaml(text)?;
+            if root.get("colors").is_some() {
+                alacritty(&root)?
+            } else {
+                base16(&root)?
+            }
+        }
+        "conf" => kitty(text)?,
+        "itermcolors" => iterm2(&parse_plist(text)?)?,
+        "json" => windows_terminal(&parse_json(text)?)?,
+        _ => {
+            return Err(ThemeError(format!(
+                "unknown theme format '.{extension}', expected .toml, .yml, .yaml, .conf, \
+                 .itermcolors or .json"
+            )));
+        }
+    };
+    if found == 0 {
+        return Err(ThemeError("no colors found".to_string()));
+    }
+    Ok(theme)
+}
+
+/// Parses `#rrggbb`, `0xrrggbb` and bare `rrggbb`, the ways scheme files
+/// write colors, besides the forms `parse_color_spec` reads
+pub(crate) fn parse_hex_color(text: &str) -> Option<Color32> {
+    let text = text.trim();
+    let hex = ["#", "0x", "0X"]
+        .iter()
+        .find_map(|prefix| text.strip_prefix(prefix))
+        .unwrap_or(text);
+    if hex.len() == 6 && hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        let rgb = u32::from_str_radix(hex, 16).ok()?;
+        return Some(Color32::from_rgb(
+            (rgb >> 16) as u8,
+            (rgb >> 8) as u8,
+            rgb as u8,
+        ));
+    }
+    parse_color_spec(text)
+}
+
+// Readers return how many colors they found along with the theme, so a file
+// that sets none is an error even though the defaults make a whole theme
+fn counted<T>(found: &mut usize, value: Option<T>) -> Option<T> {
+    *found += usize::from(value.is_some());
+    value
+}
+
+/// The color under `path`, unless it's missing or one of Alacritty's
+/// `CellForeground` and `CellBackground`, which follow the cell
+fn color_at(node: &Node, path: &[&str]) -> Result<Option<Color32>, ThemeError> {
+    let Some(value) = node.at(path) else {
+        return Ok(None);
+    };
+    let text = value.as_str().unwrap_or_default();
+    if matches!(text, "CellForeground" | "CellBackground") {
+        return Ok(None);
+    }
+    match parse_hex_color(text) {
+        Some(color) => Ok(Some(color)),
+        None => Err(ThemeError(format!(
+            "`{}` is not a color: '{text}'",
+            path.join(".")
+        ))),
+    }
+}
+
+fn toml_node(text: &str) -> Result<Node, ThemeError> {
+    let document = Document::parse(text).map_err(|error| {
+        let line = error.span().map_or(1, |span| line_at(text, span.start));
+        ThemeError(format!("line {line}: {}", error.message().trim_end()))
+    })?;
+    Ok(toml_table(document.as_table()))
+}
+
+fn toml_table(table: &Table) -> Node {
+    Node::Map(
+        table
+            .iter()
+            .map(|(key, item)| (key.to_string(), toml_item(item)))
+            .collect(),
+    )
+}
+
+fn toml_item(item: &Item) -> Node {
+    match item {
+        Item::Table(table) => toml_table(table),
+        Item::ArrayOfTables(tables) => Node::List(tables.iter().map(toml_table).collect()),
+        Item::Value(value) => toml_value(value),
+        Item::None => Node::Scalar(String::new()),
+    }
+}
+
+fn toml_value(value: &Value) -> Node {
+    match value {
+        Value::String(text) => Node::Scalar(text.value().clone()),
+        Value::Array(array) => Node::List(array.iter().map(toml_value).collect()),
+        Value::InlineTable(table) => Node::Map(
+            table
+                .iter()
+                .map(|(key, value)| (key.to_string(), toml_value(value)))
+                .collect(),
+        ),
+        Value::Integer(number) => Node::Scalar(number.value().to_string()),
+        Value::Float(number) => Node::Scalar(number.value().to_string()),
+        Value::Boolean(flag) => Node::Scalar(flag.value().to_string()),
+        Value::Datetime(datetime) => Node::Scalar(datetime.value().to_string()),
+    }
+}
+
+/// Alacritty's `[colors]`, from its TOML or older YAML configuration
+fn alacritty(root: &Node) -> Result<(Theme, usize), ThemeError> {
+    let Some(colors) = root.get("colors") else {
+        return Err(ThemeError("no `colors` section".to_string()));
+    };
+    let mut theme = Theme::default();
+    let mut found = 0;
+    let mut color = |path: &[&str]| color_at(colors, path).map(|color| counted(&mut found, color));
+    theme.foreground = color(&["primary", "foreground"])?.unwrap_or(theme.foreground);
+    theme.background = color(&["primary", "background"])?.unwrap_or(theme.background);
+    theme.cursor = color(&["cursor", "cursor"])?;
+    theme.cursor_text = color(&["cursor", "text"])?;
+    theme.selection_foreground = color(&["selection", "text"])?;
+    theme.selection_background = color(&["selection", "background"])?;
+    for (offset, group) in [(0, "normal"), (8, "bright")] {
+        for (index, name) in ALACRITTY_NAMES.iter().enumerate() {
+            if let Some(color) = color(&[group, name])? {
+                theme.palette[offset + index] = color;
+            }
+        }
+    }
+    for entry in colors
+        .get("indexed_colors")
+        .and_then(Node::as_list)
+        .unwrap_or_default()
+    {
+        let index = entry
+            .get("index")
+            .and_then(Node::as_str)
+            .and_then(|index| index.parse::<u8>().ok())
+            .ok_or_else(|| {
+                ThemeError("`indexed_colors` entries need an index from 0 to 255".to_string())
+            })?;
+        if let Some(color) = counted(&mut found, color_at(entry, &["color"])?) {
+            theme.palette[usize::from(index)] = color;
+        }
+    }
+    Ok((theme, found))
+}
+
+/// `key value` lines of a kitty configuration. Keys for kitty's own tab bar
+/// and borders are passed over.
+fn kitty(text: &str) -> Result<(Theme, usize), ThemeError> {
+    let mut theme = Theme::default();
+    let mut found = 0;
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line
+            .split_once(char::is_whitespace)
+            .map_or((line, ""), |(key, value)| (key, value.trim()));
+        let color = || {
+            parse_hex_color(value).ok_or_else(|| {
+                ThemeError(format!(
+                    "line {}: `{key}` is not a color: '{value}'",
+                    index + 1
+                ))
+            })
+        };
+        // `none` and `background` leave the color to follow the cell
+        let optional = || match value {
+            "none" | "background" => Ok(None),
+            _ => color().map(Some),
+        };
+        match key {
+            "foreground" => theme.foreground = color()?,
+            "background" => theme.background = color()?,
+            "cursor" => theme.cursor = optional()?,
+            "cursor_text_color" => theme.cursor_text = optional()?,
+            "selection_foreground" => theme.selection_foreground = optional()?,
+            "selection_background" => theme.selection_background = optional()?,
+            _ => {
+                let palette_index = key
+                    .strip_prefix("color")
+                    .and_then(|number| number.parse::<u8>().ok());
+                let Some(palette_index) = palette_index else {
+                    continue;
+                };
+                theme.palette[usize::from(palette_index)] = color()?;
+            }
+        }
+        found += 1;
+    }
+    Ok((theme, found))
+}
+
+/// An iTerm2 color preset, whose colors are dicts of components from 0 to 1
+/// in the color space the dict names
+fn iterm2(root: &Node) -> Result<(Theme, usize), ThemeError> {
+    let mut found = 0;
+    let mut color = |key: &str| {
+        let Some(dict) = root.get(key) else {
+            return Ok(None);
+        };
+        let component = |name: &str| {
+            dict.get(name)
+                .and_then(Node::as_str)
+                .and_then(|value| value.parse::<f32>().ok())
+                .filter(|value| (0.0..=1.0).contains(value))
+        };
+        let (Some(r), Some(g), Some(b)) = (
+            component("Red Component"),
+            component("Green Component"),
+            component("Blue Component"),
+        ) else {
+            return Err(ThemeError(format!(
+                "`{key}` needs red, green and blue components from 0 to 1"
+            )));
+        };
+        // Presets from before iTerm2 wrote the color space are in the
+        // calibrated space, which is close enough to sRGB to take as it
+        let [r, g, b] = match dict.get("Color Space").and_then(Node::as_str) {
+            None | Some("sRGB" | "Calibrated" | "Device") => [r, g, b],
+            Some("P3") => display_p3_to_srgb([r, g, b]),
+            Some(space) => {
+                return Err(ThemeError(format!(
+                    "`{key}` is in the unknown color space '{space}'"
+                )));
+            }
+        };
+        let byte = |value: f32| (value * 255.0).round() as u8;
+        found += 1;
+        Ok(Some(Color32::from_rgb(byte(r), byte(g), byte(b))))
+    };
+    let mut theme = Theme::default();
+    for index in 0..16 {
+        if let Some(ansi) = color(&format!("Ansi {index} Color"))? {
+            theme.palette[index] = ansi;
+        }
+    }
+    theme.foreground = color("Foreground Color")?.unwrap_or(theme.foreground);
+    theme.background = color("Background Color")?.unwrap_or(theme.background);
+    theme.cursor = color("Cursor Color")?;
+    theme.cursor_text = color("Cursor Text Color")?;
+    theme.selection_foreground = color("Selected Text Color")?;
+    theme.selection_background = color("Selection Color")?;
+    Ok((theme, found))
+}
+
+/// The sRGB color closest to a Display P3 one. Both spaces share the sRGB
+/// transfer curve and white point, so the conversion is a matrix over the
+/// linear components; P3 colors outside sRGB are clipped to it.
+fn display_p3_to_srgb(rgb: [f32; 3]) -> [f32; 3] {
+    const P3_TO_SRGB: [[f32; 3]; 3] = [
+        [1.224_940_1, -0.224_940_4, 0.0],
+        [-0.042_056_9, 1.042_057_1, 0.0],
+        [-0.019_637_6, -0.078_636_1, 1.098_273_5],
+    ];
+    let to_linear = |value: f32| {
+        if value <= 0.040_45 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let from_linear = |value: f32| {
+        if value <= 0.003_130_8 {
+            value * 12.92
+        } else {
+            1.055 * value.powf(1.0 / 2.4) - 0.055
+        }
+    };
+    let linear = rgb.map(to_linear);
+    P3_TO_SRGB.map(|row| {
+        let value: f32 = row
+            .iter()
+            .zip(linear)
+            .map(|(weight, value)| weight * value)
+            .sum();
+        from_linear(value.clamp(0.0, 1.0))
+    })
+}
+
+/// A Windows Terminal color scheme, or the first of the schemes in a whole
+/// `settings.json`
+fn windows_terminal(root: &Node) -> Result<(Theme, usize), ThemeError> {
+    let scheme = match root.get("schemes") {
+        Some(schemes) => schemes
+            .as_list()
+            .and_then(<[Node]>::first)
+            .ok_or_else(|| ThemeError("`schemes` holds no color scheme".to_string()))?,
+        None => root,
+    };
+    let mut theme = Theme::default();
+    let mut found = 0;
+    let mut color = |key: &str| color_at(scheme, &[key]).map(|color| counted(&mut found, color));
+    for (index, name) in WINDOWS_TERMINAL_NAMES.iter().enumerate() {
+        if let Some(color) = color(name)? {
+            theme.palette[index] = color;
+        }
+    }
+    theme.foreground = color("foreground")?.unwrap_or(theme.foreground);
+    theme.background = color("background")?.unwrap_or(theme.background);
+    theme.cursor = color("cursorColor")?;
+    theme.selection_background = color("selectionBackground")?;
+    Ok((theme, found))
+}
+
+/// A base16 scheme: sixteen colors `base00` to `base0F`, at the top level or,
+/// in newer schemes, under `palette`
+fn base16(root: &Node) -> Result<(Theme, usize), ThemeError> {
+    let colors = root.get("palette").unwrap_or(root);
+    let mut base = [Color32::BLACK; 16];
+    for (index, slot) in base.iter_mut().enumerate() {
+        let key = format!("base{index:02X}");
+        *slot =
+            color_at(colors, &[&key])?.ok_or_else(|| ThemeError(format!("no `{key}` color")))?;
+    }
+    let mut theme = Theme::default();
+    for (index, &base_index) in BASE16_PALETTE.iter().enumerate() {
+        theme.palette[index] = base[base_index];
+    }
+    theme.foreground = base[0x5];
+    theme.background = base[0x0];
+    theme.cursor = Some(base[0x5]);
+    theme.selection_background = Some(base[0x2]);
+    Ok((theme, base.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(rgb: u32) -> Color32 {
+        Color32::from_rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+    }
+
+    #[test]
+    fn hex_colors_in_every_spelling() {
+        assert_eq!(parse_hex_color("#1d1f21"), Some(rgb(0x1d1f21)));
+        assert_eq!(parse_hex_color("0x1D1F21"), Some(rgb(0x1d1f21)));
+        assert_eq!(parse_hex_color("1d1f21"), Some(rgb(0x1d1f21)));
+        assert_eq!(parse_hex_color("#fff"), Some(Color32::WHITE));
+        assert_eq!(parse_hex_color("rgb:ff/00/00"), Some(rgb(0xff0000)));
+        assert_eq!(parse_hex_color("red"), None);
+    }
+
+    #[test]
+    fn alacritty_toml_and_yaml() {
+        let toml = parse_theme(
+            r##"
+[colors.primary]
+background = "#1d1f21"
+foreground = "#c5c8c6"
+
+[colors.cursor]
+text = "CellBackground"
+cursor = "CellForeground"
+
+[colors.selection]
+background = "#373b41"
+
+[colors.normal]
+red = "#cc6666"
+
+[colors.bright]
+red = "0xd54e53"
+
+[[colors.indexed_colors]]
+index = 16
+color = "#de935f"
+"##,
+            "toml",
+        )
+        .unwrap();
+        assert_eq!(toml.background, rgb(0x1d1f21));
+        assert_eq!(toml.foreground, rgb(0xc5c8c6));
+        assert_eq!(toml.cursor, None);
+        assert_eq!(toml.cursor_text, None);
+        assert_eq!(toml.selection_background, Some(rgb(0x373b41)));
+        assert_eq!(toml.palette[1], rgb(0xcc6666));
+        assert_eq!(toml.palette[9], rgb(0xd54e53));
+        assert_eq!(toml.palette[16], rgb(0xde935f));
+        assert_eq!(toml.palette[2], Theme::default().palette[2]);
+
+        let yaml = parse_theme(
+            "colors:\n\
+             \x20 primary:\n\
+             \x20   background: '0x1d1f21'\n\
+             \x20   foreground: '0xc5c8c6'\n\
+             \x20 selection: { text: '#ffffff', background: '#373b41' }\n\
+             \x20 normal:\n\
+             \x20   red: '0xcc6666'\n\
+             \x20 indexed_colors:\n\
+             \x20   - { index: 16, color: '0xde935f' }\n",
+            "yml",
+        )
+        .unwrap();
+        assert_eq!(yaml.background, toml.background);
+        assert_eq!(yaml.selection_foreground, Some(Color32::WHITE));
+        assert_eq!(yaml.palette[1], toml.palette[1]);
+        assert_eq!(yaml.palette[16], toml.palette[16]);
+    }
+
+    #[test]
+    fn kitty_conf() {
+        let theme = parse_theme(
+            "# vim:ft=kitty\n\
+             foreground #dcd7ba\n\
+             background  #1f1f28\n\
+             selection_foreground none\n\
+             selection_background #2d4f67\n\
+             cursor #c8c093\n\
+             active_tab_foreground #c8c093\n\
+             color1 #c34043\n\
+             color232 #080808\n",
+            "conf",
+        )
+        .unwrap();
+        assert_eq!(theme.foreground, rgb(0xdcd7ba));
+        assert_eq!(theme.background, rgb(0x1f1f28));
+        assert_eq!(theme.selection_foreground, None);
+        assert_eq!(theme.selection_background, Some(rgb(0x2d4f67)));
+        assert_eq!(theme.cursor, Some(rgb(0xc8c093)));
+        assert_eq!(theme.palette[1], rgb(0xc34043));
+        assert_eq!(theme.palette[232], rgb(0x080808));
+
+        assert_eq!(
+            parse_theme("foreground #dcd7ba\ncolor3 yellow\n", "conf")
+                .unwrap_err()
+                .to_string(),
+            "line 2: `color3` is not a color: 'yellow'"
+        );
+    }
+
+    #[test]
+    fn iterm2_preset() {
+        let component =
+            |name: &str, value: &str| format!("<key>{name} Component</key><real>{value}</real>");
+        let color = |key: &str, r: &str, g: &str, b: &str| {
+            format!(
+                "<key>{key}</key><dict>{}{}{}<key>Color Space</key><string>sRGB</string></dict>",
+                component("Red", r),
+                component("Green", g),
+                component("Blue", b)
+            )
+        };
+        let text = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<plist version=\"1.0\"><dict>{}{}{}</dict></plist>",
+            color("Ansi 1 Color", "0.8627", "0.1961", "0.1843"),
+            color("Background Color", "0", "0.1686", "0.2118"),
+            color("Selection Color", "0.0275", "0.2118", "0.2588"),
+        );
+        let theme = parse_theme(&text, "itermcolors").unwrap();
+        assert_eq!(theme.palette[1], Color32::from_rgb(220, 50, 47));
+        assert_eq!(theme.background, Color32::from_rgb(0, 43, 54));
+        assert_eq!(
+            theme.selection_background,
+            Some(Color32::from_rgb(7, 54, 66))
+        );
+        assert_eq!(theme.foreground, Theme::default().foreground);
+
+        // Display P3 colors are converted to sRGB, the same as iTerm2 shows
+        let p3 = text
+            .replace("sRGB", "P3")
+            .replace("0.8627", "0.8")
+            .replace("0.1961", "0.2")
+            .replace("0.1843", "0.2");
+        let theme = parse_theme(&p3, "itermcolors").unwrap();
+        assert_eq!(theme.palette[1], Color32::from_rgb(222, 24, 41));
+        assert_eq!(theme.background, Color32::from_rgb(0, 44, 55));
+        assert!(
+            parse_theme(&text.replace("sRGB", "Lab"), "itermcolors")
+                .unwrap_err()
+                .to_string()
+                .contains("unknown color space 'Lab'")
+        );
+
+        let broken = format!(
+            "<plist><dict>{}</dict></plist>",
+            color("Cursor Color", "1", "2", "0")
+        );
+        assert_eq!(
+            parse_theme(&broken, "itermcolors").unwrap_err().to_string(),
+            "`Cursor Color` needs red, green and blue components from 0 to 1"
+        );
+    }
+
+    #[test]
+    fn windows_terminal_schemes() {
+        let scheme = r##"{
+            "name": "Campbell",
+            "foreground": "#CCCCCC",
+            "background": "#0C0C0C",
+            "cursorColor": "#FFFFFF",
+            "selectionBackground": "#FFFFFF",
+            "purple": "#881798",
+            "brightPurple": "#B4009E"
+        }"##;
+        let theme = parse_theme(scheme, "json").unwrap();
+        assert_eq!(theme.background, rgb(0x0c0c0c));
+        assert_eq!(theme.cursor, Some(Color32::WHITE));
+        assert_eq!(theme.selection_background, Some(Color32::WHITE));
+        assert_eq!(theme.palette[5], rgb(0x881798));
+        assert_eq!(theme.palette[13], rgb(0xb4009e));
+
+        let settings = format!("{{\n  // settings.json\n  \"schemes\": [{scheme}],\n}}");
+        assert_eq!(parse_theme(&settings, "json").unwrap(), theme);
+        assert_eq!(
+            parse_theme("{\"profiles\": {}}", "json")
+                .unwrap_err()
+                .to_string(),
+            "no colors found"
+        );
+    }
+
+    #[test]
+    fn base16_schemes() {
+        let bases = (0..16)
+            .map(|index| format!("base{index:02X}: \"{:06x}\"\n", index * 0x111111))
+            .collect::<String>();
+        let theme = parse_theme(&format!("scheme: \"Gray\"\n{bases}"), "yaml").unwrap();
+        assert_eq!(theme.background, rgb(0x000000));
+        assert_eq!(theme.foreground, rgb(0x555555));
+        assert_eq!(theme.palette[1], rgb(0x888888));
+        assert_eq!(theme.palette[8], rgb(0x333333));
+        assert_eq!(theme.palette[15], rgb(0x777777));
+        assert_eq!(theme.palette[16], rgb(0x999999));
+        assert_eq!(theme.selection_background, Some(rgb(0x222222)));
+
+        let nested = bases
+            .lines()
+            .map(|line| format!("  {line}\n"))
+            .collect::<String>();
+        let newer = parse_theme(&format!("system: \"base16\"\npalette:\n{nested}"), "yaml");
+        assert_eq!(newer.unwrap(), theme);
+
+        assert_eq!(
+            parse_theme("base00: \"000000\"\n", "yaml")
+                .unwrap_err()
+                .to_string(),
+            "no `base01` color"
+        );
+        assert!(parse_theme("", "ini").is_err());
+    }
+
+    #[test]
+    fn themes_matching_the_defaults_load() {
+        let defaults = Theme::default();
+        let hex = |color: Color32| format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b);
+        let kitty = format!(
+            "foreground {}\nbackground {}\n",
+            hex(defaults.foreground),
+            hex(defaults.background)
+        );
+        assert_eq!(parse_theme(&kitty, "conf").unwrap(), defaults);
+        let toml = format!(
+            "[colors.primary]\nforeground = \"{}\"\n",
+            hex(defaults.foreground)
+        );
+        assert_eq!(parse_theme(&toml, "toml").unwrap(), defaults);
+        assert_eq!(
+            parse_theme("# nothing\nfont_size 12\n", "conf")
+                .unwrap_err()
+                .to_string(),
+            "no colors found"
+        );
+    }
+}
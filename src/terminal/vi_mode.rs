@@ -0,0 +1,455 @@
+// Vi-style copy mode: a keyboard cursor over the screen and scrollback that
+// moves with vi motions and selects text to yank. Front ends feed it keys and
+// draw its cursor; the motions only need the grid.
+
+use crate::terminal::grid::TerminalGrid;
+use crate::terminal::selection::{Point, SelectionMode};
+
+// Keeps a mistyped count from spinning through the whole scrollback
+const MAX_COUNT: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ViKey {
+    Char(char),
+    /// A letter with Ctrl held
+    Ctrl(char),
+    Escape,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ViAction {
+    None,
+    /// Put the text on the clipboard
+    Yank(String),
+    /// Leave copy mode
+    Exit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Blank,
+    Word,
+    Punctuation,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ViMode {
+    cursor: Point,
+    // Selection mode and the point the selection started at
+    visual: Option<(SelectionMode, Point)>,
+    count: Option<usize>,
+    // A 'g' waiting for the second one of "gg"
+    pending_g: bool,
+}
+
+impl ViMode {
+    /// Starts on the terminal cursor, or at the bottom left of the viewport
+    /// when the cursor is scrolled out of view
+    pub(crate) fn new(grid: &TerminalGrid) -> Self {
+        let cursor = grid.cursor_point();
+        let cursor = if grid.viewport_cell(cursor).is_some() {
+            cursor
+        } else {
+            grid.viewport_point(grid.rows() - 1, 0)
+        };
+        Self {
+            cursor,
+            visual: None,
+            count: None,
+            pending_g: false,
+        }
+    }
+
+    /// Buffer point of the copy mode cursor
+    pub(crate) fn cursor(&self) -> Point {
+        self.cursor
+    }
+
+    pub(crate) fn handle_key(&mut self, grid: &mut TerminalGrid, key: ViKey) -> ViAction {
+        self.clamp(grid);
+        if let ViKey::Char(c @ '0'..='9') = key
+            && (c != '0' || self.count.is_some())
+        {
+            let digit = c as usize - '0' as usize;
+            self.count = Some((self.count.unwrap_or(0) * 10 + digit).min(MAX_COUNT));
+            return ViAction::None;
+        }
+        let count = self.count.take().unwrap_or(1);
+        let pending_g = std::mem::take(&mut self.pending_g);
+        let rows = grid.rows();
+        match key {
+            ViKey::Escape if self.visual.is_some() => self.set_visual(grid, None),
+            ViKey::Escape | ViKey::Char('q' | 'i') => {
+                if self.visual.is_some() {
+                    grid.clear_selection();
+                }
+                return ViAction::Exit;
+            }
+            ViKey::Char('y') => {
+                let text = grid.selected_text();
+                self.set_visual(grid, None);
+                if let Some(text) = text {
+                    return ViAction::Yank(text);
+                }
+            }
+            ViKey::Char('v') => self.toggle_visual(grid, SelectionMode::Linear),
+            ViKey::Char('V') => self.toggle_visual(grid, SelectionMode::Line),
+            // Ctrl-q is vim's stand-in for Ctrl-v where that pastes
+            ViKey::Ctrl('v' | 'q') => self.toggle_visual(grid, SelectionMode::Block),
+            ViKey::Char('g') if pending_g => self.cursor = Point::new(grid.first_line(), 0),
+            ViKey::Char('g') => self.pending_g = true,
+            ViKey::Char('G') => self.cursor = Point::new(grid.last_line(), 0),
+            ViKey::Char('h') => {
+                for _ in 0..count {
+                    self.cursor = left(grid, self.cursor);
+                }
+            }
+            ViKey::Char('l') => {
+                for _ in 0..count {
+                    self.cursor = right(grid, self.cursor);
+                }
+            }
+            ViKey::Char('j') => self.move_lines(grid, count as isize),
+            ViKey::Char('k') => self.move_lines(grid, -(count as isize)),
+            ViKey::Ctrl('d') => self.move_lines(grid, (count * rows / 2) as isize),
+            ViKey::Ctrl('u') => self.move_lines(grid, -((count * rows / 2) as isize)),
+            ViKey::Ctrl('f') => self.move_lines(grid, (count * rows) as isize),
+            ViKey::Ctrl('b') => self.move_lines(grid, -((count * rows) as isize)),
+            ViKey::Char('w') => {
+                for _ in 0..count {
+                    self.cursor = word_forward(grid, self.cursor);
+                }
+            }
+            ViKey::Char('b') => {
+                for _ in 0..count {
+                    self.cursor = word_backward(grid, self.cursor);
+                }
+            }
+            ViKey::Char('e') => {
+                for _ in 0..count {
+                    self.cursor = word_end(grid, self.cursor);
+                }
+            }
+            ViKey::Char('0') => self.cursor.col = 0,
+            ViKey::Char('^') => self.cursor = first_non_blank(grid, self.cursor.line),
+            ViKey::Char('$') => self.cursor = last_non_blank(grid, self.cursor.line),
+            ViKey::Char('H') => self.cursor = grid.viewport_point(0, 0),
+            ViKey::Char('M') => self.cursor = grid.viewport_point(rows / 2, 0),
+            ViKey::Char('L') => self.cursor = grid.viewport_point(rows - 1, 0),
+            _ => {}
+        }
+        self.sync(grid);
+        ViAction::None
+    }
+
+    // Keeps the cursor and selection on lines that still exist after old
+    // ones dropped off the scrollback
+    fn clamp(&mut self, grid: &TerminalGrid) {
+        let clamp = |point: &mut Point| {
+            if point.line < grid.first_line() {
+                *point = Point::new(grid.first_line(), 0);
+            }
+        };
+        clamp(&mut self.cursor);
+        if let Some((_, anchor)) = &mut self.visual {
+            clamp(anchor);
+        }
+    }
+
+    fn move_lines(&mut self, grid: &mut TerminalGrid, lines: isize) {
+        let line = self
+            .cursor
+            .line
+            .saturating_add_signed(lines)
+            .clamp(grid.first_line(), grid.last_line());
+        self.cursor = char_start(grid, Point::new(line, self.cursor.col));
+    }
+
+    // Switches to selecting in `mode`, or stops selecting when already in it
+    fn toggle_visual(&mut self, grid: &mut TerminalGrid, mode: SelectionMode) {
+        let visual = match self.visual {
+            Some((current, _)) if current == mode => None,
+            Some((_, anchor)) => Some((mode, anchor)),
+            None => Some((mode, self.cursor)),
+        };
+        self.set_visual(grid, visual);
+    }
+
+    fn set_visual(&mut self, grid: &mut TerminalGrid, visual: Option<(SelectionMode, Point)>) {
+        if visual.is_none() && self.visual.is_some() {
+            grid.clear_selection();
+        }
+        self.visual = visual;
+    }
+
+    fn sync(&self, grid: &mut TerminalGrid) {
+        grid.scroll_to_line(self.cursor.line);
+        if let Some((mode, anchor)) = self.visual {
+            grid.select(mode, anchor, self.cursor);
+        }
+    }
+}
+
+fn class_at(grid: &mut TerminalGrid, point: Point) -> CharClass {
+    let (text, wide_continuation) = grid.buffer_cell(point);
+    match text.chars().next() {
+        _ if wide_continuation => CharClass::Word,
+        Some(c) if c.is_alphanumeric() || c == '_' => CharClass::Word,
+        Some(c) if !c.is_whitespace() => CharClass::Punctuation,
+        _ => CharClass::Blank,
+    }
+}
+
+// Moves off the right half of a wide character
+fn char_start(grid: &mut TerminalGrid, point: Point) -> Point {
+    if point.col > 0 && grid.buffer_cell(point).1 {
+        Point::new(point.line, point.col - 1)
+    } else {
+        point
+    }
+}
+
+fn left(grid: &mut TerminalGrid, point: Point) -> Point {
+    if point.col == 0 {
+        return point;
+    }
+    char_start(grid, Point::new(point.line, point.col - 1))
+}
+
+fn right(grid: &mut TerminalGrid, point: Point) -> Point {
+    let mut col = point.col + 1;
+    if col < grid.cols() && grid.buffer_cell(Point::new(point.line, col)).1 {
+        col += 1;
+    }
+    if col < grid.cols() {
+        Point::new(point.line, col)
+    } else {
+        point
+    }
+}
+
+// Words end at line ends, unless the line wraps
+fn ends_word(grid: &mut TerminalGrid, from: Point, to: Point) -> bool {
+    from.line != to.line && !grid.buffer_row_wrapped(from.line.min(to.line))
+}
+
+// The next character in reading order, across line ends
+fn next_char(grid: &mut TerminalGrid, point: Point) -> Option<Point> {
+    let next = right(grid, point);
+    if next != point {
+        Some(next)
+    } else if point.line < grid.last_line() {
+        Some(Point::new(point.line + 1, 0))
+    } else {
+        None
+    }
+}
+
+fn previous_char(grid: &mut TerminalGrid, point: Point) -> Option<Point> {
+    if point.col > 0 {
+        Some(left(grid, point))
+    } else if point.line > grid.first_line() {
+        Some(char_start(
+            grid,
+            Point::new(point.line - 1, grid.cols() - 1),
+        ))
+    } else {
+        None
+    }
+}
+
+// Start of the next word, skipping the rest of the current one and blanks
+fn word_forward(grid: &mut TerminalGrid, point: Point) -> Point {
+    let class = class_at(grid, point);
+    let mut current = point;
+    while let Some(next) = next_char(grid, current) {
+        let line_end = ends_word(grid, current, next);
+        current = next;
+        let next_class = class_at(grid, current);
+        if next_class != CharClass::Blank && (next_class != class || line_end) {
+            return current;
+        }
+        if next_class == CharClass::Blank && class != CharClass::Blank {
+            return skip_blanks(grid, current).unwrap_or(point);
+        }
+    }
+    point
+}
+
+fn skip_blanks(grid: &mut TerminalGrid, mut point: Point) -> Option<Point> {
+    while class_at(grid, point) == CharClass::Blank {
+        point = next_char(grid, point)?;
+    }
+    Some(point)
+}
+
+// End of the current word, or of the next one when already at an end
+fn word_end(grid: &mut TerminalGrid, point: Point) -> Point {
+    let Some(mut current) = next_char(grid, point) else {
+        return point;
+    };
+    let Some(start) = skip_blanks(grid, current) else {
+        return point;
+    };
+    current = start;
+    let class = class_at(grid, current);
+    while let Some(next) = next_char(grid, current)
+        && class_at(grid, next) == class
+        && !ends_word(grid, current, next)
+    {
+        current = next;
+    }
+    current
+}
+
+// Start of the current word, or of the previous one when already at a start
+fn word_backward(grid: &mut TerminalGrid, point: Point) -> Point {
+    let mut current = point;
+    loop {
+        let Some(previous) = previous_char(grid, current) else {
+            return current;
+        };
+        current = previous;
+        if class_at(grid, current) != CharClass::Blank {
+            break;
+        }
+    }
+    let class = class_at(grid, current);
+    while let Some(previous) = previous_char(grid, current)
+        && class_at(grid, previous) == class
+        && !ends_word(grid, previous, current)
+    {
+        current = previous;
+    }
+    current
+}
+
+fn first_non_blank(grid: &mut TerminalGrid, line: usize) -> Point {
+    (0..grid.cols())
+        .map(|col| Point::new(line, col))
+        .find(|&point| class_at(grid, point) != CharClass::Blank)
+        .unwrap_or(Point::new(line, 0))
+}
+
+fn last_non_blank(grid: &mut TerminalGrid, line: usize) -> Point {
+    let last = (0..grid.cols())
+        .rev()
+        .map(|col| Point::new(line, col))
+        .find(|&point| class_at(grid, point) != CharClass::Blank)
+        .unwrap_or(Point::new(line, 0));
+    char_start(grid, last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_keys(vi: &mut ViMode, grid: &mut TerminalGrid, keys: &str) -> ViAction {
+        let mut action = ViAction::None;
+        for c in keys.chars() {
+            action = vi.handle_key(grid, ViKey::Char(c));
+        }
+        action
+    }
+
+    fn grid_with(text: &str) -> TerminalGrid {
+        let mut grid = TerminalGrid::with_scrollback(12, 4, 100);
+        grid.process_pty_bytes(text.as_bytes());
+        grid
+    }
+
+    #[test]
+    fn starts_on_the_terminal_cursor() {
+        let grid = grid_with("ab\r\ncd");
+        assert_eq!(ViMode::new(&grid).cursor(), Point::new(1, 2));
+    }
+
+    #[test]
+    fn hjkl_stay_inside_the_buffer() {
+        let mut grid = grid_with("one\r\ntwo");
+        let mut vi = ViMode::new(&grid);
+        type_keys(&mut vi, &mut grid, "kkkhhhh");
+        assert_eq!(vi.cursor(), Point::new(0, 0));
+        type_keys(&mut vi, &mut grid, "3l2j");
+        assert_eq!(vi.cursor(), Point::new(2, 3));
+        type_keys(&mut vi, &mut grid, "20l");
+        assert_eq!(vi.cursor(), Point::new(2, 11));
+    }
+
+    #[test]
+    fn word_motions_cross_punctuation_and_lines() {
+        let mut grid = grid_with("foo.bar  baz\r\nqux");
+        let mut vi = ViMode::new(&grid);
+        type_keys(&mut vi, &mut grid, "gg");
+        assert_eq!(vi.cursor(), Point::new(0, 0));
+        let mut stops = Vec::new();
+        for _ in 0..4 {
+            type_keys(&mut vi, &mut grid, "w");
+            stops.push(vi.cursor().col);
+        }
+        assert_eq!(stops, [3, 4, 9, 0]);
+        assert_eq!(vi.cursor().line, 1);
+        type_keys(&mut vi, &mut grid, "b");
+        assert_eq!(vi.cursor(), Point::new(0, 9));
+        type_keys(&mut vi, &mut grid, "0e");
+        assert_eq!(vi.cursor(), Point::new(0, 2));
+        type_keys(&mut vi, &mut grid, "$");
+        assert_eq!(vi.cursor(), Point::new(0, 11));
+    }
+
+    #[test]
+    fn visual_modes_yank_what_they_cover() {
+        let mut grid = grid_with("hello world\r\nsecond line");
+        let mut vi = ViMode::new(&grid);
+        type_keys(&mut vi, &mut grid, "ggvl");
+        assert_eq!(
+            type_keys(&mut vi, &mut grid, "ey"),
+            ViAction::Yank("hello".to_string())
+        );
+        assert!(!grid.is_selected(0, 0));
+
+        type_keys(&mut vi, &mut grid, "0V");
+        assert_eq!(
+            type_keys(&mut vi, &mut grid, "y"),
+            ViAction::Yank("hello world".to_string())
+        );
+
+        type_keys(&mut vi, &mut grid, "0");
+        vi.handle_key(&mut grid, ViKey::Ctrl('v'));
+        assert_eq!(
+            type_keys(&mut vi, &mut grid, "jly"),
+            ViAction::Yank("he\nse".to_string())
+        );
+
+        type_keys(&mut vi, &mut grid, "0");
+        vi.handle_key(&mut grid, ViKey::Ctrl('q'));
+        assert_eq!(
+            type_keys(&mut vi, &mut grid, "kly"),
+            ViAction::Yank("he\nse".to_string())
+        );
+    }
+
+    #[test]
+    fn escape_drops_the_selection_before_leaving() {
+        let mut grid = grid_with("text");
+        let mut vi = ViMode::new(&grid);
+        type_keys(&mut vi, &mut grid, "0v");
+        assert!(grid.is_selected(0, 0));
+        assert_eq!(vi.handle_key(&mut grid, ViKey::Escape), ViAction::None);
+        assert!(!grid.is_selected(0, 0));
+        assert_eq!(vi.handle_key(&mut grid, ViKey::Escape), ViAction::Exit);
+    }
+
+    #[test]
+    fn moving_into_the_scrollback_scrolls_the_view() {
+        let mut grid = grid_with("0\r\n1\r\n2\r\n3\r\n4\r\n5\r\n6");
+        let mut vi = ViMode::new(&grid);
+        assert_eq!(grid.first_line(), 0);
+        type_keys(&mut vi, &mut grid, "gg");
+        assert_eq!(grid.scroll_offset(), 3);
+        type_keys(&mut vi, &mut grid, "G");
+        assert_eq!(grid.scroll_offset(), 0);
+        type_keys(&mut vi, &mut grid, "4k");
+        assert_eq!(grid.scroll_offset(), 1);
+    }
+}